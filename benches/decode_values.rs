@@ -0,0 +1,66 @@
+//! Times decoding of large SHORT and LONG arrays, in both byte orders.
+//! Run with `cargo bench --bench decode_values`.
+
+extern crate lazytiff;
+
+use std::io::Cursor;
+use std::time::Instant;
+
+use lazytiff::{Endianness, TiffReader};
+
+const COUNT: u32 = 1 << 20;
+const ROUNDS: u32 = 20;
+
+/* A single IFD with a SHORT entry (tag 65000) and a LONG entry (tag
+ * 65001), both holding COUNT values stored after the IFD. */
+fn tiff_bytes(endianness: Endianness) -> Vec<u8> {
+    let u16_bytes = |value: u16| match endianness {
+        Endianness::Little => value.to_le_bytes().to_vec(),
+        Endianness::Big => value.to_be_bytes().to_vec(),
+    };
+    let u32_bytes = |value: u32| match endianness {
+        Endianness::Little => value.to_le_bytes().to_vec(),
+        Endianness::Big => value.to_be_bytes().to_vec(),
+    };
+    
+    let shorts_offset = 8 + 2 + 2*12 + 4;
+    let longs_offset = shorts_offset + 2*COUNT;
+    let mut bytes = match endianness {
+        Endianness::Little => b"II\x2A\x00".to_vec(),
+        Endianness::Big => b"MM\x00\x2A".to_vec(),
+    };
+    bytes.extend(u32_bytes(8));
+    bytes.extend(u16_bytes(2));
+    for &(tag, field_type, offset) in &[(65000, 3, shorts_offset), (65001, 4, longs_offset)] {
+        bytes.extend(u16_bytes(tag));
+        bytes.extend(u16_bytes(field_type));
+        bytes.extend(u32_bytes(COUNT));
+        bytes.extend(u32_bytes(offset));
+    }
+    bytes.extend(u32_bytes(0));
+    bytes.extend((0..COUNT).flat_map(|value| u16_bytes(value as u16)));
+    bytes.extend((0..COUNT).flat_map(&u32_bytes));
+    bytes
+}
+
+fn main() {
+    for &endianness in &[Endianness::Little, Endianness::Big] {
+        let mut tiff_reader = TiffReader::new(Cursor::new(tiff_bytes(endianness))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        for &(tag, name) in &[(65000, "SHORT"), (65001, "LONG")] {
+            let field = subfile.get_field_mut(tag).unwrap();
+            /* Warm up, so the first timed round doesn't pay for page
+             * faults. */
+            field.load().unwrap();
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                field.unload();
+                field.load().unwrap();
+            }
+            let per_value = start.elapsed().as_secs_f64()*1e9/f64::from(ROUNDS*COUNT);
+            println!("{:?} {}: {:.2} ns per value", endianness, name, per_value);
+        }
+    }
+}
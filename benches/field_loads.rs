@@ -0,0 +1,62 @@
+//! Times loading many small out-of-line field values, which is dominated
+//! by the per-read overhead of the shared reader, once with `TiffReader`
+//! (`Arc<Mutex<_>>`) and once with `LocalTiffReader` (`Rc<RefCell<_>>`).
+//! Run with `cargo bench --bench field_loads`.
+
+extern crate lazytiff;
+
+use std::io::Cursor;
+use std::time::Instant;
+
+use lazytiff::{LocalTiffReader, Sharing, TiffReader};
+
+const FIELD_COUNT: u16 = 4096;
+const ROUNDS: u32 = 200;
+
+/* A single little-endian IFD with FIELD_COUNT RATIONAL entries (tags
+ * 1000 and up), each holding one value stored after the IFD. */
+fn tiff_bytes() -> Vec<u8> {
+    let values_offset = 8 + 2 + 12*u32::from(FIELD_COUNT) + 4;
+    let mut bytes = b"II\x2A\x00".to_vec();
+    bytes.extend(&8u32.to_le_bytes());
+    bytes.extend(&FIELD_COUNT.to_le_bytes());
+    for i in 0..FIELD_COUNT {
+        bytes.extend(&(1000 + i).to_le_bytes());
+        bytes.extend(&5u16.to_le_bytes());
+        bytes.extend(&1u32.to_le_bytes());
+        bytes.extend(&(values_offset + 8*u32::from(i)).to_le_bytes());
+    }
+    bytes.extend(&0u32.to_le_bytes());
+    for i in 0..u32::from(FIELD_COUNT) {
+        bytes.extend(&i.to_le_bytes());
+        bytes.extend(&1u32.to_le_bytes());
+    }
+    bytes
+}
+
+/* Returns the mean time in nanoseconds to unload and reload one value. */
+fn time_field_loads<S: Sharing>(tiff_reader: &mut TiffReader<Cursor<Vec<u8>>, S>) -> f64 {
+    tiff_reader.read_all_ifds().unwrap();
+    let subfile = &mut tiff_reader.subfiles[0];
+    let tags: Vec<u16> = (1000..1000 + FIELD_COUNT).collect();
+    
+    /* Warm up, so the first timed round doesn't pay for page faults. */
+    subfile.load_all_field_values().unwrap();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for &tag in &tags {
+            let field = subfile.get_field_mut(tag).unwrap();
+            field.unload();
+            field.load().unwrap();
+        }
+    }
+    start.elapsed().as_secs_f64()*1e9/f64::from(ROUNDS*u32::from(FIELD_COUNT))
+}
+
+fn main() {
+    let mut tiff_reader = TiffReader::new(Cursor::new(tiff_bytes())).unwrap();
+    println!("Arc<Mutex<_>>: {:.1} ns per field load", time_field_loads(&mut tiff_reader));
+    
+    let mut local_tiff_reader = LocalTiffReader::new(Cursor::new(tiff_bytes())).unwrap();
+    println!("Rc<RefCell<_>>: {:.1} ns per field load", time_field_loads(&mut local_tiff_reader));
+}
@@ -90,7 +90,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tag_names.insert(532, "ReferenceBlackWhite".to_string());
     tag_names.insert(33432, "Copyright".to_string());
     
-    let mut all_tags: Vec<u16> = tag_names.keys().map(|tag| *tag).collect();
+    let mut all_tags: Vec<u16> = tag_names.keys().copied().collect();
     all_tags.sort();
     
     println!("{}", filename.file_name().unwrap().to_string_lossy());
@@ -0,0 +1,81 @@
+extern crate lazytiff;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+use lazytiff::TiffReader;
+use lazytiff::error::TiffReadError;
+
+// Usage: tiffcheck <directory>
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    
+    let directory = Path::new(&args[1]);
+    
+    let mut paths: Vec<_> = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    
+    let mut ok_count = 0;
+    let mut warning_count = 0;
+    let mut error_counts: BTreeMap<String, usize> = BTreeMap::new();
+    
+    println!("{:<40} {:>8} {:>8}  STATUS", "FILE", "SUBFILES", "WARNINGS");
+    for path in &paths {
+        let name = path.file_name().unwrap().to_string_lossy();
+        match check_file(path) {
+            Ok((num_subfiles, num_warnings)) => {
+                ok_count += 1;
+                warning_count += num_warnings;
+                let status = if num_warnings == 0 {"ok"} else {"ok with warnings"};
+                println!("{:<40} {:>8} {:>8}  {}", name, num_subfiles, num_warnings, status);
+            }
+            Err(err) => {
+                *error_counts.entry(error_kind(&err)).or_insert(0) += 1;
+                println!("{:<40} {:>8} {:>8}  error: {}", name, "-", "-", err);
+            }
+        }
+    }
+    
+    println!();
+    println!("{} files, {} read without errors, {} warnings", paths.len(), ok_count, warning_count);
+    for (kind, count) in &error_counts {
+        println!("{:>6}  {}", count, kind);
+    }
+    
+    Ok(())
+}
+
+/* Reads every IFD and loads every field value in lenient mode, returning
+ * the number of subfiles and of integrity warnings. */
+fn check_file(path: &Path) -> Result<(usize, usize), TiffReadError> {
+    let tiff_file = File::open(path)?;
+    let stream_len = tiff_file.metadata()?.len();
+    let mut tiff_reader = TiffReader::new(tiff_file)?;
+    tiff_reader.set_strict(false);
+    tiff_reader.read_all_ifds()?;
+    
+    let mut num_warnings = tiff_reader.sanity_check()?.len();
+    for (index, subfile) in tiff_reader.subfiles.iter_mut().enumerate() {
+        num_warnings += subfile.check_value_region_integrity(stream_len).len();
+        subfile.load_all_field_values().map_err(|err| TiffReadError::InSubfile {index, source: Box::new(err)})?;
+    }
+    
+    Ok((tiff_reader.subfiles.len(), num_warnings))
+}
+
+/* The name of the innermost error variant, so that e.g. an out-of-bounds
+ * read is counted the same whichever field or subfile it happened in. */
+fn error_kind(err: &TiffReadError) -> String {
+    match err {
+        TiffReadError::InField {source, ..} | TiffReadError::InSubfile {source, ..} => error_kind(source),
+        TiffReadError::Io(io_error) => format!("Io({:?})", io_error.kind()),
+        _ => {
+            let debug = format!("{:?}", err);
+            debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default().to_string()
+        }
+    }
+}
@@ -0,0 +1,692 @@
+use std::convert::TryFrom;
+use std::io::{Read, Seek};
+
+use crate::compression::Compression;
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+
+/// The NewSubfileType (254) bit field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct NewSubfileType(u32);
+
+impl NewSubfileType {
+    /// Bit 0: reduced-resolution version of another image in the file.
+    pub const REDUCED_RESOLUTION: NewSubfileType = NewSubfileType(1);
+    /// Bit 1: single page of a multi-page image.
+    pub const PAGE: NewSubfileType = NewSubfileType(2);
+    /// Bit 2: transparency mask for another image in the file.
+    pub const MASK: NewSubfileType = NewSubfileType(4);
+    
+    pub fn empty() -> Self {
+        NewSubfileType(0)
+    }
+    
+    /// Keeps all bits, including ones not defined in TIFF 6.0.
+    pub fn from_bits(bits: u32) -> Self {
+        NewSubfileType(bits)
+    }
+    
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+    
+    pub fn contains(self, other: NewSubfileType) -> bool {
+        self.0 & other.0 == other.0
+    }
+    
+    pub fn is_reduced_resolution(self) -> bool {
+        self.contains(Self::REDUCED_RESOLUTION)
+    }
+    
+    pub fn is_page(self) -> bool {
+        self.contains(Self::PAGE)
+    }
+    
+    pub fn is_mask(self) -> bool {
+        self.contains(Self::MASK)
+    }
+}
+
+/// A SubfileType (255) value. The field is deprecated in favor of
+/// NewSubfileType (254), but still found in older files.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum LegacySubfileType {
+    /// Full-resolution image data.
+    FullResolution,
+    /// Reduced-resolution version of another image in the file.
+    ReducedResolution,
+    /// Single page of a multi-page image.
+    Page,
+    /// A value not defined in TIFF 6.0.
+    Other(u16),
+}
+
+impl LegacySubfileType {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => LegacySubfileType::FullResolution,
+            2 => LegacySubfileType::ReducedResolution,
+            3 => LegacySubfileType::Page,
+            other => LegacySubfileType::Other(other),
+        }
+    }
+}
+
+/// A SampleFormat (339) value.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SampleFormat {
+    Unsigned,
+    Signed,
+    Float,
+    Undefined,
+    /// A value not defined in TIFF 6.0.
+    Other(u16),
+}
+
+impl SampleFormat {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => SampleFormat::Unsigned,
+            2 => SampleFormat::Signed,
+            3 => SampleFormat::Float,
+            4 => SampleFormat::Undefined,
+            other => SampleFormat::Other(other),
+        }
+    }
+}
+
+/// A Threshholding (263) value.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Thresholding {
+    /// No dithering or halftoning has been applied.
+    NoDithering,
+    OrderedDither,
+    ErrorDiffusion,
+    /// A value not defined in TIFF 6.0.
+    Other(u16),
+}
+
+impl Thresholding {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => Thresholding::NoDithering,
+            2 => Thresholding::OrderedDither,
+            3 => Thresholding::ErrorDiffusion,
+            other => Thresholding::Other(other),
+        }
+    }
+}
+
+/// How an alpha sample relates to the color samples, from ExtraSamples
+/// (338).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum AlphaKind {
+    /// The color samples have been multiplied by alpha
+    /// (premultiplied).
+    Associated,
+    Unassociated,
+}
+
+/// The kind of image stored in a subfile, as determined by
+/// `Subfile::image_kind`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ImageKind {
+    Bilevel,
+    Grayscale {bits: u16},
+    Palette {bits: u16},
+    Rgb,
+    /// RGB with a single extra sample holding alpha, as declared by
+    /// ExtraSamples.
+    Rgba {alpha: AlphaKind},
+    Cmyk,
+    YCbCr,
+    /// Any other combination, e.g. CIELab or separated inks other than
+    /// CMYK.
+    Other,
+}
+
+/* Accessors for baseline fields. Where the TIFF 6.0 spec defines a
+ * default value, it is returned when the field is absent. */
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    pub fn image_width(&mut self) -> Result<u32, TiffReadError> {
+        self.get_u32(tags::IMAGE_WIDTH)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::IMAGE_WIDTH})
+    }
+    
+    pub fn image_length(&mut self) -> Result<u32, TiffReadError> {
+        self.get_u32(tags::IMAGE_LENGTH)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::IMAGE_LENGTH})
+    }
+    
+    /// Returns SamplesPerPixel, which is never 0.
+    pub fn samples_per_pixel(&mut self) -> Result<u16, TiffReadError> {
+        let samples_per_pixel = self.get_u32(tags::SAMPLES_PER_PIXEL)?.unwrap_or(1);
+        match u16::try_from(samples_per_pixel) {
+            Ok(samples_per_pixel) if samples_per_pixel > 0 => Ok(samples_per_pixel),
+            _ => Err(ParseError::new(format!("Invalid SamplesPerPixel: {}", samples_per_pixel)).into()),
+        }
+    }
+    
+    /// Returns Compression, or `Compression::None` if absent.
+    pub fn compression(&mut self) -> Result<Compression, TiffReadError> {
+        let compression = self.get_u32(tags::COMPRESSION)?.unwrap_or(1);
+        u16::try_from(compression).map(Compression::from_u16).map_err(|_| ParseError::new(format!("Invalid Compression: {}", compression)).into())
+    }
+    
+    /// Returns one BitsPerSample value per sample, so the result is
+    /// never empty. A single value is taken to apply to all samples,
+    /// since some writers only store one.
+    pub fn bits_per_sample(&mut self) -> Result<Vec<u16>, TiffReadError> {
+        let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+        let values = self.get_u32_values(tags::BITS_PER_SAMPLE)?.unwrap_or_else(|| vec![1]);
+        
+        let bits_per_sample = values.iter()
+            .map(|&bits| u16::try_from(bits).map_err(|_| ParseError::new(format!("Invalid BitsPerSample: {}", bits))))
+            .collect::<Result<Vec<u16>, _>>()?;
+        
+        match bits_per_sample.len() {
+            1 => Ok(vec![bits_per_sample[0]; samples_per_pixel]),
+            len if len >= samples_per_pixel => Ok(bits_per_sample[..samples_per_pixel].to_vec()),
+            len => Err(ParseError::new(format!("Expected {} BitsPerSample values, found {}", samples_per_pixel, len)).into()),
+        }
+    }
+    
+    /// Returns RowsPerStrip, clamped to ImageLength (the default of
+    /// 2**32 - 1 means the whole image is a single strip).
+    pub fn rows_per_strip(&mut self) -> Result<u32, TiffReadError> {
+        let image_length = self.image_length()?;
+        let rows_per_strip = self.get_u32(tags::ROWS_PER_STRIP)?.unwrap_or(u32::MAX);
+        Ok(rows_per_strip.min(image_length))
+    }
+    
+    /// Returns PlanarConfiguration: 1 (chunky) or 2 (planar).
+    pub fn planar_configuration(&mut self) -> Result<u16, TiffReadError> {
+        match self.get_u32(tags::PLANAR_CONFIGURATION)?.unwrap_or(1) {
+            1 => Ok(1),
+            2 => Ok(2),
+            other => Err(ParseError::new(format!("Invalid PlanarConfiguration: {}", other)).into()),
+        }
+    }
+    
+    pub fn predictor(&mut self) -> Result<u16, TiffReadError> {
+        let predictor = self.get_u32(tags::PREDICTOR)?.unwrap_or(1);
+        u16::try_from(predictor).map_err(|_| ParseError::new(format!("Invalid Predictor: {}", predictor)).into())
+    }
+    
+    /// Returns (MinSampleValue, MaxSampleValue) for each sample. Absent
+    /// fields default to 0 and 2**BitsPerSample - 1, and a single value
+    /// is taken to apply to all samples.
+    pub fn sample_value_range(&mut self) -> Result<Vec<(u32, u32)>, TiffReadError> {
+        let bits_per_sample = self.bits_per_sample()?;
+        let samples_per_pixel = bits_per_sample.len();
+        let per_sample = |values: Option<Vec<u32>>, name: &str| match values {
+            Some(ref values) if values.len() == 1 => Ok(Some(vec![values[0]; samples_per_pixel])),
+            Some(values) if values.len() >= samples_per_pixel => Ok(Some(values)),
+            Some(values) => Err(ParseError::new(format!("Expected {} {} values, found {}", samples_per_pixel, name, values.len()))),
+            None => Ok(None),
+        };
+        
+        let min_values = per_sample(self.get_u32_values(tags::MIN_SAMPLE_VALUE)?, "MinSampleValue")?;
+        let max_values = per_sample(self.get_u32_values(tags::MAX_SAMPLE_VALUE)?, "MaxSampleValue")?;
+        
+        Ok(bits_per_sample.iter().enumerate().map(|(i, &bits)| {
+            let min = min_values.as_ref().map_or(0, |values| values[i]);
+            let max = max_values.as_ref().map_or_else(|| 1u32.checked_shl(u32::from(bits)).map_or(u32::MAX, |limit| limit - 1), |values| values[i]);
+            (min, max)
+        }).collect())
+    }
+    
+    /// Returns (SMinSampleValue, SMaxSampleValue) for each sample, for
+    /// signed or floating point samples. Since these fields can have any
+    /// numeric type, the values are converted to `f64`. There are no
+    /// defaults; `None` is returned unless both fields are present.
+    pub fn s_sample_value_range(&mut self) -> Result<Option<Vec<(f64, f64)>>, TiffReadError> {
+        let mut load = |tag: u16| -> Result<Option<Vec<f64>>, TiffReadError> {
+            match self.get_value(tag)? {
+                Some(value) => value.to_f64_vec().map(Some).ok_or(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+                None => Ok(None),
+            }
+        };
+        let min_values = load(tags::S_MIN_SAMPLE_VALUE)?;
+        let max_values = load(tags::S_MAX_SAMPLE_VALUE)?;
+        
+        match (min_values, max_values) {
+            (Some(min_values), Some(max_values)) => {
+                let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+                let value_at = |values: &[f64], i: usize| if values.len() == 1 {values[0]} else {values[i]};
+                if [&min_values, &max_values].iter().any(|values| values.len() != 1 && values.len() < samples_per_pixel) {
+                    return Err(ParseError::new(format!("Expected {} SMinSampleValue and SMaxSampleValue values", samples_per_pixel)).into());
+                }
+                Ok(Some((0..samples_per_pixel).map(|i| (value_at(&min_values, i), value_at(&max_values, i))).collect()))
+            }
+            _ => Ok(None),
+        }
+    }
+    
+    /// Returns one SampleFormat per sample. Per the spec, samples without
+    /// a value are unsigned integers, so an absent field or a missing
+    /// trailing entry is filled in with `SampleFormat::Unsigned`. As for
+    /// BitsPerSample, a single value is taken to apply to all samples.
+    ///
+    /// Use `sample_format_if_present` to avoid guessing.
+    pub fn sample_format(&mut self) -> Result<Vec<SampleFormat>, TiffReadError> {
+        let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+        let mut sample_format = self.sample_format_if_present()?.unwrap_or_default();
+        
+        if sample_format.len() == 1 {
+            sample_format = vec![sample_format[0]; samples_per_pixel];
+        }
+        sample_format.resize(samples_per_pixel, SampleFormat::Unsigned);
+        Ok(sample_format)
+    }
+    
+    /// Returns the SampleFormat values as stored, or `None` if the field
+    /// is absent.
+    pub fn sample_format_if_present(&mut self) -> Result<Option<Vec<SampleFormat>>, TiffReadError> {
+        let values = self.get_u32_values(tags::SAMPLE_FORMAT)?;
+        values.map(|values| values.iter().map(|&value| {
+            u16::try_from(value).map(SampleFormat::from_u16).map_err(|_| ParseError::new(format!("Invalid SampleFormat: {}", value)).into())
+        }).collect()).transpose()
+    }
+    
+    /// Returns NewSubfileType. If it is absent, the flags are derived
+    /// from the deprecated SubfileType instead, and if both are absent,
+    /// an empty set of flags is returned.
+    pub fn new_subfile_type(&mut self) -> Result<NewSubfileType, TiffReadError> {
+        if let Some(bits) = self.get_u32(tags::NEW_SUBFILE_TYPE)? {
+            return Ok(NewSubfileType::from_bits(bits));
+        }
+        let flags = match self.subfile_type()? {
+            Some(LegacySubfileType::ReducedResolution) => NewSubfileType::REDUCED_RESOLUTION,
+            Some(LegacySubfileType::Page) => NewSubfileType::PAGE,
+            _ => NewSubfileType::empty(),
+        };
+        Ok(flags)
+    }
+    
+    /// Returns the deprecated SubfileType field.
+    pub fn subfile_type(&mut self) -> Result<Option<LegacySubfileType>, TiffReadError> {
+        Ok(self.get_u16_field(tags::SUBFILE_TYPE, "SubfileType")?.map(LegacySubfileType::from_u16))
+    }
+    
+    /// Classifies the image from PhotometricInterpretation,
+    /// SamplesPerPixel, BitsPerSample and, for RGBA, ExtraSamples.
+    /// PhotometricInterpretation has no
+    /// default, so when it is missing the image is taken to be bilevel at
+    /// 1 bit per sample, RGB with 3 or more samples and grayscale
+    /// otherwise.
+    pub fn image_kind(&mut self) -> Result<ImageKind, TiffReadError> {
+        let samples_per_pixel = self.samples_per_pixel()?;
+        let bits = self.bits_per_sample()?.first().cloned().unwrap_or(1);
+        let photometric_interpretation = self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)?;
+        
+        let kind = match photometric_interpretation {
+            Some(0) | Some(1) if bits == 1 => ImageKind::Bilevel,
+            Some(0) | Some(1) => ImageKind::Grayscale {bits},
+            Some(2) if samples_per_pixel == 4 => match self.get_u32_values(tags::EXTRA_SAMPLES)?.unwrap_or_default()[..] {
+                [1] => ImageKind::Rgba {alpha: AlphaKind::Associated},
+                [2] => ImageKind::Rgba {alpha: AlphaKind::Unassociated},
+                _ => ImageKind::Rgb,
+            },
+            Some(2) if samples_per_pixel >= 3 => ImageKind::Rgb,
+            Some(3) => ImageKind::Palette {bits},
+            Some(5) => match self.get_u32(tags::INK_SET)?.unwrap_or(1) {
+                1 if samples_per_pixel >= 4 => ImageKind::Cmyk,
+                _ => ImageKind::Other,
+            },
+            Some(6) if samples_per_pixel >= 3 => ImageKind::YCbCr,
+            Some(_) => ImageKind::Other,
+            None if bits == 1 => ImageKind::Bilevel,
+            None if samples_per_pixel >= 3 => ImageKind::Rgb,
+            None => ImageKind::Grayscale {bits},
+        };
+        Ok(kind)
+    }
+    
+    /// Returns Threshholding as stored, or `None` if absent (in which
+    /// case the spec default is `Thresholding::NoDithering`).
+    pub fn thresholding(&mut self) -> Result<Option<Thresholding>, TiffReadError> {
+        Ok(self.get_u16_field(tags::THRESHHOLDING, "Threshholding")?.map(Thresholding::from_u16))
+    }
+    
+    /// Returns CellWidth, the width of the dithering or halftoning
+    /// matrix, or `None` if absent.
+    pub fn cell_width(&mut self) -> Result<Option<u16>, TiffReadError> {
+        self.get_u16_field(tags::CELL_WIDTH, "CellWidth")
+    }
+    
+    /// Returns CellLength, the height of the dithering or halftoning
+    /// matrix, or `None` if absent.
+    pub fn cell_length(&mut self) -> Result<Option<u16>, TiffReadError> {
+        self.get_u16_field(tags::CELL_LENGTH, "CellLength")
+    }
+    
+    pub(crate) fn get_u16_field(&mut self, tag: u16, name: &str) -> Result<Option<u16>, TiffReadError> {
+        match self.get_u32(tag)? {
+            Some(value) => u16::try_from(value).map(Some).map_err(|_| ParseError::new(format!("Invalid {}: {}", name, value)).into()),
+            None => Ok(None),
+        }
+    }
+    
+    pub(crate) fn get_u16_values(&mut self, tag: u16, name: &str) -> Result<Option<Vec<u16>>, TiffReadError> {
+        let values = match self.get_u32_values(tag)? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        values.iter()
+            .map(|&value| u16::try_from(value).map_err(|_| ParseError::new(format!("Invalid {} value: {}", name, value)).into()))
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+    
+    /// Returns the unused regions described by FreeOffsets and
+    /// FreeByteCounts as (offset, length) pairs, or `None` if neither
+    /// field is present.
+    pub fn free_regions(&mut self) -> Result<Option<Vec<(u64, u64)>>, TiffReadError> {
+        let offsets = self.get_u32_values(tags::FREE_OFFSETS)?;
+        let byte_counts = self.get_u32_values(tags::FREE_BYTE_COUNTS)?;
+        match (offsets, byte_counts) {
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(TiffReadError::MissingRequiredTag {tag: tags::FREE_BYTE_COUNTS}),
+            (None, Some(_)) => Err(TiffReadError::MissingRequiredTag {tag: tags::FREE_OFFSETS}),
+            (Some(offsets), Some(byte_counts)) => {
+                if offsets.len() != byte_counts.len() {
+                    return Err(ParseError::new(format!("{} FreeOffsets but {} FreeByteCounts", offsets.len(), byte_counts.len())).into());
+                }
+                Ok(Some(offsets.iter().zip(&byte_counts).map(|(&offset, &byte_count)| (u64::from(offset), u64::from(byte_count))).collect()))
+            }
+        }
+    }
+    
+    /// Checks that the fields required for the image's
+    /// PhotometricInterpretation by the baseline tables of the TIFF 6.0
+    /// spec (section 8) are all present, returning
+    /// `TiffReadError::MissingRequiredTag` for the first one that isn't.
+    /// This includes fields with a default value, such as Compression and
+    /// ResolutionUnit, and the strip fields, since tiles aren't baseline.
+    ///
+    /// BitsPerSample is only required for RGB and palette images, as its
+    /// default of 1 makes a bilevel image. Photometric interpretations
+    /// from the extensions are held to the RGB requirements.
+    pub fn validate_baseline(&mut self) -> Result<(), TiffReadError> {
+        let photometric_interpretation = self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)?
+            .ok_or(TiffReadError::MissingRequiredTag {tag: tags::PHOTOMETRIC_INTERPRETATION})?;
+        
+        let (needs_bits_per_sample, needs_samples_per_pixel, needs_color_map) = match photometric_interpretation {
+            0 | 1 => (false, false, false),
+            3 => (true, false, true),
+            _ => (true, true, false),
+        };
+        let required = [
+            (tags::IMAGE_WIDTH, true),
+            (tags::IMAGE_LENGTH, true),
+            (tags::BITS_PER_SAMPLE, needs_bits_per_sample),
+            (tags::COMPRESSION, true),
+            (tags::STRIP_OFFSETS, true),
+            (tags::SAMPLES_PER_PIXEL, needs_samples_per_pixel),
+            (tags::ROWS_PER_STRIP, true),
+            (tags::STRIP_BYTE_COUNTS, true),
+            (tags::X_RESOLUTION, true),
+            (tags::Y_RESOLUTION, true),
+            (tags::RESOLUTION_UNIT, true),
+            (tags::COLOR_MAP, needs_color_map),
+        ];
+        match required.iter().find(|&&(tag, is_required)| is_required && !self.contains(tag)) {
+            Some(&(tag, _)) => Err(TiffReadError::MissingRequiredTag {tag}),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlphaKind, ImageKind, LegacySubfileType, NewSubfileType, SampleFormat, Thresholding};
+    use crate::compression::Compression;
+    use crate::error::TiffReadError;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn new_subfile_type_flags() {
+        let flags = NewSubfileType::from_bits(0b101);
+        assert!(flags.is_reduced_resolution());
+        assert!(!flags.is_page());
+        assert!(flags.is_mask());
+        assert_eq!(NewSubfileType::default(), NewSubfileType::empty());
+        assert!(!NewSubfileType::empty().is_page());
+    }
+    
+    #[test]
+    fn fall_back_to_legacy_subfile_type() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(255, FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(255, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(254, FieldValue::Long(vec![0]))
+            .entry(255, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .ifd()
+            .entry(255, FieldValue::Long(vec![70000]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].subfile_type().unwrap(), Some(LegacySubfileType::ReducedResolution));
+        assert!(tiff_reader.subfiles[0].new_subfile_type().unwrap().is_reduced_resolution());
+        assert!(tiff_reader.subfiles[1].new_subfile_type().unwrap().is_page());
+        assert_eq!(tiff_reader.subfiles[2].new_subfile_type().unwrap(), NewSubfileType::empty());
+        assert_eq!(tiff_reader.subfiles[3].subfile_type().unwrap(), None);
+        assert_eq!(tiff_reader.subfiles[3].new_subfile_type().unwrap(), NewSubfileType::empty());
+        assert!(tiff_reader.subfiles[4].subfile_type().is_err());
+    }
+    
+    #[test]
+    fn sample_value_ranges() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(258, FieldValue::Short(vec![8, 16]))
+            .entry(277, FieldValue::Short(vec![2]))
+            .entry(280, FieldValue::Short(vec![10]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![32]))
+            .entry(340, FieldValue::Float(vec![-1.5]))
+            .entry(341, FieldValue::Float(vec![2.5]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].sample_value_range().unwrap(), vec![(10, 255), (10, 65535)]);
+        assert_eq!(tiff_reader.subfiles[0].s_sample_value_range().unwrap(), None);
+        assert_eq!(tiff_reader.subfiles[1].sample_value_range().unwrap(), vec![(0, u32::MAX)]);
+        assert_eq!(tiff_reader.subfiles[1].s_sample_value_range().unwrap(), Some(vec![(-1.5, 2.5)]));
+    }
+    
+    #[test]
+    fn typed_s_sample_value_ranges() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Big)
+            .entry(277, FieldValue::Short(vec![2]))
+            .entry(339, FieldValue::Short(vec![2, 2]))
+            .entry(340, FieldValue::SLong(vec![-40000, 0]))
+            .entry(341, FieldValue::SLong(vec![0, 40000]))
+            .ifd()
+            .entry(339, FieldValue::Short(vec![3]))
+            .entry(340, FieldValue::Double(vec![-0.0]))
+            .entry(341, FieldValue::Double(vec![8848.86]))
+            .ifd()
+            .entry(340, FieldValue::SShort(vec![-1]))
+            .ifd()
+            .entry(340, FieldValue::Ascii("-1".to_string()))
+            .entry(341, FieldValue::SShort(vec![1]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].s_sample_value_range().unwrap(), Some(vec![(-40000.0, 0.0), (0.0, 40000.0)]));
+        assert_eq!(tiff_reader.subfiles[1].s_sample_value_range().unwrap(), Some(vec![(-0.0, 8848.86)]));
+        assert_eq!(tiff_reader.subfiles[2].s_sample_value_range().unwrap(), None);
+        match tiff_reader.subfiles[3].s_sample_value_range() {
+            Err(TiffReadError::TypeMismatch {tag: 340, ..}) => {},
+            other => panic!("Expected TypeMismatch, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn reject_zero_samples_per_pixel() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(277, FieldValue::Short(vec![0]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![]))
+            .reader();
+        
+        for subfile in &mut tiff_reader.subfiles {
+            match subfile.bits_per_sample() {
+                Err(TiffReadError::Parse(_)) => {},
+                other => panic!("Expected ParseError, got {:?}", other),
+            }
+        }
+        assert!(tiff_reader.subfiles[0].samples_per_pixel().is_err());
+    }
+    
+    #[test]
+    fn sample_format_defaults() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(277, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(277, FieldValue::Short(vec![3]))
+            .entry(339, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(277, FieldValue::Short(vec![3]))
+            .entry(339, FieldValue::Short(vec![2, 9]))
+            .ifd()
+            .entry(277, FieldValue::Short(vec![2]))
+            .entry(339, FieldValue::Long(vec![1, 70000]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].sample_format_if_present().unwrap(), None);
+        assert_eq!(tiff_reader.subfiles[0].sample_format().unwrap(), vec![SampleFormat::Unsigned; 3]);
+        assert_eq!(tiff_reader.subfiles[1].sample_format().unwrap(), vec![SampleFormat::Float; 3]);
+        assert_eq!(tiff_reader.subfiles[2].sample_format().unwrap(), vec![SampleFormat::Signed, SampleFormat::Other(9), SampleFormat::Unsigned]);
+        assert!(tiff_reader.subfiles[3].sample_format_if_present().is_err());
+    }
+    
+    #[test]
+    fn read_free_regions() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(288, FieldValue::Long(vec![100, 300]))
+            .entry(289, FieldValue::Long(vec![20, 40]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .ifd()
+            .entry(288, FieldValue::Long(vec![100]))
+            .ifd()
+            .entry(288, FieldValue::Long(vec![100, 300]))
+            .entry(289, FieldValue::Long(vec![20]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].free_regions().unwrap(), Some(vec![(100, 20), (300, 40)]));
+        assert_eq!(tiff_reader.subfiles[1].free_regions().unwrap(), None);
+        match tiff_reader.subfiles[2].free_regions() {
+            Err(TiffReadError::MissingRequiredTag {tag: 289}) => {},
+            other => panic!("Expected MissingRequiredTag, got {:?}", other),
+        }
+        assert!(tiff_reader.subfiles[3].free_regions().is_err());
+    }
+    
+    #[test]
+    fn classify_image_kinds() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(262, FieldValue::Short(vec![0]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![16]))
+            .entry(262, FieldValue::Short(vec![1]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![4]))
+            .entry(262, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![8]))
+            .entry(277, FieldValue::Short(vec![4]))
+            .entry(262, FieldValue::Short(vec![5]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![8]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .ifd()
+            .entry(262, FieldValue::Short(vec![8]))
+            .ifd()
+            .entry(262, FieldValue::Short(vec![2]))
+            .entry(277, FieldValue::Short(vec![4]))
+            .entry(338, FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(262, FieldValue::Short(vec![2]))
+            .entry(277, FieldValue::Short(vec![4]))
+            .entry(338, FieldValue::Short(vec![0]))
+            .reader();
+        
+        let kinds: Vec<ImageKind> = tiff_reader.subfiles.iter_mut().map(|subfile| subfile.image_kind().unwrap()).collect();
+        assert_eq!(kinds, vec![
+            ImageKind::Bilevel,
+            ImageKind::Grayscale {bits: 16},
+            ImageKind::Palette {bits: 4},
+            ImageKind::Cmyk,
+            ImageKind::Rgb,
+            ImageKind::Other,
+            ImageKind::Rgba {alpha: AlphaKind::Unassociated},
+            ImageKind::Rgb,
+        ]);
+        assert_eq!(tiff_reader.subfiles[0].compression().unwrap(), Compression::None);
+    }
+    
+    #[test]
+    fn validate_baseline_fields() {
+        let common = |builder: TiffBuilder| builder
+            .entry(256, FieldValue::Short(vec![1]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(259, FieldValue::Short(vec![1]))
+            .entry(278, FieldValue::Short(vec![1]))
+            .entry(282, FieldValue::Rational(vec![crate::Rational::new_raw(72, 1)]))
+            .entry(283, FieldValue::Rational(vec![crate::Rational::new_raw(72, 1)]))
+            .entry(296, FieldValue::Short(vec![2]));
+        
+        let builder = common(TiffBuilder::new(Endianness::Little))
+            .entry(262, FieldValue::Short(vec![1]))
+            .strips(vec![vec![0]])
+            .ifd()
+            .entry(262, FieldValue::Short(vec![2]))
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .strips(vec![vec![0; 3]]);
+        let builder = common(builder)
+            .ifd()
+            .entry(262, FieldValue::Short(vec![3]))
+            .entry(258, FieldValue::Short(vec![1]))
+            .strips(vec![vec![0]]);
+        let mut tiff_reader = common(builder)
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .reader();
+        
+        let missing_tag = |result: Result<(), TiffReadError>| match result {
+            Err(TiffReadError::MissingRequiredTag {tag}) => tag,
+            other => panic!("Expected MissingRequiredTag, got {:?}", other),
+        };
+        tiff_reader.subfiles[0].validate_baseline().unwrap();
+        assert_eq!(missing_tag(tiff_reader.subfiles[1].validate_baseline()), 277);
+        assert_eq!(missing_tag(tiff_reader.subfiles[2].validate_baseline()), 320);
+        assert_eq!(missing_tag(tiff_reader.subfiles[3].validate_baseline()), 262);
+    }
+    
+    #[test]
+    fn read_thresholding() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(263, FieldValue::Short(vec![2]))
+            .entry(264, FieldValue::Short(vec![8]))
+            .entry(265, FieldValue::Short(vec![4]))
+            .ifd()
+            .entry(263, FieldValue::Short(vec![7]))
+            .entry(264, FieldValue::Long(vec![70000]))
+            .ifd()
+            .entry(263, FieldValue::Long(vec![70000]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].thresholding().unwrap(), Some(Thresholding::OrderedDither));
+        assert_eq!(tiff_reader.subfiles[0].cell_width().unwrap(), Some(8));
+        assert_eq!(tiff_reader.subfiles[0].cell_length().unwrap(), Some(4));
+        assert_eq!(tiff_reader.subfiles[1].thresholding().unwrap(), Some(Thresholding::Other(7)));
+        assert!(tiff_reader.subfiles[1].cell_width().is_err());
+        assert_eq!(tiff_reader.subfiles[1].cell_length().unwrap(), None);
+        assert!(tiff_reader.subfiles[2].thresholding().is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+use crate::types::{FieldValue, FieldValueRef, RationalExt};
+
+/// The lookup tables from TransferFunction (301), each with
+/// 2**BitsPerSample entries.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransferFunction {
+    /// Either one curve for all channels or one per channel.
+    pub curves: Vec<Vec<u16>>,
+}
+
+impl TransferFunction {
+    /// Returns the curve for the given channel. A single curve applies
+    /// to every channel.
+    pub fn curve(&self, channel: usize) -> Option<&[u16]> {
+        match self.curves.len() {
+            1 => Some(&self.curves[0]),
+            _ => self.curves.get(channel).map(|curve| curve.as_slice()),
+        }
+    }
+}
+
+/// The optical densities from GrayResponseCurve (291), scaled by
+/// GrayResponseUnit (290).
+#[derive(Debug, PartialEq, Clone)]
+pub struct GrayResponse {
+    /// GrayResponseUnit, the power of ten the stored values are divided
+    /// by (1 for tenths to 5 for hundred-thousandths).
+    pub unit: u16,
+    /// The density of each gray level, 2**BitsPerSample entries.
+    pub densities: Vec<f64>,
+}
+
+/// A YCbCrPositioning (531) value: where chroma samples sit relative to
+/// the luma samples they cover.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum YCbCrPositioning {
+    /// At the center of the block of luma samples.
+    Centered,
+    /// At the position of the block's first luma sample.
+    Cosited,
+    /// A value not defined in TIFF 6.0.
+    Other(u16),
+}
+
+impl YCbCrPositioning {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => YCbCrPositioning::Centered,
+            2 => YCbCrPositioning::Cosited,
+            other => YCbCrPositioning::Other(other),
+        }
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Loads TransferFunction (301) and splits it into 1 or 3 curves of
+    /// 2**BitsPerSample entries each.
+    pub fn transfer_function(&mut self) -> Result<Option<TransferFunction>, TiffReadError> {
+        let values = match self.get_u16_values(tags::TRANSFER_FUNCTION, "TransferFunction")? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        
+        let bits = self.first_bits_per_sample()?;
+        let curve_len = 1usize.checked_shl(u32::from(bits)).filter(|_| bits <= 16)
+            .ok_or_else(|| ParseError::new(format!("TransferFunction isn't defined for {} bits per sample", bits)))?;
+        if values.len() != curve_len && values.len() != 3*curve_len {
+            return Err(ParseError::new(format!("Expected {} or {} TransferFunction values, found {}", curve_len, 3*curve_len, values.len())).into());
+        }
+        
+        let curves = values.chunks_exact(curve_len)
+            .map(|curve| curve.to_vec())
+            .collect();
+        Ok(Some(TransferFunction {curves}))
+    }
+    
+    /// Loads GrayResponseCurve (291) and scales it by GrayResponseUnit
+    /// (290), which defaults to 2 (hundredths).
+    pub fn gray_response(&mut self) -> Result<Option<GrayResponse>, TiffReadError> {
+        let values = match self.get_u32_values(tags::GRAY_RESPONSE_CURVE)? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        
+        let unit = match self.get_u32(tags::GRAY_RESPONSE_UNIT)?.unwrap_or(2) {
+            unit @ 1..=5 => unit as u16,
+            other => return Err(ParseError::new(format!("Invalid GrayResponseUnit: {}", other)).into()),
+        };
+        let bits = self.first_bits_per_sample()?;
+        let curve_len = 1usize.checked_shl(u32::from(bits)).filter(|_| bits <= 16)
+            .ok_or_else(|| ParseError::new(format!("GrayResponseCurve isn't defined for {} bits per sample", bits)))?;
+        if values.len() != curve_len {
+            return Err(ParseError::new(format!("Expected {} GrayResponseCurve values, found {}", curve_len, values.len())).into());
+        }
+        
+        let scale = 10f64.powi(i32::from(unit));
+        let densities = values.iter().map(|&value| f64::from(value)/scale).collect();
+        Ok(Some(GrayResponse {unit, densities}))
+    }
+    
+    /// Loads WhitePoint (318) as the chromaticity (x, y) of the white
+    /// point.
+    pub fn white_point(&mut self) -> Result<Option<[f64; 2]>, TiffReadError> {
+        let values = self.get_rationals_as_f64(tags::WHITE_POINT, "WhitePoint", 2)?;
+        Ok(values.map(|values| [values[0], values[1]]))
+    }
+    
+    /// Loads PrimaryChromaticities (319) as the chromaticities (x, y) of
+    /// the red, green and blue primaries, in that order.
+    pub fn primary_chromaticities(&mut self) -> Result<Option<[f64; 6]>, TiffReadError> {
+        let values = self.get_rationals_as_f64(tags::PRIMARY_CHROMATICITIES, "PrimaryChromaticities", 6)?;
+        Ok(values.map(|values| [values[0], values[1], values[2], values[3], values[4], values[5]]))
+    }
+    
+    /// Returns ReferenceBlackWhite as a (black, white) pair per
+    /// component. When absent, the defaults are 0 and 2**BitsPerSample -
+    /// 1 for every component, except that for YCbCr images the black
+    /// point of Cb and Cr is 2**(BitsPerSample - 1), their zero level.
+    pub fn reference_black_white(&mut self) -> Result<[(f64, f64); 3], TiffReadError> {
+        if let Some(values) = self.get_rationals_as_f64(tags::REFERENCE_BLACK_WHITE, "ReferenceBlackWhite", 6)? {
+            return Ok([(values[0], values[1]), (values[2], values[3]), (values[4], values[5])]);
+        }
+        
+        let bits = i32::from(self.first_bits_per_sample()?);
+        let white = 2f64.powi(bits) - 1.0;
+        if self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)? == Some(6) {
+            let zero_level = 2f64.powi(bits - 1);
+            Ok([(0.0, white), (zero_level, white), (zero_level, white)])
+        } else {
+            Ok([(0.0, white); 3])
+        }
+    }
+    
+    /// Returns YCbCrCoefficients as the luma weights of red, green and
+    /// blue, defaulting to those of CCIR Recommendation 601-1.
+    pub fn ycbcr_coefficients(&mut self) -> Result<[f64; 3], TiffReadError> {
+        let values = self.get_rationals_as_f64(tags::YCBCR_COEFFICIENTS, "YCbCrCoefficients", 3)?;
+        Ok(values.map_or([0.299, 0.587, 0.114], |values| [values[0], values[1], values[2]]))
+    }
+    
+    /// Returns YCbCrSubSampling as (horizontal, vertical) luma samples
+    /// per chroma sample, defaulting to (2, 2). Each must be 1, 2 or 4,
+    /// and the vertical factor can't exceed the horizontal one.
+    pub fn ycbcr_subsampling(&mut self) -> Result<(u16, u16), TiffReadError> {
+        let values = self.get_u32_values(tags::YCBCR_SUBSAMPLING)?.unwrap_or_else(|| vec![2, 2]);
+        match values[..] {
+            [horizontal, vertical] if [1, 2, 4].contains(&horizontal) && [1, 2, 4].contains(&vertical) && vertical <= horizontal => {
+                Ok((horizontal as u16, vertical as u16))
+            }
+            _ => Err(ParseError::new(format!("Invalid YCbCrSubSampling: {:?}", values)).into()),
+        }
+    }
+    
+    /// Returns YCbCrPositioning, or `YCbCrPositioning::Centered` if
+    /// absent.
+    pub fn ycbcr_positioning(&mut self) -> Result<YCbCrPositioning, TiffReadError> {
+        let value = self.get_u16_field(tags::YCBCR_POSITIONING, "YCbCrPositioning")?.unwrap_or(1);
+        Ok(YCbCrPositioning::from_u16(value))
+    }
+    
+    /* The curves and tables sized by BitsPerSample go by the first
+     * sample. */
+    fn first_bits_per_sample(&mut self) -> Result<u16, TiffReadError> {
+        self.bits_per_sample()?.first().cloned().ok_or_else(|| ParseError::new("BitsPerSample has no values".to_string()).into())
+    }
+    
+    /* Loads a RATIONAL field that must hold exactly `count` values, none
+     * of which may have a zero denominator. */
+    fn get_rationals_as_f64(&mut self, tag: u16, name: &str, count: usize) -> Result<Option<Vec<f64>>, TiffReadError> {
+        let values = match self.get_value(tag)? {
+            Some(FieldValue::Rational(values)) => values,
+            Some(other) => return Err(TiffReadError::TypeMismatch {tag, found: other.field_type()}),
+            None => return Ok(None),
+        };
+        if values.len() != count {
+            return Err(ParseError::new(format!("Expected {} {} values, found {}", count, name, values.len())).into());
+        }
+        let values = values.iter()
+            .map(|value| value.checked_to_f64().ok_or_else(|| ParseError::new(format!("{} has a zero denominator", name))))
+            .collect::<Result<Vec<f64>, _>>()?;
+        Ok(Some(values))
+    }
+    
+    /// Loads ColorMap (320) and returns it as one (red, green, blue)
+    /// entry per palette index, 2**BitsPerSample entries in all.
+    pub fn color_map(&mut self) -> Result<Option<Vec<[u16; 3]>>, TiffReadError> {
+        let values = match self.get_u16_values(tags::COLOR_MAP, "ColorMap")? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        
+        let bits = self.first_bits_per_sample()?;
+        let entry_count = 1usize.checked_shl(u32::from(bits)).filter(|_| bits <= 16)
+            .ok_or_else(|| ParseError::new(format!("ColorMap isn't defined for {} bits per sample", bits)))?;
+        if values.len() != 3*entry_count {
+            return Err(ParseError::new(format!("Expected {} ColorMap values, found {}", 3*entry_count, values.len())).into());
+        }
+        
+        /* All red values come first, then all green and then all blue. */
+        let (red, green_blue) = values.split_at(entry_count);
+        let (green, blue) = green_blue.split_at(entry_count);
+        let entries = red.iter().zip(green).zip(blue)
+            .map(|((&red, &green), &blue)| [red, green, blue])
+            .collect();
+        Ok(Some(entries))
+    }
+    
+    /// Returns the ColorMap values as stored (all red values, then green,
+    /// then blue) without copying them. Returns `None` unless the field
+    /// is present, of type SHORT and already loaded, e.g. by
+    /// `get_value(tags::COLOR_MAP)`.
+    pub fn color_map_ref(&self) -> Option<&[u16]> {
+        match self.get_field(tags::COLOR_MAP)?.value_ref()? {
+            FieldValueRef::Short(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrayResponse, YCbCrPositioning};
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn read_transfer_function() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Big)
+            .entry(258, FieldValue::Short(vec![2]))
+            .entry(301, FieldValue::Short(vec![0, 100, 200, 300]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(301, FieldValue::Short(vec![0, 10, 0, 20, 0, 30]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(301, FieldValue::Long(vec![0, 0x1_0000]))
+            .reader();
+        
+        let single = tiff_reader.subfiles[0].transfer_function().unwrap().unwrap();
+        assert_eq!(single.curves.len(), 1);
+        assert_eq!(single.curve(2), Some(&[0, 100, 200, 300][..]));
+        
+        let per_channel = tiff_reader.subfiles[1].transfer_function().unwrap().unwrap();
+        assert_eq!(per_channel.curves, vec![vec![0, 10], vec![0, 20], vec![0, 30]]);
+        assert_eq!(per_channel.curve(3), None);
+        
+        assert!(tiff_reader.subfiles[2].transfer_function().is_err());
+    }
+    
+    #[test]
+    fn read_gray_response() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(291, FieldValue::Short(vec![2000, 5]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(290, FieldValue::Short(vec![3]))
+            .entry(291, FieldValue::Short(vec![2000, 5]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![2]))
+            .entry(291, FieldValue::Short(vec![2000, 5]))
+            .reader();
+        
+        let gray_response = tiff_reader.subfiles[0].gray_response().unwrap().unwrap();
+        assert_eq!(gray_response, GrayResponse {unit: 2, densities: vec![20.0, 0.05]});
+        assert_eq!(tiff_reader.subfiles[1].gray_response().unwrap().unwrap().densities, vec![2.0, 0.005]);
+        assert!(tiff_reader.subfiles[2].gray_response().is_err());
+    }
+    
+    #[test]
+    fn ycbcr_subsampling_and_positioning() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .ifd()
+            .entry(530, FieldValue::Short(vec![4, 1]))
+            .entry(531, FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(530, FieldValue::Short(vec![3, 1]))
+            .entry(531, FieldValue::Short(vec![7]))
+            .ifd()
+            .entry(530, FieldValue::Short(vec![1, 2]))
+            .entry(531, FieldValue::Long(vec![70000]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].ycbcr_subsampling().unwrap(), (2, 2));
+        assert_eq!(tiff_reader.subfiles[0].ycbcr_positioning().unwrap(), YCbCrPositioning::Centered);
+        assert_eq!(tiff_reader.subfiles[1].ycbcr_subsampling().unwrap(), (4, 1));
+        assert_eq!(tiff_reader.subfiles[1].ycbcr_positioning().unwrap(), YCbCrPositioning::Cosited);
+        assert!(tiff_reader.subfiles[2].ycbcr_subsampling().is_err());
+        assert_eq!(tiff_reader.subfiles[2].ycbcr_positioning().unwrap(), YCbCrPositioning::Other(7));
+        assert!(tiff_reader.subfiles[3].ycbcr_subsampling().is_err());
+        assert!(tiff_reader.subfiles[3].ycbcr_positioning().is_err());
+    }
+    
+    #[test]
+    fn read_color_map() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(320, FieldValue::Short(vec![0, 65535, 10, 20, 30, 40]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![2]))
+            .entry(320, FieldValue::Short(vec![0, 1, 2]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![1]))
+            .entry(320, FieldValue::Long(vec![0, 0x1_0000, 10, 20, 30, 40]))
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        assert_eq!(subfile.color_map_ref(), None);
+        assert_eq!(subfile.color_map().unwrap(), Some(vec![[0, 10, 30], [65535, 20, 40]]));
+        assert_eq!(subfile.color_map_ref(), Some(&[0, 65535, 10, 20, 30, 40][..]));
+        assert!(tiff_reader.subfiles[1].color_map().is_err());
+        assert!(tiff_reader.subfiles[2].color_map().is_err());
+    }
+    
+    #[test]
+    fn read_chromaticities() {
+        let rational = |numer, denom| crate::Rational::new_raw(numer, denom);
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(318, FieldValue::Rational(vec![rational(3127, 10000), rational(3290, 10000)]))
+            .entry(319, FieldValue::Rational(vec![rational(64, 100), rational(33, 100), rational(3, 10), rational(6, 10), rational(15, 100), rational(6, 100)]))
+            .ifd()
+            .entry(318, FieldValue::Rational(vec![rational(1, 0), rational(1, 3)]))
+            .entry(319, FieldValue::Rational(vec![rational(1, 2)]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].white_point().unwrap(), Some([0.3127, 0.329]));
+        assert_eq!(tiff_reader.subfiles[0].primary_chromaticities().unwrap(), Some([0.64, 0.33, 0.3, 0.6, 0.15, 0.06]));
+        assert!(tiff_reader.subfiles[1].white_point().is_err());
+        assert!(tiff_reader.subfiles[1].primary_chromaticities().is_err());
+    }
+    
+    #[test]
+    fn reference_black_white_defaults() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .entry(262, FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .entry(262, FieldValue::Short(vec![6]))
+            .ifd()
+            .entry(532, FieldValue::Rational([0, 255, 128, 255, 128, 255].iter().map(|&value| crate::Rational::new_raw(value, 1)).collect()))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].reference_black_white().unwrap(), [(0.0, 255.0); 3]);
+        assert_eq!(tiff_reader.subfiles[1].reference_black_white().unwrap(), [(0.0, 255.0), (128.0, 255.0), (128.0, 255.0)]);
+        assert_eq!(tiff_reader.subfiles[2].reference_black_white().unwrap(), [(0.0, 255.0), (128.0, 255.0), (128.0, 255.0)]);
+    }
+}
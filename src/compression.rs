@@ -0,0 +1,203 @@
+use crate::error::TiffReadError;
+
+pub(crate) mod ccitt;
+
+pub(crate) const PACKBITS: u16 = 32773;
+
+/// A Compression (259) value.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Compression {
+    None,
+    /// CCITT modified Huffman run-length encoding.
+    CcittRle,
+    CcittFax3,
+    CcittFax4,
+    Lzw,
+    /// JPEG as defined in TIFF 6.0 section 22, now obsolete.
+    OldJpeg,
+    /// JPEG as defined in Technical Note 2.
+    Jpeg,
+    /// Deflate, as registered by Adobe.
+    Deflate,
+    PackBits,
+    Other(u16),
+}
+
+impl Compression {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => Compression::None,
+            2 => Compression::CcittRle,
+            3 => Compression::CcittFax3,
+            4 => Compression::CcittFax4,
+            5 => Compression::Lzw,
+            6 => Compression::OldJpeg,
+            7 => Compression::Jpeg,
+            8 => Compression::Deflate,
+            PACKBITS => Compression::PackBits,
+            other => Compression::Other(other),
+        }
+    }
+    
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Compression::None => 1,
+            Compression::CcittRle => 2,
+            Compression::CcittFax3 => 3,
+            Compression::CcittFax4 => 4,
+            Compression::Lzw => 5,
+            Compression::OldJpeg => 6,
+            Compression::Jpeg => 7,
+            Compression::Deflate => 8,
+            Compression::PackBits => PACKBITS,
+            Compression::Other(value) => value,
+        }
+    }
+    
+    /// Whether strips compressed this way can be decoded by
+    /// `Subfile::read_strip` and friends.
+    pub fn is_supported(&self) -> bool {
+        matches!(self, Compression::None | Compression::PackBits)
+    }
+}
+
+/// Decodes PackBits (Compression = 32773) data into exactly
+/// `expected_len` bytes. Input left over once that many bytes have been
+/// produced is ignored, but a run or literal that would go past
+/// `expected_len`, or input that ends too early, is an error.
+pub(crate) fn decode_packbits(input: &[u8], expected_len: usize) -> Result<Vec<u8>, TiffReadError> {
+    let corrupt = |message: String| TiffReadError::CorruptCompressedData {compression: PACKBITS, message};
+    
+    let mut output = Vec::with_capacity(expected_len);
+    let mut position = 0;
+    while output.len() < expected_len {
+        let header = match input.get(position) {
+            Some(&header) => header as i8,
+            None => return Err(corrupt(format!("Input ended after {} of {} bytes", output.len(), expected_len))),
+        };
+        position += 1;
+        
+        match header {
+            /* -128 is a no-op, not a run. */
+            -128 => {},
+            0..=127 => {
+                let len = header as usize + 1;
+                let literal = input.get(position..position + len)
+                    .ok_or_else(|| corrupt(format!("Literal of {} bytes at input position {} extends past the end of the input", len, position - 1)))?;
+                if output.len() + len > expected_len {
+                    return Err(corrupt(format!("Literal of {} bytes overruns the expected {} bytes", len, expected_len)));
+                }
+                output.extend_from_slice(literal);
+                position += len;
+            }
+            _ => {
+                let len = (1 - isize::from(header)) as usize;
+                let &byte = input.get(position)
+                    .ok_or_else(|| corrupt(format!("Run at input position {} is missing its byte", position - 1)))?;
+                if output.len() + len > expected_len {
+                    return Err(corrupt(format!("Run of {} bytes overruns the expected {} bytes", len, expected_len)));
+                }
+                output.resize(output.len() + len, byte);
+                position += 1;
+            }
+        }
+    }
+    
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_packbits;
+    use crate::error::TiffReadError;
+    
+    /* Simple deterministic generator, so the randomized tests don't need
+     * an extra dependency. */
+    struct Lcg(u64);
+    
+    impl Lcg {
+        fn next_byte(&mut self) -> u8 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 56) as u8
+        }
+    }
+    
+    fn encode_packbits(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let run_len = data[i..].iter().take(128).take_while(|&&byte| byte == data[i]).count();
+            if run_len >= 2 {
+                output.push((1 - run_len as isize) as u8);
+                output.push(data[i]);
+                i += run_len;
+            } else {
+                let literal_len = (1..=128.min(data.len() - i))
+                    .take_while(|&len| i + len == data.len() || i + len + 1 == data.len() || data[i + len] != data[i + len + 1])
+                    .last()
+                    .unwrap_or(1);
+                output.push((literal_len - 1) as u8);
+                output.extend_from_slice(&data[i..i + literal_len]);
+                i += literal_len;
+            }
+        }
+        output
+    }
+    
+    #[test]
+    fn compression_codes() {
+        for value in 0..=u16::MAX {
+            assert_eq!(super::Compression::from_u16(value).to_u16(), value);
+        }
+        assert!(super::Compression::from_u16(32773).is_supported());
+        assert!(!super::Compression::Lzw.is_supported());
+    }
+    
+    #[test]
+    fn decode_packbits_example() {
+        /* The example from the TIFF 6.0 spec. */
+        let input = b"\xFE\xAA\x02\x80\x00\x2A\xFD\xAA\x03\x80\x00\x2A\x22\xF7\xAA";
+        let expected = b"\xAA\xAA\xAA\x80\x00\x2A\xAA\xAA\xAA\xAA\x80\x00\x2A\x22\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA";
+        assert_eq!(decode_packbits(input, expected.len()).unwrap(), expected.to_vec());
+    }
+    
+    #[test]
+    fn decode_packbits_edge_cases() {
+        assert_eq!(decode_packbits(b"\x80\x01ab\x80", 2).unwrap(), b"ab".to_vec());
+        assert_eq!(decode_packbits(b"\x00a\x00b", 1).unwrap(), b"a".to_vec());
+        for (input, expected_len) in [(b"\xFEa".as_ref(), 2), (b"\x02ab".as_ref(), 3), (b"\x01a".as_ref(), 1), (b"\xFD".as_ref(), 4), (b"\x80".as_ref(), 1)].iter() {
+            match decode_packbits(input, *expected_len) {
+                Err(TiffReadError::CorruptCompressedData {compression: 32773, ..}) => {},
+                other => panic!("Expected CorruptCompressedData for {:?}, got {:?}", input, other),
+            }
+        }
+    }
+    
+    #[test]
+    fn decode_random_packbits_without_panicking() {
+        let mut rng = Lcg(1);
+        for _ in 0..2000 {
+            let len = usize::from(rng.next_byte());
+            let input: Vec<u8> = (0..len).map(|_| rng.next_byte()).collect();
+            let expected_len = usize::from(rng.next_byte())*2;
+            if let Ok(output) = decode_packbits(&input, expected_len) {
+                assert_eq!(output.len(), expected_len);
+            }
+        }
+    }
+    
+    #[test]
+    fn packbits_round_trip() {
+        let mut rng = Lcg(2);
+        for _ in 0..500 {
+            let len = usize::from(rng.next_byte())*3;
+            let mut data = Vec::with_capacity(len);
+            while data.len() < len {
+                let byte = rng.next_byte() % 4;
+                let repeat = if rng.next_byte() < 64 {usize::from(rng.next_byte())} else {1};
+                data.resize(data.len() + repeat.min(len - data.len()), byte);
+            }
+            assert_eq!(decode_packbits(&encode_packbits(&data), data.len()).unwrap(), data);
+        }
+    }
+}
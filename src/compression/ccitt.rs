@@ -0,0 +1,3 @@
+//! Shared building blocks for the CCITT fax codecs.
+
+pub(crate) mod tables;
@@ -0,0 +1,382 @@
+//! The modified Huffman run-length codes from ITU-T T.4, shared by the
+//! CCITT codecs (Compression = 2, 3 and 4), and a bit reader to decode
+//! them with.
+
+/* Nothing outside the tests decodes CCITT data yet. */
+#![cfg_attr(not(test), allow(dead_code))]
+
+/// A run-length code of `len` bits, stored in the low bits of `bits`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Code {
+    pub bits: u16,
+    pub len: u8,
+    pub run: u16,
+}
+
+/// The longest code in any of the tables, apart from EOL.
+const MAX_CODE_LEN: u8 = 13;
+
+/// White terminating codes, for runs of 0 to 63 pixels.
+pub(crate) const WHITE_TERMINATING: [Code; 64] = [
+    Code {bits: 0b00110101, len: 8, run: 0},
+    Code {bits: 0b000111, len: 6, run: 1},
+    Code {bits: 0b0111, len: 4, run: 2},
+    Code {bits: 0b1000, len: 4, run: 3},
+    Code {bits: 0b1011, len: 4, run: 4},
+    Code {bits: 0b1100, len: 4, run: 5},
+    Code {bits: 0b1110, len: 4, run: 6},
+    Code {bits: 0b1111, len: 4, run: 7},
+    Code {bits: 0b10011, len: 5, run: 8},
+    Code {bits: 0b10100, len: 5, run: 9},
+    Code {bits: 0b00111, len: 5, run: 10},
+    Code {bits: 0b01000, len: 5, run: 11},
+    Code {bits: 0b001000, len: 6, run: 12},
+    Code {bits: 0b000011, len: 6, run: 13},
+    Code {bits: 0b110100, len: 6, run: 14},
+    Code {bits: 0b110101, len: 6, run: 15},
+    Code {bits: 0b101010, len: 6, run: 16},
+    Code {bits: 0b101011, len: 6, run: 17},
+    Code {bits: 0b0100111, len: 7, run: 18},
+    Code {bits: 0b0001100, len: 7, run: 19},
+    Code {bits: 0b0001000, len: 7, run: 20},
+    Code {bits: 0b0010111, len: 7, run: 21},
+    Code {bits: 0b0000011, len: 7, run: 22},
+    Code {bits: 0b0000100, len: 7, run: 23},
+    Code {bits: 0b0101000, len: 7, run: 24},
+    Code {bits: 0b0101011, len: 7, run: 25},
+    Code {bits: 0b0010011, len: 7, run: 26},
+    Code {bits: 0b0100100, len: 7, run: 27},
+    Code {bits: 0b0011000, len: 7, run: 28},
+    Code {bits: 0b00000010, len: 8, run: 29},
+    Code {bits: 0b00000011, len: 8, run: 30},
+    Code {bits: 0b00011010, len: 8, run: 31},
+    Code {bits: 0b00011011, len: 8, run: 32},
+    Code {bits: 0b00010010, len: 8, run: 33},
+    Code {bits: 0b00010011, len: 8, run: 34},
+    Code {bits: 0b00010100, len: 8, run: 35},
+    Code {bits: 0b00010101, len: 8, run: 36},
+    Code {bits: 0b00010110, len: 8, run: 37},
+    Code {bits: 0b00010111, len: 8, run: 38},
+    Code {bits: 0b00101000, len: 8, run: 39},
+    Code {bits: 0b00101001, len: 8, run: 40},
+    Code {bits: 0b00101010, len: 8, run: 41},
+    Code {bits: 0b00101011, len: 8, run: 42},
+    Code {bits: 0b00101100, len: 8, run: 43},
+    Code {bits: 0b00101101, len: 8, run: 44},
+    Code {bits: 0b00000100, len: 8, run: 45},
+    Code {bits: 0b00000101, len: 8, run: 46},
+    Code {bits: 0b00001010, len: 8, run: 47},
+    Code {bits: 0b00001011, len: 8, run: 48},
+    Code {bits: 0b01010010, len: 8, run: 49},
+    Code {bits: 0b01010011, len: 8, run: 50},
+    Code {bits: 0b01010100, len: 8, run: 51},
+    Code {bits: 0b01010101, len: 8, run: 52},
+    Code {bits: 0b00100100, len: 8, run: 53},
+    Code {bits: 0b00100101, len: 8, run: 54},
+    Code {bits: 0b01011000, len: 8, run: 55},
+    Code {bits: 0b01011001, len: 8, run: 56},
+    Code {bits: 0b01011010, len: 8, run: 57},
+    Code {bits: 0b01011011, len: 8, run: 58},
+    Code {bits: 0b01001010, len: 8, run: 59},
+    Code {bits: 0b01001011, len: 8, run: 60},
+    Code {bits: 0b00110010, len: 8, run: 61},
+    Code {bits: 0b00110011, len: 8, run: 62},
+    Code {bits: 0b00110100, len: 8, run: 63},
+];
+
+/// White makeup codes, for runs of 64 to 1728 pixels in steps of 64.
+pub(crate) const WHITE_MAKEUP: [Code; 27] = [
+    Code {bits: 0b11011, len: 5, run: 64},
+    Code {bits: 0b10010, len: 5, run: 128},
+    Code {bits: 0b010111, len: 6, run: 192},
+    Code {bits: 0b0110111, len: 7, run: 256},
+    Code {bits: 0b00110110, len: 8, run: 320},
+    Code {bits: 0b00110111, len: 8, run: 384},
+    Code {bits: 0b01100100, len: 8, run: 448},
+    Code {bits: 0b01100101, len: 8, run: 512},
+    Code {bits: 0b01101000, len: 8, run: 576},
+    Code {bits: 0b01100111, len: 8, run: 640},
+    Code {bits: 0b011001100, len: 9, run: 704},
+    Code {bits: 0b011001101, len: 9, run: 768},
+    Code {bits: 0b011010010, len: 9, run: 832},
+    Code {bits: 0b011010011, len: 9, run: 896},
+    Code {bits: 0b011010100, len: 9, run: 960},
+    Code {bits: 0b011010101, len: 9, run: 1024},
+    Code {bits: 0b011010110, len: 9, run: 1088},
+    Code {bits: 0b011010111, len: 9, run: 1152},
+    Code {bits: 0b011011000, len: 9, run: 1216},
+    Code {bits: 0b011011001, len: 9, run: 1280},
+    Code {bits: 0b011011010, len: 9, run: 1344},
+    Code {bits: 0b011011011, len: 9, run: 1408},
+    Code {bits: 0b010011000, len: 9, run: 1472},
+    Code {bits: 0b010011001, len: 9, run: 1536},
+    Code {bits: 0b010011010, len: 9, run: 1600},
+    Code {bits: 0b011000, len: 6, run: 1664},
+    Code {bits: 0b010011011, len: 9, run: 1728},
+];
+
+/// Black terminating codes, for runs of 0 to 63 pixels.
+pub(crate) const BLACK_TERMINATING: [Code; 64] = [
+    Code {bits: 0b0000110111, len: 10, run: 0},
+    Code {bits: 0b010, len: 3, run: 1},
+    Code {bits: 0b11, len: 2, run: 2},
+    Code {bits: 0b10, len: 2, run: 3},
+    Code {bits: 0b011, len: 3, run: 4},
+    Code {bits: 0b0011, len: 4, run: 5},
+    Code {bits: 0b0010, len: 4, run: 6},
+    Code {bits: 0b00011, len: 5, run: 7},
+    Code {bits: 0b000101, len: 6, run: 8},
+    Code {bits: 0b000100, len: 6, run: 9},
+    Code {bits: 0b0000100, len: 7, run: 10},
+    Code {bits: 0b0000101, len: 7, run: 11},
+    Code {bits: 0b0000111, len: 7, run: 12},
+    Code {bits: 0b00000100, len: 8, run: 13},
+    Code {bits: 0b00000111, len: 8, run: 14},
+    Code {bits: 0b000011000, len: 9, run: 15},
+    Code {bits: 0b0000010111, len: 10, run: 16},
+    Code {bits: 0b0000011000, len: 10, run: 17},
+    Code {bits: 0b0000001000, len: 10, run: 18},
+    Code {bits: 0b00001100111, len: 11, run: 19},
+    Code {bits: 0b00001101000, len: 11, run: 20},
+    Code {bits: 0b00001101100, len: 11, run: 21},
+    Code {bits: 0b00000110111, len: 11, run: 22},
+    Code {bits: 0b00000101000, len: 11, run: 23},
+    Code {bits: 0b00000010111, len: 11, run: 24},
+    Code {bits: 0b00000011000, len: 11, run: 25},
+    Code {bits: 0b000011001010, len: 12, run: 26},
+    Code {bits: 0b000011001011, len: 12, run: 27},
+    Code {bits: 0b000011001100, len: 12, run: 28},
+    Code {bits: 0b000011001101, len: 12, run: 29},
+    Code {bits: 0b000001101000, len: 12, run: 30},
+    Code {bits: 0b000001101001, len: 12, run: 31},
+    Code {bits: 0b000001101010, len: 12, run: 32},
+    Code {bits: 0b000001101011, len: 12, run: 33},
+    Code {bits: 0b000011010010, len: 12, run: 34},
+    Code {bits: 0b000011010011, len: 12, run: 35},
+    Code {bits: 0b000011010100, len: 12, run: 36},
+    Code {bits: 0b000011010101, len: 12, run: 37},
+    Code {bits: 0b000011010110, len: 12, run: 38},
+    Code {bits: 0b000011010111, len: 12, run: 39},
+    Code {bits: 0b000001101100, len: 12, run: 40},
+    Code {bits: 0b000001101101, len: 12, run: 41},
+    Code {bits: 0b000011011010, len: 12, run: 42},
+    Code {bits: 0b000011011011, len: 12, run: 43},
+    Code {bits: 0b000001010100, len: 12, run: 44},
+    Code {bits: 0b000001010101, len: 12, run: 45},
+    Code {bits: 0b000001010110, len: 12, run: 46},
+    Code {bits: 0b000001010111, len: 12, run: 47},
+    Code {bits: 0b000001100100, len: 12, run: 48},
+    Code {bits: 0b000001100101, len: 12, run: 49},
+    Code {bits: 0b000001010010, len: 12, run: 50},
+    Code {bits: 0b000001010011, len: 12, run: 51},
+    Code {bits: 0b000000100100, len: 12, run: 52},
+    Code {bits: 0b000000110111, len: 12, run: 53},
+    Code {bits: 0b000000111000, len: 12, run: 54},
+    Code {bits: 0b000000100111, len: 12, run: 55},
+    Code {bits: 0b000000101000, len: 12, run: 56},
+    Code {bits: 0b000001011000, len: 12, run: 57},
+    Code {bits: 0b000001011001, len: 12, run: 58},
+    Code {bits: 0b000000101011, len: 12, run: 59},
+    Code {bits: 0b000000101100, len: 12, run: 60},
+    Code {bits: 0b000001011010, len: 12, run: 61},
+    Code {bits: 0b000001100110, len: 12, run: 62},
+    Code {bits: 0b000001100111, len: 12, run: 63},
+];
+
+/// Black makeup codes, for runs of 64 to 1728 pixels in steps of 64.
+pub(crate) const BLACK_MAKEUP: [Code; 27] = [
+    Code {bits: 0b0000001111, len: 10, run: 64},
+    Code {bits: 0b000011001000, len: 12, run: 128},
+    Code {bits: 0b000011001001, len: 12, run: 192},
+    Code {bits: 0b000001011011, len: 12, run: 256},
+    Code {bits: 0b000000110011, len: 12, run: 320},
+    Code {bits: 0b000000110100, len: 12, run: 384},
+    Code {bits: 0b000000110101, len: 12, run: 448},
+    Code {bits: 0b0000001101100, len: 13, run: 512},
+    Code {bits: 0b0000001101101, len: 13, run: 576},
+    Code {bits: 0b0000001001010, len: 13, run: 640},
+    Code {bits: 0b0000001001011, len: 13, run: 704},
+    Code {bits: 0b0000001001100, len: 13, run: 768},
+    Code {bits: 0b0000001001101, len: 13, run: 832},
+    Code {bits: 0b0000001110010, len: 13, run: 896},
+    Code {bits: 0b0000001110011, len: 13, run: 960},
+    Code {bits: 0b0000001110100, len: 13, run: 1024},
+    Code {bits: 0b0000001110101, len: 13, run: 1088},
+    Code {bits: 0b0000001110110, len: 13, run: 1152},
+    Code {bits: 0b0000001110111, len: 13, run: 1216},
+    Code {bits: 0b0000001010010, len: 13, run: 1280},
+    Code {bits: 0b0000001010011, len: 13, run: 1344},
+    Code {bits: 0b0000001010100, len: 13, run: 1408},
+    Code {bits: 0b0000001010101, len: 13, run: 1472},
+    Code {bits: 0b0000001011010, len: 13, run: 1536},
+    Code {bits: 0b0000001011011, len: 13, run: 1600},
+    Code {bits: 0b0000001100100, len: 13, run: 1664},
+    Code {bits: 0b0000001100101, len: 13, run: 1728},
+];
+
+/// Makeup codes for runs of 1792 to 2560 pixels, shared by both
+/// colors.
+pub(crate) const EXTENDED_MAKEUP: [Code; 13] = [
+    Code {bits: 0b00000001000, len: 11, run: 1792},
+    Code {bits: 0b00000001100, len: 11, run: 1856},
+    Code {bits: 0b00000001101, len: 11, run: 1920},
+    Code {bits: 0b000000010010, len: 12, run: 1984},
+    Code {bits: 0b000000010011, len: 12, run: 2048},
+    Code {bits: 0b000000010100, len: 12, run: 2112},
+    Code {bits: 0b000000010101, len: 12, run: 2176},
+    Code {bits: 0b000000010110, len: 12, run: 2240},
+    Code {bits: 0b000000010111, len: 12, run: 2304},
+    Code {bits: 0b000000011100, len: 12, run: 2368},
+    Code {bits: 0b000000011101, len: 12, run: 2432},
+    Code {bits: 0b000000011110, len: 12, run: 2496},
+    Code {bits: 0b000000011111, len: 12, run: 2560},
+];
+
+/// Reads a buffer one bit at a time, most significant bit first
+/// (FillOrder = 1).
+#[derive(Debug, Clone)]
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            position: 0,
+        }
+    }
+    
+    /// Returns the next bit, or `None` at the end of the buffer.
+    pub fn read_bit(&mut self) -> Option<u8> {
+        let byte = self.data.get(self.position/8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Some(bit)
+    }
+    
+    /// Position of the next bit, counted from the start of the buffer.
+    pub fn bit_position(&self) -> usize {
+        self.position
+    }
+    
+    /// Skips ahead to the next byte boundary, as rows start on one in
+    /// Compression = 2.
+    pub fn align_to_byte(&mut self) {
+        self.position = self.position.div_ceil(8)*8;
+    }
+}
+
+/// Reads a single code from any of `tables`, bit by bit, and returns its
+/// run length. Returns `None` if the input ends or the bits read don't
+/// form a code within `MAX_CODE_LEN` bits.
+pub(crate) fn read_run(reader: &mut BitReader, tables: &[&[Code]]) -> Option<u16> {
+    let mut bits = 0u16;
+    for len in 1..=MAX_CODE_LEN {
+        bits = bits << 1 | u16::from(reader.read_bit()?);
+        let found = tables.iter()
+            .flat_map(|table| table.iter())
+            .find(|code| code.len == len && code.bits == bits);
+        if let Some(code) = found {
+            return Some(code.run);
+        }
+    }
+    None
+}
+
+/// Reads makeup codes followed by a terminating code and returns the
+/// total run length, or `None` if it doesn't fit in a `u32`.
+fn decode_run(reader: &mut BitReader, terminating: &[Code], makeup: &[Code]) -> Option<u32> {
+    let mut total = 0u32;
+    loop {
+        let run = read_run(reader, &[terminating, makeup, &EXTENDED_MAKEUP])?;
+        total = total.checked_add(u32::from(run))?;
+        if run < 64 {
+            return Some(total);
+        }
+    }
+}
+
+/// Decodes the length of a run of white pixels.
+pub(crate) fn decode_white_run(reader: &mut BitReader) -> Option<u32> {
+    decode_run(reader, &WHITE_TERMINATING, &WHITE_MAKEUP)
+}
+
+/// Decodes the length of a run of black pixels.
+pub(crate) fn decode_black_run(reader: &mut BitReader) -> Option<u32> {
+    decode_run(reader, &BLACK_TERMINATING, &BLACK_MAKEUP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /* Packs codes into bytes, most significant bit first. */
+    fn pack(codes: &[Code]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut bit_count = 0;
+        for code in codes {
+            for i in (0..code.len).rev() {
+                if bit_count % 8 == 0 {
+                    bytes.push(0);
+                }
+                let bit = (code.bits >> i) as u8 & 1;
+                *bytes.last_mut().unwrap() |= bit << (7 - bit_count % 8);
+                bit_count += 1;
+            }
+        }
+        bytes
+    }
+    
+    #[test]
+    fn tables_are_prefix_free() {
+        let extended: &[Code] = &EXTENDED_MAKEUP;
+        for codes in &[[&WHITE_TERMINATING[..], &WHITE_MAKEUP, extended], [&BLACK_TERMINATING[..], &BLACK_MAKEUP, extended]] {
+            let all: Vec<&Code> = codes.iter().flat_map(|table| table.iter()).collect();
+            for a in &all {
+                assert!(a.len <= MAX_CODE_LEN && u32::from(a.bits) < 1 << a.len);
+                for b in &all {
+                    let is_prefix = a.len <= b.len && b.bits >> (b.len - a.len) == a.bits;
+                    assert!(a.run == b.run || !is_prefix, "code for {} is a prefix of code for {}", a.run, b.run);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn decode_every_run_length() {
+        for run in 0..=2560 {
+            let makeup = |table: &[Code]| table.iter().chain(&EXTENDED_MAKEUP).find(|code| u32::from(code.run) == run/64*64).cloned();
+            let mut white = Vec::new();
+            let mut black = Vec::new();
+            if run >= 64 {
+                white.push(makeup(&WHITE_MAKEUP).unwrap());
+                black.push(makeup(&BLACK_MAKEUP).unwrap());
+            }
+            white.push(WHITE_TERMINATING[run as usize % 64]);
+            black.push(BLACK_TERMINATING[run as usize % 64]);
+            
+            assert_eq!(decode_white_run(&mut BitReader::new(&pack(&white))), Some(run));
+            assert_eq!(decode_black_run(&mut BitReader::new(&pack(&black))), Some(run));
+        }
+    }
+    
+    #[test]
+    fn decode_runs_in_sequence() {
+        /* White 2560 + 2560 + 5, then black 3, then white 0. */
+        let codes = [EXTENDED_MAKEUP[12], EXTENDED_MAKEUP[12], WHITE_TERMINATING[5], BLACK_TERMINATING[3], WHITE_TERMINATING[0]];
+        let bytes = pack(&codes);
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(decode_white_run(&mut reader), Some(5125));
+        assert_eq!(decode_black_run(&mut reader), Some(3));
+        assert_eq!(decode_white_run(&mut reader), Some(0));
+        
+        reader.align_to_byte();
+        assert_eq!(reader.bit_position(), bytes.len()*8);
+        assert_eq!(reader.read_bit(), None);
+        
+        /* EOL isn't a run. */
+        assert_eq!(decode_white_run(&mut BitReader::new(&[0x00, 0x10])), None);
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{Read, Seek};
+
+use ::image::{DynamicImage, ImageBuffer};
+
+use crate::baseline::{AlphaKind, ImageKind};
+use crate::error::{ParseError, TiffReadError};
+use crate::image::unpremultiply;
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Decodes the image into the closest `image::DynamicImage` variant:
+    ///
+    /// - Bilevel and grayscale images of up to 8 bits become `ImageLuma8`,
+    ///   scaled to 8 bits, and 16-bit ones `ImageLuma16`. WhiteIsZero is
+    ///   inverted.
+    /// - 8 and 16-bit RGB images become `ImageRgb8` and `ImageRgb16`,
+    ///   or `ImageRgba8` and `ImageRgba16` with an alpha sample. Associated
+    ///   alpha is converted to the unassociated alpha the `image` crate
+    ///   uses.
+    /// - Everything else `read_rgb8` supports (palette, CMYK, YCbCr and
+    ///   RGB of fewer than 8 bits) becomes `ImageRgb8`, as converted by
+    ///   `read_rgb8`.
+    ///
+    /// As with `read_rgb8`, other extra samples are dropped and the
+    /// Orientation isn't applied.
+    #[allow(clippy::wrong_self_convention)] // needs `&mut self` to load values
+    pub fn to_dynamic_image(&mut self) -> Result<DynamicImage, TiffReadError> {
+        let width = self.image_width()?;
+        let height = self.image_length()?;
+        let bits = self.unpackable_bits_per_sample()?;
+        let kind = self.image_kind()?;
+        
+        let channels = match kind {
+            ImageKind::Bilevel | ImageKind::Grayscale {..} => 1,
+            ImageKind::Rgb if bits >= 8 => 3,
+            ImageKind::Rgba {..} if bits >= 8 => 4,
+            _ => {
+                let (width, height, rgb) = self.read_rgb8()?;
+                return Ok(DynamicImage::ImageRgb8(from_raw(width, height, rgb)?));
+            }
+        };
+        
+        let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+        let mut samples: Vec<u16> = self.unpacked_samples(width, bits)?
+            .chunks_exact(samples_per_pixel)
+            .flat_map(|pixel| pixel[..channels].to_vec())
+            .collect();
+        
+        let max = (1u32 << bits) - 1;
+        if channels == 1 && self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)? == Some(0) {
+            for sample in &mut samples {
+                *sample = max as u16 - *sample;
+            }
+        }
+        if kind == (ImageKind::Rgba {alpha: AlphaKind::Associated}) {
+            for pixel in samples.chunks_exact_mut(4) {
+                let alpha = u32::from(pixel[3]);
+                for sample in &mut pixel[..3] {
+                    *sample = unpremultiply(u32::from(*sample), alpha, max) as u16;
+                }
+            }
+        }
+        
+        let image = if bits == 16 {
+            match channels {
+                1 => DynamicImage::ImageLuma16(from_raw(width, height, samples)?),
+                3 => DynamicImage::ImageRgb16(from_raw(width, height, samples)?),
+                _ => DynamicImage::ImageRgba16(from_raw(width, height, samples)?),
+            }
+        } else {
+            let samples = samples.iter().map(|&sample| ((u32::from(sample)*255 + max/2)/max) as u8).collect();
+            match channels {
+                1 => DynamicImage::ImageLuma8(from_raw(width, height, samples)?),
+                3 => DynamicImage::ImageRgb8(from_raw(width, height, samples)?),
+                _ => DynamicImage::ImageRgba8(from_raw(width, height, samples)?),
+            }
+        };
+        Ok(image)
+    }
+}
+
+fn from_raw<P: ::image::Pixel>(width: u32, height: u32, samples: Vec<P::Subpixel>) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, TiffReadError> {
+    let len = samples.len();
+    ImageBuffer::from_raw(width, height, samples)
+        .ok_or_else(|| ParseError::new(format!("Decoded {} samples, too few for a {}x{} image", len, width, height)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::image::DynamicImage;
+    
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    fn image(photometric_interpretation: u16, bits_per_sample: Vec<u16>, strip: Vec<u8>) -> DynamicImage {
+        let samples_per_pixel = bits_per_sample.len() as u16;
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![2]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(258, FieldValue::Short(bits_per_sample))
+            .entry(262, FieldValue::Short(vec![photometric_interpretation]))
+            .entry(277, FieldValue::Short(vec![samples_per_pixel]))
+            .entry(338, FieldValue::Short(vec![1]))
+            .strips(vec![strip])
+            .reader();
+        tiff_reader.subfiles[0].to_dynamic_image().unwrap()
+    }
+    
+    #[test]
+    fn convert_to_dynamic_image() {
+        assert_eq!(image(0, vec![1], vec![0b0100_0000]).into_luma8().into_raw(), vec![255, 0]);
+        assert_eq!(image(1, vec![16], vec![0x34, 0x12, 0xFF, 0xFF]).into_luma16().into_raw(), vec![0x1234, 0xFFFF]);
+        assert_eq!(image(2, vec![8, 8, 8], vec![1, 2, 3, 4, 5, 6]).into_rgb8().into_raw(), vec![1, 2, 3, 4, 5, 6]);
+        match image(2, vec![8, 8, 8, 8], vec![64, 32, 0, 128, 9, 9, 9, 0]) {
+            DynamicImage::ImageRgba8(buffer) => assert_eq!(buffer.into_raw(), vec![128, 64, 0, 128, 0, 0, 0, 0]),
+            other => panic!("Expected Rgba8, got {:?}", other.color()),
+        }
+        match image(2, vec![16, 16, 16], vec![0; 12]) {
+            DynamicImage::ImageRgb16(_) => {},
+            other => panic!("Expected Rgb16, got {:?}", other.color()),
+        }
+        match image(5, vec![8, 8, 8, 8], vec![255, 0, 0, 0, 0, 0, 0, 255]) {
+            DynamicImage::ImageRgb8(buffer) => assert_eq!(buffer.into_raw(), vec![0, 255, 255, 0, 0, 0]),
+            other => panic!("Expected Rgb8, got {:?}", other.color()),
+        }
+    }
+}
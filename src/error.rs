@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::types::FieldType;
+
 #[derive(Debug)]
 pub struct ParseError {
     message: String,
@@ -8,7 +10,7 @@ pub struct ParseError {
 impl ParseError {
     pub fn new(message: String) -> Self {
         ParseError {
-            message: message,
+            message,
         }
     }
 }
@@ -22,7 +24,157 @@ impl fmt::Display for ParseError {
 impl std::error::Error for ParseError {
 }
 
+#[derive(Debug)]
+pub enum TiffReadError {
+    Io(std::io::Error),
+    Parse(ParseError),
+    OutOfBounds {offset: u64, len: usize, stream_len: u64},
+    TypeMismatch {tag: u16, found: FieldType},
+    MissingRequiredTag {tag: u16},
+    UnsupportedCompression {compression: u16},
+    UnsupportedPredictor {predictor: u16, bits_per_sample: u16},
+    UnsupportedBitsPerSample {bits_per_sample: u16},
+    TooManyValues {count: usize},
+    IfdCycle {offset: u32},
+    InField {tag: u16, source: Box<TiffReadError>},
+    ValueOverlapsStructure {offset: u64, len: usize},
+    EmptyIfd {offset: u32},
+    CorruptCompressedData {compression: u16, message: String},
+    IfdExtendsPastEof {offset: u32, declared_entries: u16},
+    InSubfile {index: usize, source: Box<TiffReadError>},
+    BufferSizeOverflow {field_type: FieldType, count: u32},
+    InvalidFieldType {tag: u16, raw: u16},
+    InconsistentStrips {index: usize, byte_count: u32, expected: usize},
+    SegmentIndexOutOfRange {index: usize, count: usize},
+    UnsupportedPhotometricInterpretation {photometric_interpretation: u32},
+    BackwardIfdPointer {offset: u32, next_offset: u32},
+    UnknownFieldType {tag: u16, raw: u16},
+}
+
+impl fmt::Display for TiffReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiffReadError::Io(err) => write!(f, "I/O error: {}", err),
+            TiffReadError::Parse(err) => write!(f, "Parse error: {}", err),
+            TiffReadError::OutOfBounds {offset, len, stream_len} => {
+                write!(f, "Read of {} bytes at offset {} extends past end of stream (length {})", len, offset, stream_len)
+            }
+            TiffReadError::TypeMismatch {tag, found} => write!(f, "Unexpected field type {} for tag {}", found, tag),
+            TiffReadError::MissingRequiredTag {tag} => write!(f, "Required tag {} is missing", tag),
+            TiffReadError::UnsupportedCompression {compression} => write!(f, "Unsupported compression scheme {}", compression),
+            TiffReadError::UnsupportedPredictor {predictor, bits_per_sample} => {
+                write!(f, "Unsupported predictor {} for {} bits per sample", predictor, bits_per_sample)
+            }
+            TiffReadError::UnsupportedBitsPerSample {bits_per_sample} => write!(f, "Unsupported sample size of {} bits", bits_per_sample),
+            TiffReadError::TooManyValues {count} => write!(f, "Too many values for a single field ({})", count),
+            TiffReadError::IfdCycle {offset} => write!(f, "IFD chain loops back to offset {}", offset),
+            TiffReadError::InField {tag, source} => write!(f, "Error in field with tag {}: {}", tag, source),
+            TiffReadError::ValueOverlapsStructure {offset, len} => {
+                write!(f, "Value of {} bytes at offset {} overlaps the header or the IFD it is described by", len, offset)
+            }
+            TiffReadError::EmptyIfd {offset} => write!(f, "IFD at offset {} has no entries", offset),
+            TiffReadError::CorruptCompressedData {compression, message} => write!(f, "Corrupt data for compression scheme {}: {}", compression, message),
+            TiffReadError::IfdExtendsPastEof {offset, declared_entries} => {
+                write!(f, "IFD at offset {} declares {} entries, which extend past the end of the stream", offset, declared_entries)
+            }
+            TiffReadError::InSubfile {index, source} => write!(f, "Error in subfile {}: {}", index, source),
+            TiffReadError::BufferSizeOverflow {field_type, count} => {
+                write!(f, "Size of {} {} values doesn't fit in memory on this platform", count, field_type)
+            }
+            TiffReadError::InvalidFieldType {tag, raw} => write!(f, "Invalid field type {} for tag {}", raw, tag),
+            TiffReadError::InconsistentStrips {index, byte_count, expected} => {
+                write!(f, "Uncompressed strip {} has a byte count of {}, but its rows take up {} bytes", index, byte_count, expected)
+            }
+            TiffReadError::SegmentIndexOutOfRange {index, count} => write!(f, "Segment index {} out of range (segment count {})", index, count),
+            TiffReadError::UnsupportedPhotometricInterpretation {photometric_interpretation} => {
+                write!(f, "Unsupported photometric interpretation {}", photometric_interpretation)
+            }
+            TiffReadError::BackwardIfdPointer {offset, next_offset} => {
+                write!(f, "IFD at offset {} points back to the next IFD at offset {}", offset, next_offset)
+            }
+            TiffReadError::UnknownFieldType {tag, raw} => write!(f, "Unknown field type {} for tag {}", raw, tag),
+        }
+    }
+}
+
+impl std::error::Error for TiffReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TiffReadError::Io(err) => Some(err),
+            TiffReadError::Parse(err) => Some(err),
+            TiffReadError::InField {source, ..} => Some(source.as_ref()),
+            TiffReadError::InSubfile {source, ..} => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TiffReadError {
+    fn from(err: std::io::Error) -> Self {
+        TiffReadError::Io(err)
+    }
+}
+
+impl TiffReadError {
+    fn io_error_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            TiffReadError::Io(err) => err.kind(),
+            TiffReadError::OutOfBounds {..} | TiffReadError::IfdExtendsPastEof {..} => ErrorKind::UnexpectedEof,
+            TiffReadError::UnsupportedCompression {..} | TiffReadError::UnsupportedPredictor {..} | TiffReadError::UnsupportedBitsPerSample {..} => ErrorKind::Unsupported,
+            TiffReadError::UnsupportedPhotometricInterpretation {..} => ErrorKind::Unsupported,
+            TiffReadError::SegmentIndexOutOfRange {..} => ErrorKind::InvalidInput,
+            TiffReadError::InField {source, ..} | TiffReadError::InSubfile {source, ..} => source.io_error_kind(),
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+/* I/O errors are unwrapped, everything else is wrapped in an
+ * `io::Error` of a matching kind, mostly `InvalidData`. */
+impl From<TiffReadError> for std::io::Error {
+    fn from(err: TiffReadError) -> Self {
+        match err {
+            TiffReadError::Io(err) => err,
+            err => std::io::Error::new(err.io_error_kind(), err),
+        }
+    }
+}
+
+impl From<ParseError> for TiffReadError {
+    fn from(err: ParseError) -> Self {
+        TiffReadError::Parse(err)
+    }
+}
+
 pub fn escaped_string_from_bytes(bytes: &[u8]) -> String {
-    let escaped_bytes: Vec<u8> = bytes.iter().map(|c| std::ascii::escape_default(*c)).flatten().collect();
+    let escaped_bytes: Vec<u8> = bytes.iter().flat_map(|c| std::ascii::escape_default(*c)).collect();
     String::from_utf8_lossy(&escaped_bytes).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind};
+    
+    use super::{ParseError, TiffReadError};
+    
+    #[test]
+    fn convert_to_io_error() {
+        let io_error = io::Error::from(TiffReadError::Io(io::Error::new(ErrorKind::PermissionDenied, "denied")));
+        assert_eq!(io_error.kind(), ErrorKind::PermissionDenied);
+        assert!(io_error.get_ref().is_some());
+        
+        let io_error = io::Error::from(TiffReadError::from(ParseError::new("bad".to_string())));
+        assert_eq!(io_error.kind(), ErrorKind::InvalidData);
+        assert!(io_error.get_ref().unwrap().downcast_ref::<TiffReadError>().is_some());
+        
+        let in_field = TiffReadError::InField {tag: 273, source: Box::new(TiffReadError::OutOfBounds {offset: 10, len: 4, stream_len: 12})};
+        assert_eq!(io::Error::from(in_field).kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(io::Error::from(TiffReadError::UnsupportedCompression {compression: 5}).kind(), ErrorKind::Unsupported);
+        
+        fn read_something() -> io::Result<()> {
+            Err(TiffReadError::EmptyIfd {offset: 8})?
+        }
+        assert_eq!(read_something().unwrap_err().to_string(), "IFD at offset 8 has no entries");
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::{Read, Seek};
+
+use crate::error::TiffReadError;
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+use crate::types::{FieldValue, RationalExt};
+
+// Tags in the GPS IFD, as defined by the Exif spec
+const GPS_LATITUDE_REF: u16 = 1;
+const GPS_LATITUDE: u16 = 2;
+const GPS_LONGITUDE_REF: u16 = 3;
+const GPS_LONGITUDE: u16 = 4;
+const GPS_ALTITUDE_REF: u16 = 5;
+const GPS_ALTITUDE: u16 = 6;
+
+/// Position read from the GPS IFD. Latitude and longitude are in signed
+/// decimal degrees (negative for south and west), altitude is in meters
+/// (negative below sea level).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GpsInfo {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Follows the GPSIFD pointer (34853) and reads the position stored
+    /// there. Returns `Ok(None)` if the subfile has no GPS IFD.
+    pub fn gps_info(&mut self) -> Result<Option<GpsInfo>, TiffReadError> {
+        let gps_ifd_offset = match self.get_ifd_offsets(tags::GPS_IFD)?.and_then(|offsets| offsets.first().cloned()) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let mut gps_ifd = self.child_subfile(gps_ifd_offset)?;
+        
+        let latitude = signed_coordinate(&mut gps_ifd, GPS_LATITUDE, GPS_LATITUDE_REF, 'S')?;
+        let longitude = signed_coordinate(&mut gps_ifd, GPS_LONGITUDE, GPS_LONGITUDE_REF, 'W')?;
+        
+        let altitude = match gps_ifd.get_value(GPS_ALTITUDE)? {
+            Some(FieldValue::Rational(values)) => values.first().and_then(|value| value.checked_to_f64()),
+            _ => None,
+        };
+        let below_sea_level = match gps_ifd.get_value(GPS_ALTITUDE_REF)? {
+            Some(FieldValue::Byte(values)) => values.first() == Some(&1),
+            _ => false,
+        };
+        let altitude = altitude.map(|altitude| if below_sea_level {-altitude} else {altitude});
+        
+        Ok(Some(GpsInfo {
+            latitude,
+            longitude,
+            altitude,
+        }))
+    }
+}
+
+/// Reads a degrees/minutes/seconds coordinate and its hemisphere
+/// reference, returning signed decimal degrees. Files that store fewer
+/// than three components (e.g. decimal minutes) are handled too.
+fn signed_coordinate<R: Read + Seek, S: Sharing>(gps_ifd: &mut Subfile<R, S>, value_tag: u16, ref_tag: u16, negative_ref: char) -> Result<Option<f64>, TiffReadError> {
+    let components = match gps_ifd.get_value(value_tag)? {
+        Some(FieldValue::Rational(values)) if !values.is_empty() && values.len() <= 3 => values.clone(),
+        _ => return Ok(None),
+    };
+    
+    let mut degrees = 0.0;
+    for (component, scale) in components.iter().zip(&[1.0, 60.0, 3600.0]) {
+        match component.checked_to_f64() {
+            Some(value) => degrees += value / scale,
+            None => return Ok(None),
+        }
+    }
+    
+    let is_negative = match gps_ifd.get_value(ref_tag)? {
+        Some(FieldValue::Ascii(reference)) => reference.trim().starts_with(negative_ref),
+        _ => false,
+    };
+    
+    Ok(Some(if is_negative {-degrees} else {degrees}))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    fn ifd_entry(tag: u16, field_type: u16, count: u32, value_offset: [u8; 4]) -> Vec<u8> {
+        [&tag.to_le_bytes()[..], &field_type.to_le_bytes(), &count.to_le_bytes(), &value_offset].concat()
+    }
+    
+    fn rationals(values: &[(u32, u32)]) -> Vec<u8> {
+        values.iter().flat_map(|(numer, denom)| [numer.to_le_bytes(), denom.to_le_bytes()].concat()).collect()
+    }
+    
+    #[test]
+    fn read_gps_info() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".to_vec(), // image file header, offset 8 to first IFD
+            b"\x01\x00".to_vec(), // IFD: number of entries (1)
+            ifd_entry(34853, 4, 1, 26u32.to_le_bytes()), // GPSIFD, LONG, offset 26
+            b"\x00\x00\x00\x00".to_vec(), // IFD: offset to next IFD (0 = N/A)
+            b"\x06\x00".to_vec(), // GPS IFD: number of entries (6)
+            ifd_entry(1, 2, 2, *b"N\0\0\0"), // GPSLatitudeRef
+            ifd_entry(2, 5, 3, 104u32.to_le_bytes()), // GPSLatitude, offset 104
+            ifd_entry(3, 2, 2, *b"W\0\0\0"), // GPSLongitudeRef
+            ifd_entry(4, 5, 3, 128u32.to_le_bytes()), // GPSLongitude, offset 128
+            ifd_entry(5, 1, 1, *b"\x01\0\0\0"), // GPSAltitudeRef (below sea level)
+            ifd_entry(6, 5, 1, 152u32.to_le_bytes()), // GPSAltitude, offset 152
+            b"\x00\x00\x00\x00".to_vec(), // GPS IFD: offset to next IFD (0 = N/A)
+            rationals(&[(55, 1), (40, 1), (30, 1)]),
+            rationals(&[(12, 1), (34, 1), (1800, 100)]),
+            rationals(&[(105, 10)]),
+        ].concat();
+        let cursor = Cursor::new(tiff_bytes);
+        let mut tiff_reader = crate::TiffReader::new(cursor).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let gps_info = tiff_reader.subfiles[0].gps_info().unwrap().unwrap();
+        assert!((gps_info.latitude.unwrap() - 55.675).abs() < 1e-9);
+        assert!((gps_info.longitude.unwrap() + 12.57166666666).abs() < 1e-9);
+        assert_eq!(gps_info.altitude, Some(-10.5));
+    }
+}
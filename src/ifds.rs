@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Seek};
+use std::thread::JoinHandle;
+
+use crate::error::TiffReadError;
+use crate::reader::{ReaderRef, Sharing, Threaded};
+use crate::subfile::Subfile;
+use crate::types::Endianness;
+
+type PendingSubfile<R, S> = JoinHandle<Result<Subfile<R, S>, TiffReadError>>;
+type Spawner<R, S> = fn(ReaderRef<R, S>, u32, Endianness) -> PendingSubfile<R, S>;
+
+/// Iterator over the IFD chain, parsing each IFD only when it is
+/// requested. Created by `TiffReader::ifds`. Iteration ends after the
+/// first error, including `TiffReadError::IfdCycle` if the chain loops.
+/// In strict mode, a next-IFD offset below the offset of the IFD it is
+/// stored in is also an error (`TiffReadError::BackwardIfdPointer`),
+/// even if it doesn't lead to a cycle.
+#[derive(Debug)]
+pub struct Ifds<R, S: Sharing = Threaded> {
+    reader_ref: ReaderRef<R, S>,
+    endianness: Endianness,
+    previous_offset: Option<u32>,
+    next_offset: Option<u32>,
+    visited: BTreeSet<u32>,
+    spawner: Option<Spawner<R, S>>,
+    pending: Option<(u32, PendingSubfile<R, S>)>,
+}
+
+impl<R: Read + Seek, S: Sharing> Ifds<R, S> {
+    pub(crate) fn new(reader_ref: ReaderRef<R, S>, endianness: Endianness, first_offset: u32) -> Self {
+        Ifds {
+            reader_ref,
+            endianness,
+            previous_offset: None,
+            next_offset: Some(first_offset),
+            visited: BTreeSet::new(),
+            spawner: None,
+            pending: None,
+        }
+    }
+    
+    fn take_prefetched(&mut self, offset: u32) -> Option<Result<Subfile<R, S>, TiffReadError>> {
+        match self.pending.take() {
+            Some((pending_offset, handle)) if pending_offset == offset => {
+                match handle.join() {
+                    Ok(result) => Some(result),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "prefetch")]
+fn spawn_subfile_parse<R: Read + Seek + Send + 'static>(reader_ref: ReaderRef<R, Threaded>, offset: u32, endianness: Endianness) -> PendingSubfile<R, Threaded> {
+    std::thread::spawn(move || Subfile::new(reader_ref, offset, endianness))
+}
+
+/* Only available with the default `Threaded` sharing, since the subfile
+ * is parsed on another thread. */
+#[cfg(feature = "prefetch")]
+impl<R: Read + Seek + Send + 'static> Ifds<R> {
+    /// Enables read-ahead: after an IFD is returned, the next IFD in the
+    /// chain is parsed on a background thread so that it is ready when
+    /// requested.
+    pub fn prefetch(mut self) -> Self {
+        self.spawner = Some(spawn_subfile_parse::<R>);
+        self
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> Iterator for Ifds<R, S> {
+    type Item = Result<Subfile<R, S>, TiffReadError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next_offset.take()?;
+        if !self.visited.insert(offset) {
+            return Some(Err(TiffReadError::IfdCycle {offset}));
+        }
+        /* Legal files may store IFDs in any order, so this is only a
+         * heuristic for damaged chains and needs strict mode. */
+        if let Some(previous_offset) = self.previous_offset {
+            if offset < previous_offset && self.reader_ref.is_strict() {
+                return Some(Err(TiffReadError::BackwardIfdPointer {offset: previous_offset, next_offset: offset}));
+            }
+        }
+        self.previous_offset = Some(offset);
+        
+        let result = match self.take_prefetched(offset) {
+            Some(result) => result,
+            None => Subfile::new(self.reader_ref.clone(), offset, self.endianness),
+        };
+        let subfile = match result {
+            Ok(subfile) => subfile,
+            Err(err) => return Some(Err(err)),
+        };
+        
+        self.next_offset = subfile.offset_to_next_ifd();
+        if let (Some(spawner), Some(next_offset)) = (self.spawner, self.next_offset) {
+            if !self.visited.contains(&next_offset) {
+                self.pending = Some((next_offset, spawner(self.reader_ref.clone(), next_offset, self.endianness)));
+            }
+        }
+        
+        Some(Ok(subfile))
+    }
+}
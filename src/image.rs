@@ -0,0 +1,928 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{Read, Seek};
+
+use crate::baseline::{AlphaKind, ImageKind};
+use crate::compression::{decode_packbits, PACKBITS};
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::{ReaderRef, SharedReader, Sharing, Threaded};
+use crate::subfile::Subfile;
+use crate::tags;
+use crate::types::Endianness;
+
+/// A single decoded row of pixel data. For planar images
+/// (PlanarConfiguration = 2) a row holds the samples of one plane only.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Row {
+    pub y: u32,
+    pub plane: u16,
+    pub data: Vec<u8>,
+}
+
+/// A processing step applied while decoding an image.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeStep {
+    Decompressed {compression: u16},
+    PredictorUndone {predictor: u16},
+    /// Associated alpha was converted to unassociated alpha, see
+    /// `DecodeOptions::unpremultiply_alpha`.
+    AlphaUnpremultiplied,
+}
+
+/// A processing step that wasn't applied, leaving the data as stored.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SkippedStep {
+    /// Samples weren't converted to BlackIsZero grayscale or RGB, e.g.
+    /// palette indices or YCbCr samples are returned as is.
+    PhotometricNotNormalized {photometric_interpretation: u32},
+    /// Rows are in stored order, not rotated or flipped to the
+    /// Orientation.
+    OrientationNotApplied {orientation: u32},
+    /// Color samples are still premultiplied by alpha, either because it
+    /// wasn't requested or because it is only done for 8 and 16-bit
+    /// chunky (PlanarConfiguration = 1) images.
+    AlphaNotUnpremultiplied,
+}
+
+/// Optional processing for `Subfile::read_image_with_options`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DecodeOptions {
+    /// Divide the color samples of RGBA images with associated alpha by
+    /// alpha, so that the result has unassociated alpha.
+    pub unpremultiply_alpha: bool,
+}
+
+/// What `Subfile::read_image` did to the stored data, and what it left
+/// for the caller to do.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DecodeReport {
+    pub applied: Vec<DecodeStep>,
+    pub skipped: Vec<SkippedStep>,
+    /// For RGBA images, how alpha relates to the color samples in the
+    /// returned data.
+    pub alpha: Option<AlphaKind>,
+}
+
+/// Decoded strips kept by a subfile, up to `capacity` of them. The least
+/// recently used strip is evicted first. A capacity of 0 disables
+/// caching.
+#[derive(Debug)]
+pub(crate) struct SegmentCache {
+    capacity: usize,
+    /* Most recently used last. */
+    segments: VecDeque<(usize, Vec<u8>)>,
+}
+
+impl SegmentCache {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        SegmentCache {capacity, segments: VecDeque::new()}
+    }
+    
+    fn get(&mut self, index: usize) -> Option<Vec<u8>> {
+        let position = self.segments.iter().position(|(cached_index, _)| *cached_index == index)?;
+        let segment = self.segments.remove(position).unwrap();
+        let data = segment.1.clone();
+        self.segments.push_back(segment);
+        Some(data)
+    }
+    
+    fn insert(&mut self, index: usize, data: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.segments.retain(|(cached_index, _)| *cached_index != index);
+        self.segments.push_back((index, data));
+        self.evict_to_capacity();
+    }
+    
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_to_capacity();
+    }
+    
+    fn evict_to_capacity(&mut self) {
+        while self.segments.len() > self.capacity {
+            self.segments.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PlaneLayout {
+    row_bytes: usize,
+    samples_per_pixel: usize,
+    bits_per_sample: Vec<u16>,
+}
+
+impl PlaneLayout {
+    fn new(width: u32, bits_per_sample: Vec<u16>) -> Result<Self, TiffReadError> {
+        let bits_per_pixel: u64 = bits_per_sample.iter().map(|&bits| u64::from(bits)).sum();
+        
+        /* Rows are padded to a whole number of bytes. */
+        let row_bytes = (u64::from(width)*bits_per_pixel).div_ceil(8);
+        let row_bytes = usize::try_from(row_bytes).map_err(|_| ParseError::new(format!("Row size too big ({} bytes)", row_bytes)))?;
+        
+        Ok(PlaneLayout {
+            row_bytes,
+            samples_per_pixel: bits_per_sample.len(),
+            bits_per_sample,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct DecodedStrip {
+    first_row: u32,
+    rows: u32,
+    plane: u16,
+    row_bytes: usize,
+    data: Vec<u8>,
+}
+
+/// Everything needed to locate and decode the strips of a subfile,
+/// gathered up front so that decoding doesn't borrow the subfile.
+#[derive(Debug, Clone)]
+struct StripLayout {
+    offsets: Vec<u32>,
+    byte_counts: Vec<u32>,
+    image_length: u32,
+    rows_per_strip: u32,
+    strips_per_plane: usize,
+    planes: Vec<PlaneLayout>,
+    compression: u16,
+    predictor: u16,
+    endianness: Endianness,
+}
+
+impl StripLayout {
+    fn from_subfile<R: Read + Seek, S: Sharing>(subfile: &mut Subfile<R, S>) -> Result<Self, TiffReadError> {
+        let width = subfile.image_width()?;
+        let image_length = subfile.image_length()?;
+        let rows_per_strip = subfile.rows_per_strip()?;
+        let bits_per_sample = subfile.bits_per_sample()?;
+        let is_planar = subfile.planar_configuration()? == 2;
+        let predictor = subfile.predictor()?;
+        let compression = subfile.get_u32(tags::COMPRESSION)?.unwrap_or(1);
+        let compression = u16::try_from(compression).map_err(|_| TiffReadError::UnsupportedCompression {compression: u16::MAX})?;
+        
+        let offsets = subfile.get_u32_values(tags::STRIP_OFFSETS)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::STRIP_OFFSETS})?;
+        let byte_counts = subfile.get_u32_values(tags::STRIP_BYTE_COUNTS)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::STRIP_BYTE_COUNTS})?;
+        
+        let planes = if is_planar {
+            bits_per_sample.iter().map(|&bits| PlaneLayout::new(width, vec![bits])).collect::<Result<Vec<_>, _>>()?
+        } else {
+            vec![PlaneLayout::new(width, bits_per_sample)?]
+        };
+        
+        let strips_per_plane = segments_along(image_length, rows_per_strip, "RowsPerStrip")? as usize;
+        
+        let strip_count = strips_per_plane*planes.len();
+        if offsets.len() < strip_count || byte_counts.len() < strip_count {
+            return Err(ParseError::new(format!("Expected {} strips, found {} StripOffsets and {} StripByteCounts", strip_count, offsets.len(), byte_counts.len())).into());
+        }
+        
+        Ok(StripLayout {
+            offsets,
+            byte_counts,
+            image_length,
+            rows_per_strip,
+            strips_per_plane,
+            planes,
+            compression,
+            predictor,
+            endianness: subfile.endianness(),
+        })
+    }
+    
+    fn strip_count(&self) -> usize {
+        self.strips_per_plane*self.planes.len()
+    }
+    
+    fn decode_strip<R: Read + Seek, S: Sharing>(&self, reader: &SharedReader<R, S>, index: usize) -> Result<DecodedStrip, TiffReadError> {
+        if index >= self.strip_count() {
+            return Err(TiffReadError::SegmentIndexOutOfRange {index, count: self.strip_count()});
+        }
+        
+        let plane_index = index/self.strips_per_plane;
+        let plane = &self.planes[plane_index];
+        let first_row = (index % self.strips_per_plane) as u32*self.rows_per_strip;
+        let rows = self.rows_per_strip.min(self.image_length - first_row);
+        let expected_len = plane.row_bytes.checked_mul(rows as usize).ok_or_else(|| ParseError::new("Strip size too big".to_string()))?;
+        
+        /* Uncompressed strips must hold exactly their rows. Decoders
+         * stop at `expected_len`, so compressed strips can't expand
+         * beyond it either. */
+        if self.compression == 1 && self.byte_counts[index] as usize != expected_len {
+            return Err(TiffReadError::InconsistentStrips {index, byte_count: self.byte_counts[index], expected: expected_len});
+        }
+        
+        let raw = reader.read_bytes_at(u64::from(self.offsets[index]), self.byte_counts[index] as usize)?;
+        let mut data = match self.compression {
+            1 => raw,
+            PACKBITS => decode_packbits(&raw, expected_len)?,
+            compression => return Err(TiffReadError::UnsupportedCompression {compression}),
+        };
+        
+        if data.len() < expected_len {
+            return Err(ParseError::new(format!("Strip {} decoded to {} bytes, expected {}", index, data.len(), expected_len)).into());
+        }
+        data.truncate(expected_len);
+        
+        match self.predictor {
+            1 => {},
+            2 => undo_horizontal_differencing(&mut data, plane, self.endianness)?,
+            predictor => return Err(TiffReadError::UnsupportedPredictor {predictor, bits_per_sample: plane.bits_per_sample[0]}),
+        }
+        
+        Ok(DecodedStrip {
+            first_row,
+            rows,
+            plane: plane_index as u16,
+            row_bytes: plane.row_bytes,
+            data,
+        })
+    }
+}
+
+/* The number of segments of size `segment_len` needed to cover `len`
+ * pixels, i.e. `len/segment_len` rounded up. An empty image has no
+ * segments, whatever their size (RowsPerStrip is clamped to 0 then). */
+fn segments_along(len: u32, segment_len: u32, name: &str) -> Result<u32, TiffReadError> {
+    if len == 0 {
+        return Ok(0);
+    }
+    if segment_len == 0 {
+        return Err(ParseError::new(format!("{} is 0", name)).into());
+    }
+    Ok(len.div_ceil(segment_len))
+}
+
+/// Reverses Predictor = 2 (horizontal differencing), in place. Every
+/// sample in the plane must have the same bit depth of 8, 16 or 32.
+fn undo_horizontal_differencing(data: &mut [u8], plane: &PlaneLayout, endianness: Endianness) -> Result<(), TiffReadError> {
+    let bits = plane.bits_per_sample[0];
+    if plane.bits_per_sample.iter().any(|&other_bits| other_bits != bits) || plane.row_bytes == 0 {
+        return Err(TiffReadError::UnsupportedPredictor {predictor: 2, bits_per_sample: bits});
+    }
+    
+    let stride = plane.samples_per_pixel;
+    for row in data.chunks_exact_mut(plane.row_bytes) {
+        match bits {
+            8 => {
+                for i in stride..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - stride]);
+                }
+            }
+            16 => {
+                let read = |bytes: &[u8]| match endianness {
+                    Endianness::Little => u16::from_le_bytes([bytes[0], bytes[1]]),
+                    Endianness::Big => u16::from_be_bytes([bytes[0], bytes[1]]),
+                };
+                for i in stride..row.len()/2 {
+                    let value = read(&row[2*i..]).wrapping_add(read(&row[2*(i - stride)..]));
+                    let value_bytes = match endianness {
+                        Endianness::Little => value.to_le_bytes(),
+                        Endianness::Big => value.to_be_bytes(),
+                    };
+                    row[2*i..2*i + 2].copy_from_slice(&value_bytes);
+                }
+            }
+            32 => {
+                let read = |bytes: &[u8]| match endianness {
+                    Endianness::Little => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                    Endianness::Big => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                };
+                for i in stride..row.len()/4 {
+                    let value = read(&row[4*i..]).wrapping_add(read(&row[4*(i - stride)..]));
+                    let value_bytes = match endianness {
+                        Endianness::Little => value.to_le_bytes(),
+                        Endianness::Big => value.to_be_bytes(),
+                    };
+                    row[4*i..4*i + 4].copy_from_slice(&value_bytes);
+                }
+            }
+            _ => return Err(TiffReadError::UnsupportedPredictor {predictor: 2, bits_per_sample: bits}),
+        }
+    }
+    
+    Ok(())
+}
+
+/* Divides a color sample by alpha, both at most `max`. Fully
+ * transparent pixels become black. */
+pub(crate) fn unpremultiply(color: u32, alpha: u32, max: u32) -> u32 {
+    if alpha == 0 {0} else {((u64::from(color)*u64::from(max) + u64::from(alpha/2))/u64::from(alpha)).min(u64::from(max)) as u32}
+}
+
+/// Divides the color samples of chunky RGBA pixels of 8 or 16 bits per
+/// sample by alpha, rounding to nearest. Pixels with an alpha of 0 become
+/// all zero.
+fn unpremultiply_alpha(data: &mut [u8], bits: u16, endianness: Endianness) {
+    if bits == 8 {
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = u32::from(pixel[3]);
+            for sample in &mut pixel[..3] {
+                *sample = unpremultiply(u32::from(*sample), alpha, 255) as u8;
+            }
+        }
+    } else {
+        let read = |bytes: &[u8]| match endianness {
+            Endianness::Little => u16::from_le_bytes([bytes[0], bytes[1]]),
+            Endianness::Big => u16::from_be_bytes([bytes[0], bytes[1]]),
+        };
+        for pixel in data.chunks_exact_mut(8) {
+            let alpha = u32::from(read(&pixel[6..8]));
+            for sample in pixel[..6].chunks_exact_mut(2) {
+                let value = unpremultiply(u32::from(read(sample)), alpha, 65535) as u16;
+                let bytes = match endianness {
+                    Endianness::Little => value.to_le_bytes(),
+                    Endianness::Big => value.to_be_bytes(),
+                };
+                sample.copy_from_slice(&bytes);
+            }
+        }
+    }
+}
+
+/// Unpacks a row of 1, 2 or 4-bit samples, packed MSB-first, into one
+/// byte per sample. The padding bits at the end of the row are ignored.
+///
+/// Panics if `bits_per_sample` is not 1, 2 or 4, or if `row_bytes` is
+/// too short to hold `width` samples.
+pub fn unpack_subbyte_samples(row_bytes: &[u8], width: usize, bits_per_sample: u16) -> Vec<u8> {
+    assert!([1, 2, 4].contains(&bits_per_sample), "Unsupported sub-byte sample size: {}", bits_per_sample);
+    let bits = usize::from(bits_per_sample);
+    let samples_per_byte = 8/bits;
+    assert!(row_bytes.len() >= width.div_ceil(samples_per_byte), "Row of {} bytes is too short for {} samples", row_bytes.len(), width);
+    
+    let mask = (1u8 << bits) - 1;
+    (0..width).map(|x| {
+        let shift = 8 - bits*(x % samples_per_byte + 1);
+        (row_bytes[x/samples_per_byte] >> shift) & mask
+    }).collect()
+}
+
+/// Iterator over the decoded rows of a stripped image, created by
+/// `Subfile::strip_rows`. Only one strip is held in memory at a time.
+#[derive(Debug)]
+pub struct StripRows<R, S: Sharing = Threaded> {
+    reader_ref: ReaderRef<R, S>,
+    layout: Option<StripLayout>,
+    error: Option<TiffReadError>,
+    next_strip: usize,
+    current_strip: Option<DecodedStrip>,
+    next_row_in_strip: u32,
+}
+
+impl<R: Read + Seek, S: Sharing> Iterator for StripRows<R, S> {
+    type Item = Result<Row, TiffReadError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        
+        loop {
+            if let Some(strip) = &self.current_strip {
+                if self.next_row_in_strip < strip.rows {
+                    let start = self.next_row_in_strip as usize*strip.row_bytes;
+                    let row = Row {
+                        y: strip.first_row + self.next_row_in_strip,
+                        plane: strip.plane,
+                        data: strip.data[start..start + strip.row_bytes].to_vec(),
+                    };
+                    self.next_row_in_strip += 1;
+                    return Some(Ok(row));
+                }
+            }
+            
+            /* Drop the exhausted strip before reading the next one. */
+            self.current_strip = None;
+            
+            let layout = self.layout.as_ref()?;
+            if self.next_strip >= layout.strip_count() {
+                return None;
+            }
+            
+            match layout.decode_strip(&self.reader_ref, self.next_strip) {
+                Ok(strip) => {
+                    self.current_strip = Some(strip);
+                    self.next_row_in_strip = 0;
+                    self.next_strip += 1;
+                }
+                Err(err) => {
+                    self.layout = None;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Reads and decodes a single strip, returning its rows as packed
+    /// bytes with any predictor undone.
+    ///
+    /// If a segment cache has been set up with
+    /// `TiffReaderOptions::segment_cache_capacity` or
+    /// `set_segment_cache_capacity`, a cached strip is returned without
+    /// reading any fields or image data, and newly decoded strips are
+    /// cached. `reinterpret_field` and `unload_all_field_values` clear the
+    /// cache; after changing a field through `get_field_mut`, call
+    /// `evict_segments` if the change affects the strips.
+    pub fn read_strip(&mut self, index: usize) -> Result<Vec<u8>, TiffReadError> {
+        if let Some(data) = self.segment_cache_mut().get(index) {
+            return Ok(data);
+        }
+        
+        let layout = StripLayout::from_subfile(self)?;
+        let count = layout.strip_count();
+        if index >= count {
+            return Err(TiffReadError::SegmentIndexOutOfRange {index, count});
+        }
+        let data = layout.decode_strip(self.reader_ref(), index)?.data;
+        self.segment_cache_mut().insert(index, data.clone());
+        Ok(data)
+    }
+    
+    /// Returns the number of strips the image should have according to
+    /// ImageLength and RowsPerStrip, times SamplesPerPixel for planar
+    /// images. Valid indices for `read_strip` are below this.
+    pub fn strip_count(&mut self) -> Result<u32, TiffReadError> {
+        let strips_per_plane = segments_along(self.image_length()?, self.rows_per_strip()?, "RowsPerStrip")?;
+        self.times_planes(strips_per_plane)
+    }
+    
+    /// Returns (first row, row count) for each strip, in the order of
+    /// `read_strip`'s indices. All strips hold RowsPerStrip rows except
+    /// the last one of each plane, which holds what is left.
+    pub fn strip_row_ranges(&mut self) -> Result<Vec<(u32, u32)>, TiffReadError> {
+        let image_length = self.image_length()?;
+        let rows_per_strip = self.rows_per_strip()?;
+        let strips_per_plane = segments_along(image_length, rows_per_strip, "RowsPerStrip")?;
+        let planes = self.times_planes(1)?;
+        
+        let plane_ranges = (0..strips_per_plane).map(|strip| {
+            let first_row = strip*rows_per_strip;
+            (first_row, rows_per_strip.min(image_length - first_row))
+        });
+        Ok((0..planes).flat_map(|_| plane_ranges.clone()).collect())
+    }
+    
+    /// Returns the number of tiles the image should have according to
+    /// its dimensions and TileWidth and TileLength, times SamplesPerPixel
+    /// for planar images.
+    pub fn tile_count(&mut self) -> Result<u32, TiffReadError> {
+        let tile_width = self.get_u32(tags::TILE_WIDTH)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::TILE_WIDTH})?;
+        let tile_length = self.get_u32(tags::TILE_LENGTH)?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::TILE_LENGTH})?;
+        let tiles_across = segments_along(self.image_width()?, tile_width, "TileWidth")?;
+        let tiles_down = segments_along(self.image_length()?, tile_length, "TileLength")?;
+        
+        let tiles_per_plane = tiles_across.checked_mul(tiles_down)
+            .ok_or_else(|| ParseError::new(format!("{} by {} tiles is too many", tiles_across, tiles_down)))?;
+        self.times_planes(tiles_per_plane)
+    }
+    
+    fn times_planes(&mut self, segments_per_plane: u32) -> Result<u32, TiffReadError> {
+        let planes = if self.planar_configuration()? == 2 {u32::from(self.samples_per_pixel()?)} else {1};
+        segments_per_plane.checked_mul(planes)
+            .ok_or_else(|| ParseError::new(format!("{} segments in each of {} planes is too many", segments_per_plane, planes)).into())
+    }
+    
+    /// Sets how many decoded strips `read_strip` keeps in memory, evicting
+    /// the least recently used ones if more are cached. 0 disables
+    /// caching.
+    pub fn set_segment_cache_capacity(&mut self, capacity: usize) {
+        self.segment_cache_mut().set_capacity(capacity);
+    }
+    
+    /// Drops all cached strips, keeping the cache capacity.
+    pub fn evict_segments(&mut self) {
+        self.segment_cache_mut().segments.clear();
+    }
+    
+    /// Returns an iterator decoding the image row by row, one strip at a
+    /// time. Errors (including missing or invalid layout fields) are
+    /// returned from the iterator, after which it ends.
+    pub fn strip_rows(&mut self) -> StripRows<R, S> {
+        let (layout, error) = match StripLayout::from_subfile(self) {
+            Ok(layout) => (Some(layout), None),
+            Err(err) => (None, Some(err)),
+        };
+        
+        StripRows {
+            reader_ref: self.reader_ref().clone(),
+            layout,
+            error,
+            next_strip: 0,
+            current_strip: None,
+            next_row_in_strip: 0,
+        }
+    }
+    
+    /// Decodes every strip and concatenates the rows, plane by plane for
+    /// planar images, along with a report of the processing steps that
+    /// were applied and skipped.
+    pub fn read_image(&mut self) -> Result<(Vec<u8>, DecodeReport), TiffReadError> {
+        self.read_image_with_options(DecodeOptions::default())
+    }
+    
+    /// Like `read_image`, with optional processing steps.
+    pub fn read_image_with_options(&mut self, options: DecodeOptions) -> Result<(Vec<u8>, DecodeReport), TiffReadError> {
+        let layout = StripLayout::from_subfile(self)?;
+        
+        let mut data = Vec::new();
+        for index in 0..layout.strip_count() {
+            data.extend(layout.decode_strip(self.reader_ref(), index)?.data);
+        }
+        
+        let mut report = DecodeReport::default();
+        if layout.compression != 1 {
+            report.applied.push(DecodeStep::Decompressed {compression: layout.compression});
+        }
+        if layout.predictor != 1 {
+            report.applied.push(DecodeStep::PredictorUndone {predictor: layout.predictor});
+        }
+        /* BlackIsZero and RGB are returned as is, which is already the
+         * normal form. */
+        match self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)? {
+            None | Some(1) | Some(2) => {},
+            Some(photometric_interpretation) => report.skipped.push(SkippedStep::PhotometricNotNormalized {photometric_interpretation}),
+        }
+        match self.get_u32(tags::ORIENTATION)? {
+            None | Some(1) => {},
+            Some(orientation) => report.skipped.push(SkippedStep::OrientationNotApplied {orientation}),
+        }
+        
+        if let ImageKind::Rgba {alpha} = self.image_kind()? {
+            report.alpha = Some(alpha);
+            if alpha == AlphaKind::Associated {
+                let bits = &layout.planes[0].bits_per_sample;
+                let is_chunky = layout.planes.len() == 1;
+                if options.unpremultiply_alpha && is_chunky && bits.iter().all(|&other_bits| other_bits == bits[0]) && (bits[0] == 8 || bits[0] == 16) {
+                    unpremultiply_alpha(&mut data, bits[0], layout.endianness);
+                    report.applied.push(DecodeStep::AlphaUnpremultiplied);
+                    report.alpha = Some(AlphaKind::Unassociated);
+                } else {
+                    report.skipped.push(SkippedStep::AlphaNotUnpremultiplied);
+                }
+            }
+        }
+        
+        Ok((data, report))
+    }
+    
+    /// Reads the raw, still compressed bytes of every strip (or tile, if
+    /// TileOffsets is present) in order and concatenates them. Unlike
+    /// `read_strip`, nothing is decoded.
+    pub fn read_all_image_data(&mut self) -> Result<Vec<u8>, TiffReadError> {
+        let (offsets_tag, byte_counts_tag) = if self.contains(tags::TILE_OFFSETS) {
+            (tags::TILE_OFFSETS, tags::TILE_BYTE_COUNTS)
+        } else {
+            (tags::STRIP_OFFSETS, tags::STRIP_BYTE_COUNTS)
+        };
+        let offsets = self.get_u32_values(offsets_tag)?.ok_or(TiffReadError::MissingRequiredTag {tag: offsets_tag})?;
+        let byte_counts = self.get_u32_values(byte_counts_tag)?.ok_or(TiffReadError::MissingRequiredTag {tag: byte_counts_tag})?;
+        if offsets.len() != byte_counts.len() {
+            return Err(ParseError::new(format!("Found {} segment offsets but {} byte counts", offsets.len(), byte_counts.len())).into());
+        }
+        
+        /* Each read is checked against the stream length, so the
+         * capacity is only reserved once it is known to be sane. */
+        let total_len: u64 = byte_counts.iter().map(|&byte_count| u64::from(byte_count)).sum();
+        let mut data = Vec::with_capacity(if total_len <= self.reader_ref().stream_len() {total_len as usize} else {0});
+        for (&offset, &byte_count) in offsets.iter().zip(&byte_counts) {
+            data.extend(self.read_bytes_at(u64::from(offset), byte_count as usize)?);
+        }
+        
+        Ok(data)
+    }
+    
+    /// Size in bytes of the decoded image, with every row padded to a
+    /// whole number of bytes (per plane, for planar images).
+    pub fn uncompressed_image_size(&mut self) -> Result<u64, TiffReadError> {
+        let width = u64::from(self.image_width()?);
+        let image_length = u64::from(self.image_length()?);
+        let bits_per_sample = self.bits_per_sample()?;
+        let plane_bits: Vec<u64> = if self.planar_configuration()? == 2 {
+            bits_per_sample.iter().map(|&bits| u64::from(bits)).collect()
+        } else {
+            vec![bits_per_sample.iter().map(|&bits| u64::from(bits)).sum()]
+        };
+        
+        let too_big = || ParseError::new("Image size too big".to_string());
+        let mut row_bytes: u64 = 0;
+        for bits in plane_bits {
+            let plane_row_bytes = width.checked_mul(bits).ok_or_else(too_big)?.div_ceil(8);
+            row_bytes = row_bytes.checked_add(plane_row_bytes).ok_or_else(too_big)?;
+        }
+        Ok(row_bytes.checked_mul(image_length).ok_or_else(too_big)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use super::{DecodeOptions, DecodeReport, DecodeStep, Row, SkippedStep, unpack_subbyte_samples};
+    use crate::baseline::AlphaKind;
+    
+    fn ifd_entry(tag: u16, field_type: u16, count: u32, value_offset: [u8; 4]) -> Vec<u8> {
+        [&tag.to_le_bytes()[..], &field_type.to_le_bytes(), &count.to_le_bytes(), &value_offset].concat()
+    }
+    
+    fn short(value: u16) -> [u8; 4] {
+        let bytes = value.to_le_bytes();
+        [bytes[0], bytes[1], 0, 0]
+    }
+    
+    /* 4x3 8-bit grayscale image in two strips (2 rows + 1 row). */
+    fn two_strip_tiff(predictor: u16, pixels: &[u8]) -> Vec<u8> {
+        [
+            b"II\x2A\x00\x08\x00\x00\x00".to_vec(), // image file header, offset 8 to first IFD
+            b"\x08\x00".to_vec(), // IFD: number of entries (8)
+            ifd_entry(256, 3, 1, short(4)), // ImageWidth
+            ifd_entry(257, 3, 1, short(3)), // ImageLength
+            ifd_entry(258, 3, 1, short(8)), // BitsPerSample
+            ifd_entry(262, 3, 1, short(1)), // PhotometricInterpretation
+            ifd_entry(273, 3, 2, [110, 0, 118, 0]), // StripOffsets (110, 118)
+            ifd_entry(278, 3, 1, short(2)), // RowsPerStrip
+            ifd_entry(279, 3, 2, [8, 0, 4, 0]), // StripByteCounts (8, 4)
+            ifd_entry(317, 3, 1, short(predictor)), // Predictor
+            b"\x00\x00\x00\x00".to_vec(), // IFD: offset to next IFD (0 = N/A)
+            pixels.to_vec(),
+        ].concat()
+    }
+    
+    #[test]
+    fn stream_rows_from_strips() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_strip_tiff(1, &pixels))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let rows: Vec<Row> = tiff_reader.subfiles[0].strip_rows().collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![
+            Row {y: 0, plane: 0, data: vec![0, 1, 2, 3]},
+            Row {y: 1, plane: 0, data: vec![4, 5, 6, 7]},
+            Row {y: 2, plane: 0, data: vec![8, 9, 10, 11]},
+        ]);
+    }
+    
+    #[test]
+    fn undo_horizontal_differencing_predictor() {
+        let differences = [10, 1, 1, 1, 20, 2, 2, 2, 30, 255, 255, 255];
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_strip_tiff(2, &differences))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        assert_eq!(tiff_reader.subfiles[0].read_strip(0).unwrap(), vec![10, 11, 12, 13, 20, 22, 24, 26]);
+        assert_eq!(tiff_reader.subfiles[0].read_strip(1).unwrap(), vec![30, 29, 28, 27]);
+    }
+    
+    #[test]
+    fn reject_out_of_range_strip_index() {
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_strip_tiff(1, &[0; 12]))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        subfile.set_segment_cache_capacity(4);
+        
+        for &index in &[2, 3, usize::MAX] {
+            match subfile.read_strip(index) {
+                Err(crate::error::TiffReadError::SegmentIndexOutOfRange {index: found, count: 2}) => assert_eq!(found, index),
+                other => panic!("Expected SegmentIndexOutOfRange, got {:?}", other),
+            }
+        }
+        assert_eq!(subfile.read_strip(1).unwrap().len(), 4);
+    }
+    
+    #[test]
+    fn read_image_with_report() {
+        let differences = [10, 1, 1, 1, 20, 2, 2, 2, 30, 255, 255, 255];
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_strip_tiff(2, &differences))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let (data, report) = tiff_reader.subfiles[0].read_image().unwrap();
+        assert_eq!(data, vec![10, 11, 12, 13, 20, 22, 24, 26, 30, 29, 28, 27]);
+        assert_eq!(report, DecodeReport {applied: vec![DecodeStep::PredictorUndone {predictor: 2}], skipped: vec![], alpha: None});
+        
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![2]))
+            .entry(257, crate::FieldValue::Short(vec![1]))
+            .entry(258, crate::FieldValue::Short(vec![8]))
+            .entry(259, crate::FieldValue::Short(vec![32773]))
+            .entry(262, crate::FieldValue::Short(vec![3]))
+            .entry(274, crate::FieldValue::Short(vec![6]))
+            .strips(vec![b"\xFF\x05".to_vec()])
+            .reader();
+        let (data, report) = tiff_reader.subfiles[0].read_image().unwrap();
+        assert_eq!(data, vec![5, 5]);
+        assert_eq!(report.applied, vec![DecodeStep::Decompressed {compression: 32773}]);
+        assert_eq!(report.skipped, vec![
+            SkippedStep::PhotometricNotNormalized {photometric_interpretation: 3},
+            SkippedStep::OrientationNotApplied {orientation: 6},
+        ]);
+    }
+    
+    #[test]
+    fn report_out_of_range_codes_without_truncating() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![1]))
+            .entry(257, crate::FieldValue::Short(vec![1]))
+            .entry(262, crate::FieldValue::Long(vec![0x1_0003]))
+            .entry(274, crate::FieldValue::Long(vec![0x1_0006]))
+            .strips(vec![vec![0]])
+            .reader();
+        let (_, report) = tiff_reader.subfiles[0].read_image().unwrap();
+        assert_eq!(report.skipped, vec![
+            SkippedStep::PhotometricNotNormalized {photometric_interpretation: 0x1_0003},
+            SkippedStep::OrientationNotApplied {orientation: 0x1_0006},
+        ]);
+    }
+    
+    #[test]
+    fn cached_strips_are_not_read_again() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let reader = crate::testing::CountingReader::new(Cursor::new(two_strip_tiff(1, &pixels)));
+        let counter = reader.counter();
+        let options = crate::TiffReaderOptions::default().buffer_capacity(1).segment_cache_capacity(1);
+        let mut tiff_reader = crate::TiffReader::with_options(reader, options).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        assert_eq!(subfile.read_strip(1).unwrap(), vec![8, 9, 10, 11]);
+        counter.reset();
+        assert_eq!(subfile.read_strip(1).unwrap(), vec![8, 9, 10, 11]);
+        assert_eq!((counter.reads(), counter.seeks()), (0, 0));
+        
+        /* Reading strip 0 evicts strip 1. */
+        subfile.read_strip(0).unwrap();
+        counter.reset();
+        subfile.read_strip(1).unwrap();
+        assert!(counter.reads() > 0);
+        
+        subfile.evict_segments();
+        counter.reset();
+        subfile.read_strip(1).unwrap();
+        assert!(counter.reads() > 0);
+        
+        /* Borrowing a field mutably leaves the cache alone, while
+         * replacing or unloading field values clears it. */
+        subfile.get_field_mut(crate::tags::STRIP_OFFSETS).unwrap();
+        counter.reset();
+        subfile.read_strip(1).unwrap();
+        assert_eq!(counter.reads(), 0);
+        
+        subfile.unload_all_field_values();
+        counter.reset();
+        subfile.read_strip(1).unwrap();
+        assert!(counter.reads() > 0);
+        
+        subfile.reinterpret_field(crate::tags::STRIP_OFFSETS, crate::FieldType::Short).unwrap();
+        counter.reset();
+        subfile.read_strip(1).unwrap();
+        assert!(counter.reads() > 0);
+    }
+    
+    #[test]
+    fn unpremultiply_associated_alpha() {
+        let build = |endianness, bits: u16, pixels: Vec<u8>| crate::test_util::TiffBuilder::new(endianness)
+            .entry(256, crate::FieldValue::Short(vec![2]))
+            .entry(257, crate::FieldValue::Short(vec![1]))
+            .entry(258, crate::FieldValue::Short(vec![bits; 4]))
+            .entry(262, crate::FieldValue::Short(vec![2]))
+            .entry(277, crate::FieldValue::Short(vec![4]))
+            .entry(338, crate::FieldValue::Short(vec![1]))
+            .strips(vec![pixels])
+            .reader();
+        let options = DecodeOptions {unpremultiply_alpha: true};
+        
+        let mut tiff_reader = build(crate::Endianness::Little, 8, vec![50, 100, 0, 128, 9, 9, 9, 0]);
+        let (data, report) = tiff_reader.subfiles[0].read_image().unwrap();
+        assert_eq!(data, vec![50, 100, 0, 128, 9, 9, 9, 0]);
+        assert_eq!((report.alpha, report.skipped), (Some(AlphaKind::Associated), vec![SkippedStep::AlphaNotUnpremultiplied]));
+        
+        let (data, report) = tiff_reader.subfiles[0].read_image_with_options(options).unwrap();
+        assert_eq!(data, vec![100, 199, 0, 128, 0, 0, 0, 0]);
+        assert_eq!(report.applied, vec![DecodeStep::AlphaUnpremultiplied]);
+        assert_eq!(report.alpha, Some(AlphaKind::Unassociated));
+        
+        let pixels = [0x4000u16, 0xFFFF, 0, 0x8000, 0, 0, 0, 0].iter().flat_map(|value| value.to_be_bytes().to_vec()).collect();
+        let mut tiff_reader = build(crate::Endianness::Big, 16, pixels);
+        let (data, _) = tiff_reader.subfiles[0].read_image_with_options(options).unwrap();
+        let expected: Vec<u8> = [0x8000u16, 0xFFFF, 0, 0x8000, 0, 0, 0, 0].iter().flat_map(|value| value.to_be_bytes().to_vec()).collect();
+        assert_eq!(data, expected);
+    }
+    
+    #[test]
+    fn reject_inconsistent_strip_byte_counts() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![2]))
+            .entry(257, crate::FieldValue::Short(vec![2]))
+            .entry(258, crate::FieldValue::Short(vec![8]))
+            .entry(278, crate::FieldValue::Short(vec![1]))
+            .strips(vec![vec![1, 2, 3], vec![4, 5]])
+            .reader();
+        
+        match tiff_reader.subfiles[0].read_strip(0) {
+            Err(crate::error::TiffReadError::InconsistentStrips {index: 0, byte_count: 3, expected: 2}) => {},
+            other => panic!("Expected InconsistentStrips, got {:?}", other),
+        }
+        assert_eq!(tiff_reader.subfiles[0].read_strip(1).unwrap(), vec![4, 5]);
+    }
+    
+    #[test]
+    fn unpack_sub_byte_samples() {
+        assert_eq!(unpack_subbyte_samples(&[0b1010_0000], 3, 1), vec![1, 0, 1]);
+        assert_eq!(unpack_subbyte_samples(&[0b1110_0100, 0b1100_0000], 5, 2), vec![3, 2, 1, 0, 3]);
+        assert_eq!(unpack_subbyte_samples(&[0x12, 0x30], 3, 4), vec![1, 2, 3]);
+    }
+    
+    #[test]
+    fn read_packbits_strip() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![3]))
+            .entry(257, crate::FieldValue::Short(vec![2]))
+            .entry(258, crate::FieldValue::Short(vec![8]))
+            .entry(259, crate::FieldValue::Short(vec![32773]))
+            .strips(vec![b"\xFD\x07\x01\x01\x02".to_vec()])
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].read_strip(0).unwrap(), vec![7, 7, 7, 7, 1, 2]);
+        assert_eq!(tiff_reader.subfiles[0].read_all_image_data().unwrap(), b"\xFD\x07\x01\x01\x02".to_vec());
+    }
+    
+    #[test]
+    fn compute_uncompressed_image_size() {
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_strip_tiff(1, &[0; 12]))).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        assert_eq!(tiff_reader.subfiles[0].uncompressed_image_size().unwrap(), 12);
+        
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![5]))
+            .entry(257, crate::FieldValue::Short(vec![3]))
+            .entry(258, crate::FieldValue::Short(vec![1, 4]))
+            .entry(277, crate::FieldValue::Short(vec![2]))
+            .entry(284, crate::FieldValue::Short(vec![2]))
+            .reader();
+        assert_eq!(tiff_reader.subfiles[0].uncompressed_image_size().unwrap(), (1 + 3)*3);
+    }
+    
+    #[test]
+    fn compute_strip_row_ranges() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(257, crate::FieldValue::Short(vec![10]))
+            .entry(278, crate::FieldValue::Short(vec![4]))
+            .ifd()
+            .entry(257, crate::FieldValue::Short(vec![8]))
+            .entry(277, crate::FieldValue::Short(vec![2]))
+            .entry(278, crate::FieldValue::Short(vec![4]))
+            .entry(284, crate::FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(257, crate::FieldValue::Short(vec![3]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].strip_row_ranges().unwrap(), vec![(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(tiff_reader.subfiles[1].strip_row_ranges().unwrap(), vec![(0, 4), (4, 4), (0, 4), (4, 4)]);
+        assert_eq!(tiff_reader.subfiles[2].strip_row_ranges().unwrap(), vec![(0, 3)]);
+        for subfile in &mut tiff_reader.subfiles {
+            assert_eq!(subfile.strip_row_ranges().unwrap().len() as u32, subfile.strip_count().unwrap());
+        }
+    }
+    
+    #[test]
+    fn count_strips_and_tiles() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Short(vec![100]))
+            .entry(257, crate::FieldValue::Short(vec![50]))
+            .entry(277, crate::FieldValue::Short(vec![3]))
+            .entry(278, crate::FieldValue::Short(vec![16]))
+            .entry(322, crate::FieldValue::Short(vec![32]))
+            .entry(323, crate::FieldValue::Short(vec![16]))
+            .ifd()
+            .entry(256, crate::FieldValue::Short(vec![100]))
+            .entry(257, crate::FieldValue::Short(vec![50]))
+            .entry(277, crate::FieldValue::Short(vec![3]))
+            .entry(284, crate::FieldValue::Short(vec![2]))
+            .entry(322, crate::FieldValue::Short(vec![64]))
+            .entry(323, crate::FieldValue::Short(vec![0]))
+            .ifd()
+            .entry(256, crate::FieldValue::Short(vec![100]))
+            .reader();
+        
+        let chunky = &mut tiff_reader.subfiles[0];
+        assert_eq!(chunky.strip_count().unwrap(), 4);
+        assert_eq!(chunky.tile_count().unwrap(), 4*4);
+        
+        /* No RowsPerStrip means a single strip per plane. */
+        let planar = &mut tiff_reader.subfiles[1];
+        assert_eq!(planar.strip_count().unwrap(), 3);
+        assert!(planar.tile_count().is_err());
+        
+        match tiff_reader.subfiles[2].strip_count() {
+            Err(crate::error::TiffReadError::MissingRequiredTag {tag: 257}) => {},
+            other => panic!("Expected MissingRequiredTag, got {:?}", other),
+        }
+        match tiff_reader.subfiles[2].tile_count() {
+            Err(crate::error::TiffReadError::MissingRequiredTag {tag: 322}) => {},
+            other => panic!("Expected MissingRequiredTag, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Returns the ink names from InkNames (333), one per NUL-separated
+    /// string. If NumberOfInks (334) is present, the number of names
+    /// must match it.
+    pub fn ink_names(&mut self) -> Result<Option<Vec<String>>, TiffReadError> {
+        let ink_names = match self.get_ascii_strings(tags::INK_NAMES)? {
+            Some(ink_names) => ink_names,
+            None => return Ok(None),
+        };
+        
+        if let Some(number_of_inks) = self.get_u32(tags::NUMBER_OF_INKS)? {
+            if ink_names.len() as u32 != number_of_inks {
+                return Err(ParseError::new(format!("InkNames holds {} names, but NumberOfInks is {}", ink_names.len(), number_of_inks)).into());
+            }
+        }
+        
+        Ok(Some(ink_names))
+    }
+    
+    /// Returns DotRange (336): the component values for 0% and 100% dot.
+    /// If a pair is given for every ink, the first pair is returned.
+    pub fn dot_range(&mut self) -> Result<Option<[u16; 2]>, TiffReadError> {
+        self.get_u16_pair(tags::DOT_RANGE, "DotRange")
+    }
+    
+    /// Returns HalftoneHints (321): the highlight and shadow values.
+    pub fn halftone_hints(&mut self) -> Result<Option<[u16; 2]>, TiffReadError> {
+        self.get_u16_pair(tags::HALFTONE_HINTS, "HalftoneHints")
+    }
+    
+    fn get_u16_pair(&mut self, tag: u16, name: &str) -> Result<Option<[u16; 2]>, TiffReadError> {
+        let values = match self.get_u32_values(tag)? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        match values[..] {
+            [low, high, ..] if values.len() % 2 == 0 && low <= u32::from(u16::MAX) && high <= u32::from(u16::MAX) => Ok(Some([low as u16, high as u16])),
+            _ => Err(ParseError::new(format!("Invalid {}: {:?}", name, values)).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TiffReadError;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn read_ink_names() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(333, FieldValue::Ascii("Cyan\0Magenta\0Yellow\0Black".to_string()))
+            .entry(334, FieldValue::Short(vec![4]))
+            .ifd()
+            .entry(333, FieldValue::Ascii("C\0M".to_string()))
+            .entry(334, FieldValue::Short(vec![2]))
+            .ifd()
+            .entry(333, FieldValue::Ascii("C\0M".to_string()))
+            .entry(334, FieldValue::Short(vec![3]))
+            .ifd()
+            .raw_entry(333, 99, 4, b"C\0M\0".to_vec())
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].ink_names().unwrap(), Some(vec!["Cyan".to_string(), "Magenta".to_string(), "Yellow".to_string(), "Black".to_string()]));
+        assert_eq!(tiff_reader.subfiles[1].ink_names().unwrap(), Some(vec!["C".to_string(), "M".to_string()]));
+        assert!(tiff_reader.subfiles[2].ink_names().is_err());
+        match tiff_reader.subfiles[3].ink_names() {
+            Err(TiffReadError::UnknownFieldType {tag: 333, raw: 99}) => {},
+            other => panic!("Expected UnknownFieldType, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn read_dot_range_and_halftone_hints() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Big)
+            .entry(321, FieldValue::Short(vec![10, 240]))
+            .entry(336, FieldValue::Byte(vec![5, 250]))
+            .ifd()
+            .entry(336, FieldValue::Short(vec![1, 2, 3]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].dot_range().unwrap(), Some([5, 250]));
+        assert_eq!(tiff_reader.subfiles[0].halftone_hints().unwrap(), Some([10, 240]));
+        assert!(tiff_reader.subfiles[1].dot_range().is_err());
+        assert_eq!(tiff_reader.subfiles[1].halftone_hints().unwrap(), None);
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+use crate::error::TiffReadError;
+use crate::reader::Sharing;
+use crate::subfile::{Subfile, ValueLocation};
+use crate::{tags, TiffReader};
+
+/// A suspicious out-of-line value region found by
+/// `Subfile::check_value_region_integrity`, or an unexpected value found
+/// by `Subfile::validate_enumerated_tags` or `TiffReader::sanity_check`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum IntegrityWarning {
+    /// The value region extends past the end of the stream.
+    PastEndOfFile {tag: u16, offset: u64, len: usize},
+    /// The value regions of two fields overlap.
+    OverlapsField {tag: u16, other_tag: u16},
+    /// The value region overlaps the IFD it is described by.
+    OverlapsIfd {tag: u16},
+    /// An enumerated field holds a value not defined for it.
+    UnexpectedValue {tag: u16, value: u32},
+    /// A field holds a value so far out of range that the byte order is
+    /// likely wrong or the IFD is corrupt.
+    ImplausibleValue {tag: u16, value: u32},
+}
+
+/* The values defined for enumerated fields in TIFF 6.0, plus
+ * Compression = 7 and 8 from Technical Note 2 and Predictor = 3 from
+ * Technical Note 3. */
+const ENUMERATED_TAG_VALUES: [(u16, &[u32]); 13] = [
+    (tags::SUBFILE_TYPE, &[1, 2, 3]),
+    (tags::COMPRESSION, &[1, 2, 3, 4, 5, 6, 7, 8, 32773]),
+    (tags::PHOTOMETRIC_INTERPRETATION, &[0, 1, 2, 3, 4, 5, 6, 8]),
+    (tags::THRESHHOLDING, &[1, 2, 3]),
+    (tags::FILL_ORDER, &[1, 2]),
+    (tags::ORIENTATION, &[1, 2, 3, 4, 5, 6, 7, 8]),
+    (tags::PLANAR_CONFIGURATION, &[1, 2]),
+    (tags::GRAY_RESPONSE_UNIT, &[1, 2, 3, 4, 5]),
+    (tags::RESOLUTION_UNIT, &[1, 2, 3]),
+    (tags::PREDICTOR, &[1, 2, 3]),
+    (tags::INK_SET, &[1, 2]),
+    (tags::EXTRA_SAMPLES, &[0, 1, 2]),
+    (tags::SAMPLE_FORMAT, &[1, 2, 3, 4]),
+];
+
+/* Larger images exist, but a small LONG dimension read in the wrong
+ * byte order is at least 2^24, so this catches those with room to
+ * spare. */
+const MAX_PLAUSIBLE_DIMENSION: u32 = 1 << 20;
+
+fn is_plausible(tag: u16, value: u32) -> bool {
+    match tag {
+        tags::IMAGE_WIDTH | tags::IMAGE_LENGTH => value > 0 && value <= MAX_PLAUSIBLE_DIMENSION,
+        tags::BITS_PER_SAMPLE => value > 0 && value <= 64,
+        /* Private compression schemes use values from 32768 up, while a
+         * small value read in the wrong byte order is a multiple of 256
+         * below that. */
+        tags::COMPRESSION => value != 0 && !(value < 32768 && value.is_multiple_of(256)),
+        _ => true,
+    }
+}
+
+fn overlaps(a: &Range<u64>, b: &Range<u64>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Checks the out-of-line value regions of all fields against each
+    /// other, the IFD and the stream length. Only field offsets and
+    /// sizes are inspected, so no I/O is performed, and the result
+    /// doesn't depend on which values have been loaded.
+    pub fn check_value_region_integrity(&self, stream_len: u64) -> Vec<IntegrityWarning> {
+        let regions: Vec<(u16, Range<u64>)> = self.fields()
+            .filter_map(|(tag, field)| match field.value_location() {
+                Some(ValueLocation::OutOfLine {offset, len}) => Some((tag, offset..offset.saturating_add(len as u64))),
+                _ => None,
+            })
+            .collect();
+        let ifd_range = self.ifd_byte_range();
+        
+        let mut warnings = Vec::new();
+        for (i, (tag, range)) in regions.iter().enumerate() {
+            if range.end > stream_len {
+                warnings.push(IntegrityWarning::PastEndOfFile {tag: *tag, offset: range.start, len: (range.end - range.start) as usize});
+            }
+            if overlaps(range, &ifd_range) {
+                warnings.push(IntegrityWarning::OverlapsIfd {tag: *tag});
+            }
+            for (other_tag, other_range) in &regions[i + 1..] {
+                if overlaps(range, other_range) {
+                    warnings.push(IntegrityWarning::OverlapsField {tag: *tag, other_tag: *other_tag});
+                }
+            }
+        }
+        
+        warnings
+    }
+    
+    /// Loads the enumerated fields that are present and reports every
+    /// value outside the set the spec defines for the field.
+    pub fn validate_enumerated_tags(&mut self) -> Result<Vec<IntegrityWarning>, TiffReadError> {
+        let mut warnings = Vec::new();
+        for &(tag, valid_values) in &ENUMERATED_TAG_VALUES {
+            for value in self.get_u32_values(tag)?.unwrap_or_default() {
+                if !valid_values.contains(&value) {
+                    warnings.push(IntegrityWarning::UnexpectedValue {tag, value});
+                }
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Looks for values of ImageWidth, ImageLength, BitsPerSample and
+    /// Compression that are too far out of range to be intended, which
+    /// suggests the byte order is wrong or the file is corrupt. Returns
+    /// the index of the subfile along with each warning. Reads all IFDs
+    /// first if that hasn't been done yet.
+    ///
+    /// This is a heuristic: an empty result doesn't mean the file is
+    /// valid.
+    pub fn sanity_check(&mut self) -> Result<Vec<(usize, IntegrityWarning)>, TiffReadError> {
+        if self.subfiles.is_empty() {
+            self.read_all_ifds()?;
+        }
+        
+        let mut warnings = Vec::new();
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            for &tag in &[tags::IMAGE_WIDTH, tags::IMAGE_LENGTH, tags::BITS_PER_SAMPLE, tags::COMPRESSION] {
+                for value in subfile.get_u32_values(tag)?.unwrap_or_default() {
+                    if !is_plausible(tag, value) {
+                        warnings.push((index, IntegrityWarning::ImplausibleValue {tag, value}));
+                    }
+                }
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use super::IntegrityWarning;
+    
+    #[test]
+    fn detect_overlapping_and_truncated_regions() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x04\x00".as_ref(), // IFD: number of entries (4)
+            b"\x1A\x01\x05\x00\x01\x00\x00\x00\x3E\x00\x00\x00".as_ref(), // XResolution, RATIONAL, offset 62
+            b"\x1B\x01\x05\x00\x01\x00\x00\x00\x42\x00\x00\x00".as_ref(), // YResolution, RATIONAL, offset 66 (overlaps XResolution)
+            b"\x31\x01\x02\x00\x08\x00\x00\x00\x0A\x00\x00\x00".as_ref(), // Software, ASCII, offset 10 (inside the IFD)
+            b"\x3B\x01\x02\x00\x08\x00\x00\x00\x50\x00\x00\x00".as_ref(), // Artist, ASCII, offset 80 (past EOF)
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"\x2C\x01\x00\x00\x01\x00\x00\x00\x01\x00".as_ref(), // overlapping rationals
+        ].concat();
+        let stream_len = tiff_bytes.len() as u64;
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let warnings = tiff_reader.subfiles[0].check_value_region_integrity(stream_len);
+        assert_eq!(warnings, vec![
+            IntegrityWarning::OverlapsField {tag: 282, other_tag: 283},
+            IntegrityWarning::PastEndOfFile {tag: 283, offset: 66, len: 8},
+            IntegrityWarning::OverlapsIfd {tag: 305},
+            IntegrityWarning::PastEndOfFile {tag: 315, offset: 80, len: 8},
+        ]);
+    }
+    
+    #[test]
+    fn detect_overlap_of_loaded_ascii_values() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD: number of entries (2)
+            b"\x31\x01\x02\x00\x06\x00\x00\x00\x26\x00\x00\x00".as_ref(), // Software, ASCII, 6 bytes at offset 38
+            b"\x3B\x01\x02\x00\x05\x00\x00\x00\x2B\x00\x00\x00".as_ref(), // Artist, ASCII, 5 bytes at offset 43 (shares a byte with Software)
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"Hello\0abc\0".as_ref(),
+        ].concat();
+        let stream_len = tiff_bytes.len() as u64;
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let subfile = &mut tiff_reader.subfiles[0];
+        subfile.load_all_field_values().unwrap();
+        assert_eq!(subfile.check_value_region_integrity(stream_len), vec![IntegrityWarning::OverlapsField {tag: 305, other_tag: 315}]);
+    }
+    
+    #[test]
+    fn detect_unexpected_enumerated_values() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(259, crate::FieldValue::Short(vec![1]))
+            .entry(262, crate::FieldValue::Short(vec![7]))
+            .entry(338, crate::FieldValue::Short(vec![1, 9]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].validate_enumerated_tags().unwrap(), vec![
+            IntegrityWarning::UnexpectedValue {tag: 262, value: 7},
+            IntegrityWarning::UnexpectedValue {tag: 338, value: 9},
+        ]);
+    }
+    
+    #[test]
+    fn detect_implausible_values() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(crate::Endianness::Little)
+            .entry(256, crate::FieldValue::Long(vec![4000]))
+            .entry(257, crate::FieldValue::Long(vec![3000]))
+            .entry(258, crate::FieldValue::Short(vec![8, 8, 8]))
+            .entry(259, crate::FieldValue::Short(vec![32773]))
+            .ifd()
+            .entry(256, crate::FieldValue::Long(vec![1 << 24]))
+            .entry(258, crate::FieldValue::Short(vec![2048]))
+            .entry(259, crate::FieldValue::Short(vec![256]))
+            .reader();
+        
+        assert_eq!(tiff_reader.sanity_check().unwrap(), vec![
+            (1, IntegrityWarning::ImplausibleValue {tag: 256, value: 1 << 24}),
+            (1, IntegrityWarning::ImplausibleValue {tag: 258, value: 2048}),
+            (1, IntegrityWarning::ImplausibleValue {tag: 259, value: 256}),
+        ]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+
+const Q_TABLE_SIZE: usize = 64;
+const HUFFMAN_COUNTS_SIZE: usize = 16;
+const HUFFMAN_MAX_VALUES: usize = 256;
+
+/// Tables referenced by the old-style JPEG tags (Compression = 6). Each
+/// table is kept as the raw bytes found in the file, in the order given
+/// by the corresponding offset array.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OldJpegTables {
+    /// 64-byte quantization tables from JPEGQTables (519).
+    pub q_tables: Vec<Vec<u8>>,
+    /// DC Huffman tables from JPEGDCTables (520): 16 code length counts
+    /// followed by the symbol values.
+    pub dc_tables: Vec<Vec<u8>>,
+    /// AC Huffman tables from JPEGACTables (521), laid out like the DC
+    /// tables.
+    pub ac_tables: Vec<Vec<u8>>,
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    pub fn has_old_jpeg_tables(&self) -> bool {
+        [tags::JPEG_Q_TABLES, tags::JPEG_DC_TABLES, tags::JPEG_AC_TABLES].iter().any(|&tag| self.get_field(tag).is_some())
+    }
+    
+    /// Loads the JPEGQTables, JPEGDCTables and JPEGACTables offset
+    /// arrays and reads the tables they point to. Absent tags result in
+    /// empty table lists.
+    pub fn old_jpeg_tables(&mut self) -> Result<OldJpegTables, TiffReadError> {
+        let q_offsets = self.get_u32_values(tags::JPEG_Q_TABLES)?.unwrap_or_default();
+        let dc_offsets = self.get_u32_values(tags::JPEG_DC_TABLES)?.unwrap_or_default();
+        let ac_offsets = self.get_u32_values(tags::JPEG_AC_TABLES)?.unwrap_or_default();
+        
+        let q_tables = q_offsets.iter()
+            .map(|&offset| self.read_bytes_at(u64::from(offset), Q_TABLE_SIZE))
+            .collect::<Result<_, _>>()?;
+        let dc_tables = dc_offsets.iter()
+            .map(|&offset| self.read_huffman_table(offset))
+            .collect::<Result<_, _>>()?;
+        let ac_tables = ac_offsets.iter()
+            .map(|&offset| self.read_huffman_table(offset))
+            .collect::<Result<_, _>>()?;
+        
+        Ok(OldJpegTables {
+            q_tables,
+            dc_tables,
+            ac_tables,
+        })
+    }
+    
+    fn read_huffman_table(&self, offset: u32) -> Result<Vec<u8>, TiffReadError> {
+        let mut table = self.read_bytes_at(u64::from(offset), HUFFMAN_COUNTS_SIZE)?;
+        let value_count: usize = table.iter().map(|&count| usize::from(count)).sum();
+        
+        /* A Huffman table can hold at most 256 symbols, so a larger
+         * total means the offset doesn't actually point at a table. */
+        if value_count > HUFFMAN_MAX_VALUES {
+            return Err(ParseError::new(format!("Huffman table at offset {} declares {} values (expected <= {})", offset, value_count, HUFFMAN_MAX_VALUES)).into());
+        }
+        
+        let values = self.read_bytes_at(u64::from(offset) + HUFFMAN_COUNTS_SIZE as u64, value_count)?;
+        table.extend(values);
+        
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    #[test]
+    fn read_old_jpeg_tables() {
+        let q_table: Vec<u8> = (0..64).collect();
+        let dc_table = [
+            b"\x00\x01\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".as_ref(), // code length counts (3 values)
+            b"\x05\x06\x07".as_ref(), // values
+        ].concat();
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD: number of entries (2)
+            b"\x07\x02\x04\x00\x01\x00\x00\x00\x26\x00\x00\x00".as_ref(), // JPEGQTables, LONG, 1 value: offset 38
+            b"\x08\x02\x04\x00\x01\x00\x00\x00\x66\x00\x00\x00".as_ref(), // JPEGDCTables, LONG, 1 value: offset 102
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            &q_table,
+            &dc_table,
+        ].concat();
+        let cursor = Cursor::new(tiff_bytes);
+        let mut tiff_reader = crate::TiffReader::new(cursor).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let subfile = &mut tiff_reader.subfiles[0];
+        assert!(subfile.has_old_jpeg_tables());
+        let tables = subfile.old_jpeg_tables().unwrap();
+        assert_eq!(tables.q_tables, vec![q_table]);
+        assert_eq!(tables.dc_tables, vec![dc_table]);
+        assert!(tables.ac_tables.is_empty());
+    }
+}
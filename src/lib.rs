@@ -3,28 +3,184 @@
 extern crate num_rational;
 
 use std::convert::TryInto;
-use std::io::{Read, Seek, BufReader};
-use std::sync::{Arc, Mutex};
+use std::io::{Read, Seek, SeekFrom, BufReader, Cursor};
 
-use types::Endianness;
 use subfile::Subfile;
-use error::ParseError;
+use error::{ParseError, TiffReadError};
+use reader::{ReaderRef, SharedReader};
+
+pub use types::{Endianness, FieldType, FieldValue, FieldValueRef, Rational, RationalExt, SRational};
+pub use baseline::{AlphaKind, ImageKind, LegacySubfileType, NewSubfileType, SampleFormat, Thresholding};
+pub use jpeg::OldJpegTables;
+pub use gps::GpsInfo;
+pub use image::{DecodeOptions, DecodeReport, DecodeStep, Row, SkippedStep, StripRows, unpack_subbyte_samples};
+pub use subfile::{FieldInfo, OwnedSubfile, SubfileSnapshot, ValueLocation};
+pub use integrity::IntegrityWarning;
+pub use ifds::Ifds;
+pub use options::TiffReaderOptions;
+pub use photoshop::{ImageSourceBlock, PhotoshopResource};
+pub use colorimetry::{GrayResponse, TransferFunction, YCbCrPositioning};
+pub use compression::Compression;
+pub use tree::{IfdNode, PointerKind};
+pub use summary::SubfileSummary;
+pub use provenance::Provenance;
+pub use reader::{Local, Sharing, Threaded};
+pub use local::LocalTiffReader;
+
+/* Emits a `tracing` event at TRACE level when the "tracing" feature is
+ * enabled, and compiles to nothing otherwise. Defined before the module
+ * declarations so that every module can use it. */
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($($arg:tt)*) => { tracing::trace!($($arg)*) }
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($($arg:tt)*) => {}
+}
 
 mod types;
 mod subfile;
+mod reader;
+mod jpeg;
+mod gps;
+mod baseline;
+mod image;
+mod integrity;
+mod ifds;
+mod inks;
+mod photoshop;
+mod colorimetry;
+mod tree;
+mod compression;
+mod pyramid;
+mod pages;
+mod summary;
+mod overlay;
+mod rgb;
+mod provenance;
+mod local;
+#[cfg(feature = "image")]
+mod dynamic_image;
+mod options;
+mod writer;
+#[cfg(test)]
+mod test_util;
 pub mod error;
+pub mod tags;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// Checks whether `probe` finds a classic TIFF magic number ("II*\0" or
+/// "MM\0*"), reading at most 4 bytes from the current position. The
+/// position is restored afterwards, whatever the result.
+pub fn peek_is_tiff<R: Read + Seek>(reader: &mut R) -> std::io::Result<bool> {
+    Ok(matches!(probe(reader)?, Some(TiffProbe {variant: TiffVariant::Classic, ..})))
+}
+
+/// The flavour of TIFF indicated by the magic number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiffVariant {
+    /// Classic TIFF, with magic number 42 and 32-bit offsets.
+    Classic,
+    /// BigTIFF, with magic number 43 and 64-bit offsets. `TiffReader`
+    /// can't read these.
+    BigTiff,
+}
+
+impl TiffVariant {
+    pub fn offset_width(self) -> OffsetWidth {
+        match self {
+            TiffVariant::Classic => OffsetWidth::Four,
+            TiffVariant::BigTiff => OffsetWidth::Eight,
+        }
+    }
+}
+
+/// The size of offsets (and of IFD entry counts and value fields along
+/// with them) in a TIFF variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetWidth {
+    /// 4-byte offsets, as in classic TIFF.
+    Four,
+    /// 8-byte offsets, as in BigTIFF.
+    Eight,
+}
+
+impl OffsetWidth {
+    pub fn bytes(self) -> u64 {
+        match self {
+            OffsetWidth::Four => 4,
+            OffsetWidth::Eight => 8,
+        }
+    }
+    
+    /// Size of the entry count at the start of an IFD: 2 bytes for
+    /// classic TIFF and 8 for BigTIFF.
+    pub fn entry_count_len(self) -> u64 {
+        match self {
+            OffsetWidth::Four => 2,
+            OffsetWidth::Eight => 8,
+        }
+    }
+    
+    /// Size of an IFD entry: a 2-byte tag and a 2-byte type, followed by
+    /// a count and a value/offset of `bytes()` each.
+    pub fn entry_len(self) -> u64 {
+        4 + 2*self.bytes()
+    }
+    
+    /// Number of bytes taken up by an IFD with `entry_count` entries,
+    /// including the entry count and the next IFD offset: 2 + 12n + 4
+    /// for classic TIFF and 8 + 20n + 8 for BigTIFF.
+    pub fn ifd_len(self, entry_count: u64) -> u64 {
+        self.entry_count_len() + self.entry_len()*entry_count + self.bytes()
+    }
+}
+
+/// What `probe` found at the start of a stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TiffProbe {
+    pub endianness: Endianness,
+    pub variant: TiffVariant,
+}
+
+/// Checks the byte order mark and magic number at the current position,
+/// reading at most 4 bytes, and returns `None` if they aren't those of
+/// a classic TIFF or BigTIFF file. Streams too short to hold them are not
+/// TIFF either; only I/O failures are errors. The position is restored
+/// afterwards, whatever the result.
+pub fn probe<R: Read + Seek>(reader: &mut R) -> Result<Option<TiffProbe>, TiffReadError> {
+    let start = reader.stream_position()?;
+    
+    let mut magic_number = Vec::with_capacity(4);
+    let read_result = reader.by_ref().take(4).read_to_end(&mut magic_number);
+    reader.seek(SeekFrom::Start(start))?;
+    read_result?;
+    
+    let (endianness, variant) = match magic_number.as_slice() {
+        b"II\x2A\x00" => (Endianness::Little, TiffVariant::Classic),
+        b"MM\x00\x2A" => (Endianness::Big, TiffVariant::Classic),
+        b"II\x2B\x00" => (Endianness::Little, TiffVariant::BigTiff),
+        b"MM\x00\x2B" => (Endianness::Big, TiffVariant::BigTiff),
+        _ => return Ok(None),
+    };
+    Ok(Some(TiffProbe {endianness, variant}))
+}
 
 #[derive(Debug)]
-pub struct TiffReader<R> {
+pub struct TiffReader<R, S: Sharing = Threaded> {
     endianness: Endianness,
-    buf_reader_ref: Arc<Mutex<BufReader<R>>>,
+    reader_ref: ReaderRef<R, S>,
     offset_to_first_ifd: u32,
-    pub subfiles: Vec<Subfile<R>>,
+    pub subfiles: Vec<Subfile<R, S>>,
 }
 
 #[derive(Debug)]
 pub struct Header {
     pub endianness: Endianness,
+    pub variant: TiffVariant,
     pub offset_to_first_ifd: u32
 }
 
@@ -44,46 +200,157 @@ impl Header {
             Endianness::Big => u32::from_be_bytes(offset_bytes),
         };
         
+        /* Only classic TIFF headers are recognized so far. */
         Ok(Header {
-            endianness: endianness,
-            offset_to_first_ifd: offset_to_first_ifd,
+            endianness,
+            variant: TiffVariant::Classic,
+            offset_to_first_ifd,
         })
     }
 }
 
 impl<R: Read + Seek> TiffReader<R> {
-    pub fn new(reader: R) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut buf_reader = BufReader::new(reader);
-        let mut header_bytes = [0u8; 8];
-        buf_reader.seek(std::io::SeekFrom::Start(0))?;
-        buf_reader.read_exact(&mut header_bytes)?;
+    pub fn new(reader: R) -> Result<Self, TiffReadError> {
+        Self::with_options(reader, TiffReaderOptions::default())
+    }
+    
+    /// Like `new`, but sets the capacity (in bytes) of the internal
+    /// `BufReader` instead of using its default. A larger buffer can
+    /// reduce the number of reads when loading many large field values.
+    pub fn with_capacity(reader: R, capacity: usize) -> Result<Self, TiffReadError> {
+        Self::with_options(reader, TiffReaderOptions::default().buffer_capacity(capacity))
+    }
+    
+    /// Reads a TIFF whose header starts at position `base` in the
+    /// stream, e.g. one embedded in another file format. Every offset in
+    /// the file (and every offset passed to `read_bytes_at`) is taken
+    /// relative to `base`, and `stream_len` is measured from there.
+    pub fn with_base_offset(reader: R, base: u64) -> Result<Self, TiffReadError> {
+        Self::with_options(reader, TiffReaderOptions::default().base_offset(base))
+    }
+    
+    pub fn with_options(reader: R, options: TiffReaderOptions) -> Result<Self, TiffReadError> {
+        Self::open(reader, options)
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /* Shared by the constructors of `TiffReader` and `LocalTiffReader`,
+     * which can't both be inherent on `TiffReader<R, S>`: with `S`
+     * defaulted, `TiffReader::new` would then be ambiguous. */
+    pub(crate) fn open(reader: R, options: TiffReaderOptions) -> Result<Self, TiffReadError> {
+        let buf_reader = BufReader::with_capacity(options.buffer_capacity, reader);
+        let mut shared_reader = SharedReader::new(buf_reader, options.base_offset)?;
+        shared_reader.set_strict(options.strict);
+        shared_reader.set_segment_cache_capacity(options.segment_cache_capacity);
+        
+        let header_bytes: [u8; 8] = shared_reader.read_bytes_at(0, 8)?[..].try_into().unwrap();
         let header = Header::from_bytes(&header_bytes)?;
+        shared_reader.set_offset_width(header.variant.offset_width());
         
         /* The TIFF 6.0 spec says at least one IFD is mandatory
          * (and that IFD needs to start after the header). */
         if header.offset_to_first_ifd >= 8 {
             Ok(TiffReader {
                 endianness: header.endianness,
-                buf_reader_ref: Arc::new(Mutex::new(buf_reader)),
+                reader_ref: ReaderRef::new(shared_reader),
                 offset_to_first_ifd: header.offset_to_first_ifd,
                 subfiles: Vec::new(),
             })
         }
         else {
-            Err(ParseError::new(format!("Offset to first IFD too small (found offset {}, expected >= 8)", header.offset_to_first_ifd).to_string()).into())
+            Err(ParseError::new(format!("Offset to first IFD too small (found offset {}, expected >= 8)", header.offset_to_first_ifd)).into())
         }
     }
     
-    pub fn read_all_ifds(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut ifd_offset = self.offset_to_first_ifd;
-        while ifd_offset != 0 {
-            let subfile = Subfile::new(self.buf_reader_ref.clone(), ifd_offset, self.endianness)?;
-            ifd_offset = subfile.offset_to_next_ifd().unwrap_or(0);
-            self.subfiles.push(subfile);
+    pub fn read_all_ifds(&mut self) -> Result<(), TiffReadError> {
+        trace_event!(offset_to_first_ifd = self.offset_to_first_ifd, "reading IFD chain");
+        for subfile in self.ifds() {
+            self.subfiles.push(subfile?);
         }
         
         Ok(())
     }
+    
+    /// Parses only the first IFD, without following the chain, and
+    /// returns it. If `subfiles` already holds IFDs, the first one is
+    /// returned as is.
+    pub fn read_first_ifd_only(&mut self) -> Result<&Subfile<R, S>, TiffReadError> {
+        if self.subfiles.is_empty() {
+            let subfile = Subfile::new(self.reader_ref.clone(), self.offset_to_first_ifd, self.endianness)?;
+            self.subfiles.push(subfile);
+        }
+        
+        Ok(&self.subfiles[0])
+    }
+    
+    /// Returns the offset and length of the first IFD, including its
+    /// entry count and next-IFD offset, by reading only the 2-byte entry
+    /// count. Together with the 8-byte header, this is everything needed
+    /// to parse the first IFD, so a remote file can be fetched with a
+    /// single range request for `0..offset + length` and opened from
+    /// memory. Out-of-line values are not included.
+    pub fn first_ifd_byte_range(&self) -> Result<(u64, u64), TiffReadError> {
+        let offset = u64::from(self.offset_to_first_ifd);
+        let entry_count_bytes: [u8; 2] = self.reader_ref.read_bytes_at(offset, 2)?[..].try_into().unwrap();
+        let entry_count = match self.endianness {
+            Endianness::Little => u16::from_le_bytes(entry_count_bytes),
+            Endianness::Big => u16::from_be_bytes(entry_count_bytes),
+        };
+        Ok((offset, self.offset_width().ifd_len(u64::from(entry_count))))
+    }
+    
+    /// Size of the offsets in the file, as given by the TIFF variant in
+    /// the header. Only classic TIFF can be read so far, so this is
+    /// `OffsetWidth::Four`.
+    pub fn offset_width(&self) -> OffsetWidth {
+        self.reader_ref.offset_width()
+    }
+    
+    /// Returns an iterator that parses the IFD chain lazily, one IFD per
+    /// call to `next`, independently of `subfiles`.
+    pub fn ifds(&self) -> Ifds<R, S> {
+        Ifds::new(self.reader_ref.clone(), self.endianness, self.offset_to_first_ifd)
+    }
+    
+    /// Declares that fields with the given tag hold offsets to IFDs, so
+    /// that `Subfile::follow_registered_pointers` will follow them.
+    /// ExifIFD (34665) and GPSIFD (34853) are registered by default.
+    pub fn register_ifd_pointer(&mut self, tag: u16) {
+        self.reader_ref.register_ifd_pointer(tag);
+    }
+    
+    /// Enables or disables strict mode, which turns signs of likely
+    /// corruption that are otherwise tolerated into errors. Off by
+    /// default. Applies to subfiles already read as well.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.reader_ref.set_strict(strict);
+    }
+    
+    /// Length of the underlying stream, as measured when the reader
+    /// was created.
+    pub fn stream_len(&self) -> u64 {
+        self.reader_ref.stream_len()
+    }
+    
+    /// Reads `len` raw bytes starting at `offset` in the underlying
+    /// stream. Fails with `TiffReadError::OutOfBounds` if the range
+    /// extends past the end of the stream.
+    pub fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Vec<u8>, TiffReadError> {
+        self.reader_ref.read_bytes_at(offset, len)
+    }
+}
+
+impl TiffReader<Cursor<Vec<u8>>> {
+    /// Reads all of `reader` into memory and opens the result, for
+    /// sources that can't seek, such as pipes or network streams. The
+    /// reader then works exactly as for a seekable file, including lazy
+    /// IFD iteration and loading of values, just without further I/O.
+    pub fn from_reader_buffered<S: Read>(mut reader: S) -> Result<Self, TiffReadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        TiffReader::new(Cursor::new(bytes))
+    }
 }
 
 #[cfg(test)]
@@ -112,6 +379,15 @@ mod tests {
         println!("{:#?}", tiff_reader);
     }
     
+    #[test]
+    fn create_tiff_reader_with_capacity() {
+        let header_bytes = b"II\x2A\x00\xD2\x02\x96\x49";
+        let cursor = Cursor::new(header_bytes);
+        let tiff_reader = crate::TiffReader::with_capacity(cursor, 64*1024).unwrap();
+        assert_eq!(tiff_reader.endianness, Endianness::Little);
+        assert_eq!(tiff_reader.offset_to_first_ifd, 1234567890u32);
+    }
+    
     #[test]
     #[should_panic]
     fn fail_create_tiff_reader_with_first_offset_too_low() {
@@ -164,4 +440,419 @@ mod tests {
         );
         println!("{:#?}", tiff_reader);
     }
+    
+    #[test]
+    fn read_ifd_right_after_header() {
+        let tiff_bytes = [
+            b"MM\x00\x2A\x00\x00\x00\x08".as_ref(), // image file header, offset 8 to first IFD
+            b"\x00\x01".as_ref(), // IFD: number of entries (1)
+            b"\x01\x00\x00\x03\x00\x00\x00\x01\x00\x40\x00\x00".as_ref(), // ImageWidth, SHORT, 64
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes.clone())).unwrap();
+        assert_eq!(tiff_reader.offset_to_first_ifd, 8);
+        tiff_reader.read_all_ifds().unwrap();
+        assert_eq!(tiff_reader.subfiles.len(), 1);
+        assert_eq!(tiff_reader.subfiles[0].image_width().unwrap(), 64);
+        
+        /* One byte lower and the IFD would overlap the header. */
+        let mut overlapping = tiff_bytes;
+        overlapping[7] = 7;
+        assert!(crate::TiffReader::new(Cursor::new(overlapping)).is_err());
+    }
+    
+    #[test]
+    fn read_ifd_with_base_offset() {
+        let tiff_bytes = [
+            b"JUNK!".as_ref(), // data preceding the embedded TIFF (5 bytes)
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x01\x00".as_ref(), // IFD: number of entries (1)
+            b"\x1A\x01\x05\x00\x01\x00\x00\x00\x1A\x00\x00\x00".as_ref(), // XResolution, RATIONAL, offset 26
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"\x2C\x01\x00\x00\x01\x00\x00\x00".as_ref(), // 300/1
+        ].concat();
+        let cursor = Cursor::new(tiff_bytes);
+        let mut tiff_reader = crate::TiffReader::with_base_offset(cursor, 5).unwrap();
+        assert_eq!(tiff_reader.stream_len(), 34);
+        tiff_reader.read_all_ifds().unwrap();
+        assert_eq!(
+            tiff_reader.subfiles[0].get_value(282).unwrap(),
+            Some(&types::FieldValue::Rational(vec![types::Rational::new_raw(300, 1)]))
+        );
+        assert_eq!(tiff_reader.read_bytes_at(0, 2).unwrap(), b"II".to_vec());
+    }
+    
+    #[test]
+    fn read_bytes_at_bounds_checked() {
+        let tiff_bytes = b"II\x2A\x00\x08\x00\x00\x00\xCA\xFE\xBE\xEF";
+        let cursor = Cursor::new(tiff_bytes);
+        let tiff_reader = crate::TiffReader::new(cursor).unwrap();
+        assert_eq!(tiff_reader.stream_len(), 12);
+        assert_eq!(tiff_reader.read_bytes_at(8, 4).unwrap(), vec![0xCA, 0xFE, 0xBE, 0xEF]);
+        assert_eq!(tiff_reader.read_bytes_at(12, 0).unwrap(), vec![]);
+        match tiff_reader.read_bytes_at(10, 4) {
+            Err(crate::error::TiffReadError::OutOfBounds {offset: 10, len: 4, stream_len: 12}) => {},
+            other => panic!("Expected OutOfBounds error, got {:?}", other),
+        }
+        assert!(tiff_reader.read_bytes_at(u64::MAX, 2).is_err());
+    }
+    
+    #[test]
+    fn follow_unregistered_ifd_pointer() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x05\x00".as_ref(), // IFD: number of entries (5)
+            b"\x50\xC3\x04\x00\x01\x00\x00\x00\x4A\x00\x00\x00".as_ref(), // private tag 50000, LONG, offset 74
+            b"\x51\xC3\x0D\x00\x01\x00\x00\x00\x4A\x00\x00\x00".as_ref(), // private tag 50001, IFD type (13), offset 74
+            b"\x52\xC3\x03\x00\x01\x00\x00\x00\x4A\x00\x00\x00".as_ref(), // private tag 50002, SHORT, value 74
+            b"\x53\xC3\x04\x00\x01\x00\x00\x00\xE8\x03\x00\x00".as_ref(), // private tag 50003, LONG, offset 1000
+            b"\x55\xC3\x63\x00\x01\x00\x00\x00\x4A\x00\x00\x00".as_ref(), // private tag 50005, unknown type 99, offset 74
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"\x01\x00".as_ref(), // private IFD: number of entries (1)
+            b"\x01\x00\x01\x00\x01\x00\x00\x00\x07\x00\x00\x00".as_ref(), // tag 1, BYTE, value 7
+            b"\x00\x00\x00\x00".as_ref(), // private IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        for &tag in &[50000, 50001] {
+            let child = subfile.follow_as_ifd(tag).unwrap();
+            assert_eq!(child.get_field(1).unwrap().get_value_if_local(), Some(&types::FieldValue::Byte(vec![7])));
+        }
+        match subfile.follow_as_ifd(50002) {
+            Err(crate::error::TiffReadError::TypeMismatch {tag: 50002, found: types::FieldType::Short}) => {},
+            other => panic!("Expected TypeMismatch error, got {:?}", other),
+        }
+        match subfile.follow_as_ifd(50003) {
+            Err(crate::error::TiffReadError::InField {tag: 50003, source}) => match *source {
+                crate::error::TiffReadError::OutOfBounds {offset: 1000, ..} => {},
+                other => panic!("Expected OutOfBounds error, got {:?}", other),
+            },
+            other => panic!("Expected InField error, got {:?}", other),
+        }
+        match subfile.follow_as_ifd(50004) {
+            Err(crate::error::TiffReadError::MissingRequiredTag {tag: 50004}) => {},
+            other => panic!("Expected MissingRequiredTag error, got {:?}", other),
+        }
+        match subfile.follow_as_ifd(50005) {
+            Err(crate::error::TiffReadError::UnknownFieldType {tag: 50005, raw: 99}) => {},
+            other => panic!("Expected UnknownFieldType error, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn follow_registered_ifd_pointers() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD: number of entries (2)
+            b"\x69\x87\x04\x00\x01\x00\x00\x00\x26\x00\x00\x00".as_ref(), // ExifIFD (34665), LONG, offset 38
+            b"\x50\xC3\x0D\x00\x01\x00\x00\x00\x38\x00\x00\x00".as_ref(), // private tag 50000, IFD type (13), offset 56
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"\x01\x00".as_ref(), // Exif IFD: number of entries (1)
+            b"\x01\x00\x01\x00\x01\x00\x00\x00\x07\x00\x00\x00".as_ref(), // tag 1, BYTE, value 7
+            b"\x00\x00\x00\x00".as_ref(), // Exif IFD: offset to next IFD (0 = N/A)
+            b"\x01\x00".as_ref(), // private IFD: number of entries (1)
+            b"\x02\x00\x01\x00\x01\x00\x00\x00\x09\x00\x00\x00".as_ref(), // tag 2, BYTE, value 9
+            b"\x00\x00\x00\x00".as_ref(), // private IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let cursor = Cursor::new(tiff_bytes);
+        let mut tiff_reader = crate::TiffReader::new(cursor).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let children = tiff_reader.subfiles[0].follow_registered_pointers().unwrap();
+        assert_eq!(children.keys().cloned().collect::<Vec<_>>(), vec![crate::tags::EXIF_IFD]);
+        
+        tiff_reader.register_ifd_pointer(50000);
+        let children = tiff_reader.subfiles[0].follow_registered_pointers().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(
+            children[&crate::tags::EXIF_IFD].get_field(1).unwrap().get_value_if_local(),
+            Some(&types::FieldValue::Byte(vec![7]))
+        );
+        assert_eq!(
+            children[&50000].get_field(2).unwrap().get_value_if_local(),
+            Some(&types::FieldValue::Byte(vec![9]))
+        );
+    }
+    
+    #[test]
+    fn diff_subfile_tags() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD 0: number of entries (2)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageWidth, SHORT, 16
+            b"\x01\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageLength, SHORT, 16
+            b"\x26\x00\x00\x00".as_ref(), // IFD 0: offset to next IFD (38)
+            b"\x02\x00".as_ref(), // IFD 1: number of entries (2)
+            b"\x00\x01\x04\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageWidth, LONG, 16
+            b"\x03\x01\x03\x00\x01\x00\x00\x00\x01\x00\x00\x00".as_ref(), // Compression, SHORT, 1
+            b"\x00\x00\x00\x00".as_ref(), // IFD 1: offset to next IFD (0 = N/A)
+        ].concat();
+        let cursor = Cursor::new(tiff_bytes);
+        let mut tiff_reader = crate::TiffReader::new(cursor).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        let tag_diff = crate::tags::diff(&tiff_reader.subfiles[0], &tiff_reader.subfiles[1]);
+        assert_eq!(tag_diff.only_in_a, [257].iter().cloned().collect());
+        assert_eq!(tag_diff.only_in_b, [259].iter().cloned().collect());
+        assert_eq!(tag_diff.differing, [256].iter().cloned().collect());
+    }
+    
+    #[test]
+    fn diff_ignores_loaded_values() {
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .raw_entry(305, 2, 8, b"Writer\0\0".to_vec())
+            .build();
+        let mut tiff_reader_a = crate::TiffReader::new(Cursor::new(tiff_bytes.clone())).unwrap();
+        tiff_reader_a.read_all_ifds().unwrap();
+        let mut tiff_reader_b = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader_b.read_all_ifds().unwrap();
+        
+        tiff_reader_a.subfiles[0].get_value(305).unwrap();
+        assert_eq!(crate::tags::diff(&tiff_reader_a.subfiles[0], &tiff_reader_b.subfiles[0]), crate::tags::TagDiff::default());
+    }
+    
+    /* IFD with an out-of-line RATIONAL (XResolution = 300/1) and an
+     * out-of-line SRATIONAL (tag 1337 = -5/2), in the given byte order. */
+    fn rational_tiff_bytes(endianness: Endianness) -> Vec<u8> {
+        let u16_bytes = |value: u16| match endianness {
+            Endianness::Little => value.to_le_bytes().to_vec(),
+            Endianness::Big => value.to_be_bytes().to_vec(),
+        };
+        let u32_bytes = |value: u32| match endianness {
+            Endianness::Little => value.to_le_bytes().to_vec(),
+            Endianness::Big => value.to_be_bytes().to_vec(),
+        };
+        let byte_order_mark = match endianness {
+            Endianness::Little => b"II".to_vec(),
+            Endianness::Big => b"MM".to_vec(),
+        };
+        
+        [
+            byte_order_mark, u16_bytes(42), u32_bytes(8), // image file header, offset 8 to first IFD
+            u16_bytes(2), // IFD: number of entries (2)
+            u16_bytes(282), u16_bytes(5), u32_bytes(1), u32_bytes(38), // XResolution, RATIONAL, offset 38
+            u16_bytes(1337), u16_bytes(10), u32_bytes(1), u32_bytes(46), // tag 1337, SRATIONAL, offset 46
+            u32_bytes(0), // IFD: offset to next IFD (0 = N/A)
+            u32_bytes(300), u32_bytes(1), // 300/1
+            u32_bytes(-5i32 as u32), u32_bytes(2), // -5/2
+        ].concat()
+    }
+    
+    #[test]
+    fn load_out_of_line_rationals_in_both_byte_orders() {
+        for &endianness in &[Endianness::Little, Endianness::Big] {
+            let cursor = Cursor::new(rational_tiff_bytes(endianness));
+            let mut tiff_reader = crate::TiffReader::new(cursor).unwrap();
+            assert_eq!(tiff_reader.endianness, endianness);
+            tiff_reader.read_all_ifds().unwrap();
+            let subfile = &mut tiff_reader.subfiles[0];
+            
+            let field = subfile.get_field_mut(282).unwrap();
+            assert_eq!(field.get_value_if_local(), None);
+            field.load().unwrap();
+            match field.get_value().unwrap() {
+                Some(types::FieldValue::Rational(values)) => {
+                    assert_eq!(values.len(), 1);
+                    assert_eq!(*values[0].numer(), 300);
+                    assert_eq!(*values[0].denom(), 1);
+                }
+                other => panic!("Expected RATIONAL value, got {:?}", other),
+            }
+            
+            match subfile.get_value(1337).unwrap() {
+                Some(types::FieldValue::SRational(values)) => {
+                    assert_eq!(values.len(), 1);
+                    assert_eq!(*values[0].numer(), -5);
+                    assert_eq!(*values[0].denom(), 2);
+                }
+                other => panic!("Expected SRATIONAL value, got {:?}", other),
+            }
+        }
+    }
+    
+    fn two_ifd_chain(second_next_offset: u8) -> Vec<u8> {
+        [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x01\x00".as_ref(), // IFD 0: number of entries (1)
+            b"\x39\x05\x01\x00\x01\x00\x00\x00\x00\x00\x00\x00".as_ref(), // tag 1337, BYTE, value 0
+            b"\x1A\x00\x00\x00".as_ref(), // IFD 0: offset to next IFD (26)
+            b"\x01\x00".as_ref(), // IFD 1: number of entries (1)
+            b"\x39\x05\x01\x00\x01\x00\x00\x00\x01\x00\x00\x00".as_ref(), // tag 1337, BYTE, value 1
+            &[second_next_offset, 0, 0, 0], // IFD 1: offset to next IFD
+        ].concat()
+    }
+    
+    #[test]
+    fn iterate_ifds_lazily() {
+        let tiff_reader = crate::TiffReader::new(Cursor::new(two_ifd_chain(0))).unwrap();
+        let values: Vec<_> = tiff_reader.ifds()
+            .map(|subfile| subfile.unwrap().get_field(1337).unwrap().get_value_if_local().cloned())
+            .collect();
+        assert_eq!(values, vec![Some(types::FieldValue::Byte(vec![0])), Some(types::FieldValue::Byte(vec![1]))]);
+        assert!(tiff_reader.subfiles.is_empty());
+    }
+    
+    #[test]
+    fn iterate_ifds_of_buffered_read_only_source() {
+        /* Only implements Read, so it can't be used with TiffReader::new. */
+        struct ReadOnly(Cursor<Vec<u8>>);
+        impl std::io::Read for ReadOnly {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Big)
+            .entry(282, types::FieldValue::Rational(vec![types::Rational::new_raw(300, 1)]))
+            .ifd()
+            .entry(282, types::FieldValue::Rational(vec![types::Rational::new_raw(600, 1)]))
+            .build();
+        let tiff_reader = crate::TiffReader::from_reader_buffered(ReadOnly(Cursor::new(tiff_bytes))).unwrap();
+        
+        let mut values = Vec::new();
+        for subfile in tiff_reader.ifds() {
+            let mut subfile = subfile.unwrap();
+            assert!(!subfile.get_field(282).unwrap().is_loaded());
+            values.push(subfile.get_rationals(282).unwrap().unwrap());
+        }
+        assert_eq!(values, vec![vec![(300, 1)], vec![(600, 1)]]);
+        assert!(tiff_reader.subfiles.is_empty());
+    }
+    
+    #[test]
+    fn detect_ifd_cycle() {
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(two_ifd_chain(8))).unwrap();
+        match tiff_reader.read_all_ifds() {
+            Err(crate::error::TiffReadError::IfdCycle {offset: 8}) => {},
+            other => panic!("Expected IfdCycle error, got {:?}", other),
+        }
+        assert_eq!(tiff_reader.subfiles.len(), 2);
+    }
+    
+    #[test]
+    fn reject_backward_ifd_pointer_in_strict_mode() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x1A\x00\x00\x00".as_ref(), // image file header, offset 26 to first IFD
+            b"\x01\x00".as_ref(), // IFD 1: number of entries (1)
+            b"\x39\x05\x01\x00\x01\x00\x00\x00\x01\x00\x00\x00".as_ref(), // tag 1337, BYTE, value 1
+            b"\x00\x00\x00\x00".as_ref(), // IFD 1: offset to next IFD (none)
+            b"\x01\x00".as_ref(), // IFD 0: number of entries (1)
+            b"\x39\x05\x01\x00\x01\x00\x00\x00\x00\x00\x00\x00".as_ref(), // tag 1337, BYTE, value 0
+            b"\x08\x00\x00\x00".as_ref(), // IFD 0: offset to next IFD (8)
+        ].concat();
+        
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes.clone())).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        assert_eq!(tiff_reader.subfiles.len(), 2);
+        
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.set_strict(true);
+        match tiff_reader.read_all_ifds() {
+            Err(crate::error::TiffReadError::BackwardIfdPointer {offset: 26, next_offset: 8}) => {},
+            other => panic!("Expected BackwardIfdPointer error, got {:?}", other),
+        }
+        assert_eq!(tiff_reader.subfiles.len(), 1);
+    }
+    
+    #[cfg(feature = "prefetch")]
+    #[test]
+    fn iterate_ifds_with_prefetch() {
+        let tiff_reader = crate::TiffReader::new(Cursor::new(two_ifd_chain(0))).unwrap();
+        let subfiles: Vec<_> = tiff_reader.ifds().prefetch().collect::<Result<_, _>>().unwrap();
+        assert_eq!(subfiles.len(), 2);
+        assert_eq!(subfiles[1].get_field(1337).unwrap().get_value_if_local(), Some(&types::FieldValue::Byte(vec![1])));
+    }
+    
+    #[test]
+    fn read_first_ifd_only() {
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(256, types::FieldValue::Short(vec![64]))
+            .ifd()
+            .entry(256, types::FieldValue::Short(vec![32]))
+            .build();
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        
+        let subfile = tiff_reader.read_first_ifd_only().unwrap();
+        assert_eq!(subfile.get_field(256).unwrap().get_value_if_local(), Some(&types::FieldValue::Short(vec![64])));
+        assert!(subfile.offset_to_next_ifd().is_some());
+        assert_eq!(tiff_reader.subfiles.len(), 1);
+    }
+    
+    #[test]
+    fn fetch_first_ifd_by_range() {
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Big)
+            .entry(256, types::FieldValue::Short(vec![64]))
+            .entry(257, types::FieldValue::Short(vec![48]))
+            .ifd()
+            .entry(256, types::FieldValue::Short(vec![32]))
+            .build();
+        let tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes.clone())).unwrap();
+        let (offset, len) = tiff_reader.first_ifd_byte_range().unwrap();
+        assert_eq!((offset, len), (8, 2 + 2*12 + 4));
+        assert_eq!(tiff_reader.offset_width(), crate::OffsetWidth::Four);
+        assert_eq!(crate::TiffVariant::BigTiff.offset_width().ifd_len(2), 8 + 2*20 + 8);
+        assert_eq!(crate::OffsetWidth::Eight.entry_len(), 20);
+        
+        let prefix = tiff_bytes[..(offset + len) as usize].to_vec();
+        let mut tiff_reader = crate::TiffReader::new(Cursor::new(prefix)).unwrap();
+        let subfile = tiff_reader.read_first_ifd_only().unwrap();
+        assert_eq!(subfile.get_field(257).unwrap().get_value_if_local(), Some(&types::FieldValue::Short(vec![48])));
+        assert_eq!(subfile.ifd_byte_span(), (offset, len as usize));
+    }
+    
+    #[test]
+    fn peek_is_tiff_restores_position() {
+        use std::io::{Seek, SeekFrom};
+        
+        let mut cursor = Cursor::new(b"xxMM\x00\x2A\x00\x00\x00\x08".to_vec());
+        cursor.seek(SeekFrom::Start(2)).unwrap();
+        assert!(crate::peek_is_tiff(&mut cursor).unwrap());
+        assert_eq!(cursor.position(), 2);
+        
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        assert!(!crate::peek_is_tiff(&mut cursor).unwrap());
+        assert_eq!(cursor.position(), 0);
+        
+        cursor.seek(SeekFrom::Start(8)).unwrap();
+        assert!(!crate::peek_is_tiff(&mut cursor).unwrap());
+        assert_eq!(cursor.position(), 8);
+    }
+    
+    #[test]
+    fn probe_tiff_variants() {
+        use std::io::{Seek, SeekFrom};
+        use crate::{TiffProbe, TiffVariant};
+        
+        let mut cursor = Cursor::new(b"xxII\x2B\x00\x08\x00\x00\x00".to_vec());
+        cursor.seek(SeekFrom::Start(2)).unwrap();
+        assert_eq!(crate::probe(&mut cursor).unwrap(), Some(TiffProbe {endianness: Endianness::Little, variant: TiffVariant::BigTiff}));
+        assert_eq!(cursor.position(), 2);
+        
+        let mut cursor = Cursor::new(b"MM\x00\x2A\x00\x00\x00\x08".to_vec());
+        assert_eq!(crate::probe(&mut cursor).unwrap(), Some(TiffProbe {endianness: Endianness::Big, variant: TiffVariant::Classic}));
+        assert_eq!(cursor.position(), 0);
+        
+        for bytes in &[b"".as_ref(), b"MM\x00".as_ref(), b"\x89PNG\r\n".as_ref(), b"II\x00\x2A".as_ref()] {
+            let mut cursor = Cursor::new(bytes.to_vec());
+            assert_eq!(crate::probe(&mut cursor).unwrap(), None);
+            assert_eq!(cursor.position(), 0);
+        }
+    }
+    
+    #[test]
+    fn create_tiff_reader_with_options() {
+        let tiff_bytes = [
+            b"padding!".as_ref(), // 8 bytes before the TIFF header
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x00\x00".as_ref(), // IFD: number of entries (0)
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let options = crate::TiffReaderOptions::default().base_offset(8).buffer_capacity(16).strict(true);
+        let mut tiff_reader = crate::TiffReader::with_options(Cursor::new(tiff_bytes), options).unwrap();
+        
+        assert_eq!(tiff_reader.stream_len(), 14);
+        assert!(tiff_reader.read_all_ifds().is_err());
+    }
 }
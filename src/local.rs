@@ -0,0 +1,77 @@
+use std::io::{Read, Seek};
+use std::ops::{Deref, DerefMut};
+
+use crate::error::TiffReadError;
+use crate::options::TiffReaderOptions;
+use crate::reader::Local;
+use crate::TiffReader;
+
+/// A `TiffReader` whose subfiles and fields share the reader through an
+/// `Rc<RefCell<_>>` rather than an `Arc<Mutex<_>>`. This avoids locking
+/// on every value read, at the cost of the subfiles and fields being
+/// neither `Send` nor `Sync`. Everything else works as with `TiffReader`,
+/// which this dereferences to.
+#[derive(Debug)]
+pub struct LocalTiffReader<R>(TiffReader<R, Local>);
+
+impl<R: Read + Seek> LocalTiffReader<R> {
+    pub fn new(reader: R) -> Result<Self, TiffReadError> {
+        Self::with_options(reader, TiffReaderOptions::default())
+    }
+    
+    pub fn with_options(reader: R, options: TiffReaderOptions) -> Result<Self, TiffReadError> {
+        TiffReader::open(reader, options).map(LocalTiffReader)
+    }
+    
+    /// Returns the wrapped `TiffReader`, e.g. to move its subfiles out.
+    pub fn into_inner(self) -> TiffReader<R, Local> {
+        self.0
+    }
+}
+
+impl<R> Deref for LocalTiffReader<R> {
+    type Target = TiffReader<R, Local>;
+    
+    fn deref(&self) -> &TiffReader<R, Local> {
+        &self.0
+    }
+}
+
+impl<R> DerefMut for LocalTiffReader<R> {
+    fn deref_mut(&mut self) -> &mut TiffReader<R, Local> {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use crate::subfile::Subfile;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    use crate::LocalTiffReader;
+    
+    #[test]
+    fn read_fields_through_local_reader() {
+        let bytes = TiffBuilder::new(Endianness::Big)
+            .entry(256, FieldValue::Short(vec![640]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .ifd()
+            .entry(256, FieldValue::Long(vec![320]))
+            .build();
+        
+        let mut tiff_reader = LocalTiffReader::new(Cursor::new(bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        assert_eq!(tiff_reader.subfiles.len(), 2);
+        assert_eq!(tiff_reader.subfiles[0].image_width().unwrap(), 640);
+        assert_eq!(tiff_reader.subfiles[0].get_ascii(305).unwrap().as_deref(), Some("lazytiff"));
+        assert_eq!(tiff_reader.subfiles[1].image_width().unwrap(), 320);
+    }
+    
+    #[test]
+    fn threaded_subfiles_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Subfile<Cursor<Vec<u8>>>>();
+    }
+}
@@ -0,0 +1,54 @@
+/* Same as the default capacity of `std::io::BufReader`. */
+const DEFAULT_BUFFER_CAPACITY: usize = 8*1024;
+
+/// Configuration for `TiffReader::with_options`. Each setter returns the
+/// updated options, so they can be chained starting from `default()`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TiffReaderOptions {
+    pub(crate) buffer_capacity: usize,
+    pub(crate) base_offset: u64,
+    pub(crate) strict: bool,
+    pub(crate) segment_cache_capacity: usize,
+}
+
+impl Default for TiffReaderOptions {
+    fn default() -> Self {
+        TiffReaderOptions {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            base_offset: 0,
+            strict: false,
+            segment_cache_capacity: 0,
+        }
+    }
+}
+
+impl TiffReaderOptions {
+    /// Capacity in bytes of the internal `BufReader` (see
+    /// `TiffReader::with_capacity`). Defaults to 8 KiB.
+    pub fn buffer_capacity(mut self, buffer_capacity: usize) -> Self {
+        self.buffer_capacity = buffer_capacity;
+        self
+    }
+    
+    /// Position of the TIFF header in the stream (see
+    /// `TiffReader::with_base_offset`). Defaults to 0.
+    pub fn base_offset(mut self, base_offset: u64) -> Self {
+        self.base_offset = base_offset;
+        self
+    }
+    
+    /// Whether to start in strict mode (see `TiffReader::set_strict`).
+    /// Defaults to false.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+    
+    /// Number of decoded strips each subfile keeps for `read_strip` (see
+    /// `Subfile::set_segment_cache_capacity`). Defaults to 0, which
+    /// disables caching.
+    pub fn segment_cache_capacity(mut self, segment_cache_capacity: usize) -> Self {
+        self.segment_cache_capacity = segment_cache_capacity;
+        self
+    }
+}
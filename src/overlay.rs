@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use crate::error::TiffReadError;
+use crate::reader::Sharing;
+use crate::types::FieldValue;
+use crate::TiffReader;
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Returns the values of all fields of the subfile at
+    /// `subfile_index`, plus those of the subfile at `inherit_from` that
+    /// it doesn't have itself. This is the usual way of sharing metadata
+    /// in formats like OME-TIFF, where the first IFD holds it for the
+    /// whole file. Reads all IFDs first if that hasn't been done yet.
+    ///
+    /// Fields of unknown type are left out. Errors are wrapped in
+    /// `TiffReadError::InSubfile` with the index of the subfile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of range for `subfiles`.
+    pub fn effective_fields(&mut self, subfile_index: usize, inherit_from: usize) -> Result<BTreeMap<u16, FieldValue>, TiffReadError> {
+        if self.subfiles.is_empty() {
+            self.read_all_ifds()?;
+        }
+        
+        let mut value_map = |index: usize| {
+            self.subfiles[index].to_value_map(true).map_err(|err| TiffReadError::InSubfile {index, source: Box::new(err)})
+        };
+        let mut values = value_map(inherit_from)?;
+        values.extend(value_map(subfile_index)?);
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn overlay_fields_on_inherited_ones() {
+        let tiff_bytes = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![640]))
+            .entry(270, FieldValue::Ascii("<OME/>".to_string()))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![320]))
+            .entry(257, FieldValue::Short(vec![240]))
+            .raw_entry(65000, 99, 1, vec![0; 4])
+            .build();
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        
+        let values = tiff_reader.effective_fields(1, 0).unwrap();
+        assert_eq!(values.keys().cloned().collect::<Vec<_>>(), vec![256, 257, 270, 305]);
+        assert_eq!(values[&256], FieldValue::Short(vec![320]));
+        assert_eq!(values[&305], FieldValue::Ascii("lazytiff\0".to_string()));
+        
+        assert_eq!(tiff_reader.effective_fields(0, 0).unwrap(), tiff_reader.subfiles[0].to_value_map(true).unwrap());
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::{tags, TiffReader};
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Returns the PageNumber field as (page index, total pages). Page
+    /// indices start at 0, and a total of 0 means the number of pages is
+    /// unknown.
+    pub fn page_number(&mut self) -> Result<Option<(u16, u16)>, TiffReadError> {
+        let values = match self.get_u32_values(tags::PAGE_NUMBER)? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        match values[..] {
+            [page, total] if page <= u32::from(u16::MAX) && total <= u32::from(u16::MAX) => Ok(Some((page as u16, total as u16))),
+            _ => Err(ParseError::new(format!("Invalid PageNumber: {:?}", values)).into()),
+        }
+    }
+    
+    /// Returns DocumentName, the name of the document the image was
+    /// scanned from.
+    pub fn document_name(&mut self) -> Result<Option<String>, TiffReadError> {
+        self.get_ascii(tags::DOCUMENT_NAME)
+    }
+    
+    /// Returns PageName, the name of the page the image was scanned from.
+    pub fn page_name(&mut self) -> Result<Option<String>, TiffReadError> {
+        self.get_ascii(tags::PAGE_NAME)
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Returns the indices of all subfiles in `subfiles`, ordered by the
+    /// page index of their PageNumber field. Subfiles without PageNumber
+    /// come after those with it, and ties are kept in IFD chain order.
+    pub fn subfiles_in_page_order(&mut self) -> Result<Vec<usize>, TiffReadError> {
+        let mut pages = Vec::with_capacity(self.subfiles.len());
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            let page = subfile.page_number()?.map(|(page, _)| page);
+            pages.push((page.is_none(), page, index));
+        }
+        
+        pages.sort();
+        Ok(pages.into_iter().map(|(_, _, index)| index).collect())
+    }
+    
+    /// Groups the indices of all subfiles in `subfiles` by DocumentName,
+    /// in IFD chain order within each document. Subfiles without
+    /// DocumentName are grouped under the empty string.
+    pub fn group_pages_by_document(&mut self) -> Result<BTreeMap<String, Vec<usize>>, TiffReadError> {
+        let mut documents: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            let document_name = subfile.document_name()?.unwrap_or_default();
+            documents.entry(document_name).or_default().push(index);
+        }
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn order_subfiles_by_page_number() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(297, FieldValue::Short(vec![2, 3]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![64]))
+            .ifd()
+            .entry(297, FieldValue::Short(vec![0, 3]))
+            .ifd()
+            .entry(297, FieldValue::Short(vec![1, 3]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].page_number().unwrap(), Some((2, 3)));
+        assert_eq!(tiff_reader.subfiles[1].page_number().unwrap(), None);
+        assert_eq!(tiff_reader.subfiles_in_page_order().unwrap(), vec![2, 3, 0, 1]);
+        
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(297, FieldValue::Short(vec![1]))
+            .reader();
+        assert!(tiff_reader.subfiles[0].page_number().is_err());
+    }
+    
+    #[test]
+    fn group_pages_by_document_name() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(269, FieldValue::Ascii("Invoice".to_string()))
+            .entry(285, FieldValue::Ascii("Front".to_string()))
+            .ifd()
+            .entry(269, FieldValue::Ascii("Receipt".to_string()))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .ifd()
+            .entry(269, FieldValue::Ascii("Invoice".to_string()))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].page_name().unwrap(), Some("Front".to_string()));
+        assert_eq!(tiff_reader.subfiles[1].page_name().unwrap(), None);
+        assert_eq!(tiff_reader.subfiles[3].document_name().unwrap(), Some("Invoice".to_string()));
+        
+        let documents = tiff_reader.group_pages_by_document().unwrap();
+        let expected: Vec<(String, Vec<usize>)> = vec![
+            (String::new(), vec![2]),
+            ("Invoice".to_string(), vec![0, 3]),
+            ("Receipt".to_string(), vec![1]),
+        ];
+        assert_eq!(documents.into_iter().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,191 @@
+use std::convert::TryInto;
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+use crate::types::{Endianness, FieldValue};
+
+const RESOURCE_SIGNATURE: &[u8; 4] = b"8BIM";
+const IMAGE_SOURCE_DATA_HEADER: &[u8] = b"Adobe Photoshop Document Data Block\0";
+
+/// An image resource block from the Photoshop (34377) field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhotoshopResource {
+    pub id: u16,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A tagged block from the ImageSourceData (37724) field, such as the
+/// layer records under the key "Layr".
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageSourceBlock {
+    /// "8BIM" or "8B64", in big-endian order.
+    pub signature: [u8; 4],
+    /// The block key, such as "Layr", in big-endian order.
+    pub key: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Loads the ImageSourceData (37724) field, where Photoshop stores
+    /// the layers of a layered TIFF, as raw bytes.
+    pub fn image_source_data(&mut self) -> Result<Option<Vec<u8>>, TiffReadError> {
+        match self.get_value(tags::IMAGE_SOURCE_DATA)? {
+            Some(FieldValue::Byte(bytes)) | Some(FieldValue::Undefined(bytes)) => Ok(Some(bytes.clone())),
+            Some(value) => Err(TiffReadError::TypeMismatch {tag: tags::IMAGE_SOURCE_DATA, found: value.field_type()}),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads ImageSourceData and splits it into its tagged blocks. The
+    /// block data, such as the layer records, is not interpreted.
+    pub fn image_source_blocks(&mut self) -> Result<Option<Vec<ImageSourceBlock>>, TiffReadError> {
+        let endianness = self.endianness();
+        match self.image_source_data()? {
+            Some(bytes) => Ok(Some(parse_image_source_blocks(&bytes, endianness)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads the Photoshop (34377) field and splits it into its "8BIM"
+    /// resource blocks. The resource data itself is not interpreted.
+    pub fn photoshop_resources(&mut self) -> Result<Option<Vec<PhotoshopResource>>, TiffReadError> {
+        match self.get_value(tags::PHOTOSHOP)? {
+            Some(FieldValue::Byte(bytes)) | Some(FieldValue::Undefined(bytes)) => Ok(Some(parse_resource_blocks(bytes)?)),
+            Some(value) => Err(TiffReadError::TypeMismatch {tag: tags::PHOTOSHOP, found: value.field_type()}),
+            None => Ok(None),
+        }
+    }
+}
+
+/* Each block is the signature, a 2-byte ID, a Pascal string name padded
+ * to an even length (counting the length byte), and 4-byte length-
+ * prefixed data, also padded to an even length. Photoshop always writes
+ * these big-endian, independent of the TIFF byte order. */
+fn parse_resource_blocks(bytes: &[u8]) -> Result<Vec<PhotoshopResource>, ParseError> {
+    let truncated = |position: usize| ParseError::new(format!("Photoshop resource block at position {} is truncated", position));
+    
+    let mut resources = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let block = &bytes[position..];
+        if block.len() < 7 {
+            return Err(truncated(position));
+        }
+        if &block[0..4] != RESOURCE_SIGNATURE {
+            return Err(ParseError::new(format!("Expected Photoshop resource signature at position {}", position)));
+        }
+        let id = u16::from_be_bytes([block[4], block[5]]);
+        
+        let name_len = usize::from(block[6]);
+        let name_end = 7 + name_len;
+        let data_start = name_end + name_end % 2 + 4;
+        if block.len() < data_start {
+            return Err(truncated(position));
+        }
+        let name = String::from_utf8_lossy(&block[7..name_end]).to_string();
+        
+        let data_len = u32::from_be_bytes(block[data_start - 4..data_start].try_into().unwrap()) as usize;
+        let data_end = data_start.checked_add(data_len).filter(|&end| end <= block.len()).ok_or_else(|| truncated(position))?;
+        let data = block[data_start..data_end].to_vec();
+        
+        resources.push(PhotoshopResource {id, name, data});
+        position += data_end + data_len % 2;
+    }
+    
+    Ok(resources)
+}
+
+/* After a fixed header, each block is a 4-byte signature, a 4-byte key,
+ * a 4-byte length and the data, padded to a multiple of 4 bytes. Unlike
+ * image resource blocks, these follow the TIFF byte order, so in
+ * little-endian files the signature and key appear reversed. */
+fn parse_image_source_blocks(bytes: &[u8], endianness: Endianness) -> Result<Vec<ImageSourceBlock>, ParseError> {
+    if !bytes.starts_with(IMAGE_SOURCE_DATA_HEADER) {
+        return Err(ParseError::new("ImageSourceData doesn't start with the Photoshop header".to_string()));
+    }
+    let truncated = |position: usize| ParseError::new(format!("ImageSourceData block at position {} is truncated", position));
+    let in_big_endian = |field: &[u8]| -> [u8; 4] {
+        let mut field: [u8; 4] = field.try_into().unwrap();
+        if endianness == Endianness::Little {
+            field.reverse();
+        }
+        field
+    };
+    
+    let mut blocks = Vec::new();
+    let mut position = IMAGE_SOURCE_DATA_HEADER.len();
+    while position < bytes.len() {
+        let block = &bytes[position..];
+        if block.len() < 12 {
+            return Err(truncated(position));
+        }
+        let signature = in_big_endian(&block[0..4]);
+        if &signature != RESOURCE_SIGNATURE && &signature != b"8B64" {
+            return Err(ParseError::new(format!("Expected Photoshop block signature at position {}", position)));
+        }
+        let key = in_big_endian(&block[4..8]);
+        let data_len = u32::from_be_bytes(in_big_endian(&block[8..12])) as usize;
+        let data_end = 12usize.checked_add(data_len).filter(|&end| end <= block.len()).ok_or_else(|| truncated(position))?;
+        
+        blocks.push(ImageSourceBlock {signature, key, data: block[12..data_end].to_vec()});
+        position += data_end.div_ceil(4)*4;
+    }
+    
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    use super::{ImageSourceBlock, PhotoshopResource};
+    
+    #[test]
+    fn read_photoshop_resources() {
+        let blob = [
+            b"8BIM\x04\x04".as_ref(), // signature, ID 0x0404 (IPTC-NAA)
+            b"\x00\x00".as_ref(), // empty name, padded
+            b"\x00\x00\x00\x03abc\x00".as_ref(), // 3 bytes of data, padded
+            b"8BIM\x03\xED".as_ref(), // signature, ID 0x03ED (resolution info)
+            b"\x03res".as_ref(), // name "res"
+            b"\x00\x00\x00\x02\x01\x02".as_ref(), // 2 bytes of data
+        ].concat();
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(34377, FieldValue::Undefined(blob))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].photoshop_resources().unwrap(), Some(vec![
+            PhotoshopResource {id: 0x0404, name: String::new(), data: b"abc".to_vec()},
+            PhotoshopResource {id: 0x03ED, name: "res".to_string(), data: vec![1, 2]},
+        ]));
+    }
+    
+    #[test]
+    fn read_image_source_blocks() {
+        let blob = [
+            b"Adobe Photoshop Document Data Block\0".as_ref(), // header
+            b"MIB8ryaL".as_ref(), // signature "8BIM", key "Layr", reversed
+            b"\x06\x00\x00\x00".as_ref(), // 6 bytes of data
+            b"layers\x00\x00".as_ref(), // data, padded to 8 bytes
+            b"MIB8ksML".as_ref(), // signature "8BIM", key "LMsk", reversed
+            b"\x00\x00\x00\x00".as_ref(), // no data
+        ].concat();
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(37724, FieldValue::Undefined(blob.clone()))
+            .ifd()
+            .entry(37724, FieldValue::Undefined(b"not Photoshop data".to_vec()))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].image_source_data().unwrap(), Some(blob));
+        assert_eq!(tiff_reader.subfiles[0].image_source_blocks().unwrap(), Some(vec![
+            ImageSourceBlock {signature: *b"8BIM", key: *b"Layr", data: b"layers".to_vec()},
+            ImageSourceBlock {signature: *b"8BIM", key: *b"LMsk", data: vec![]},
+        ]));
+        assert!(tiff_reader.subfiles[1].image_source_blocks().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{Read, Seek};
+
+use crate::error::TiffReadError;
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+
+/// The descriptive ASCII fields saying where an image comes from, as
+/// returned by `Subfile::provenance`. Each is `None` if absent.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Provenance {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub software: Option<String>,
+    pub host_computer: Option<String>,
+    pub artist: Option<String>,
+    /// The photographer's copyright notice. An editor's notice after the
+    /// first NUL is not included.
+    pub copyright: Option<String>,
+    /// DateTime as stored, normally "YYYY:MM:DD HH:MM:SS".
+    pub date_time: Option<String>,
+    pub target_printer: Option<String>,
+}
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Loads Make, Model, Software, HostComputer, Artist, Copyright,
+    /// DateTime and TargetPrinter, as with `get_ascii`.
+    pub fn provenance(&mut self) -> Result<Provenance, TiffReadError> {
+        Ok(Provenance {
+            make: self.get_ascii(tags::MAKE)?,
+            model: self.get_ascii(tags::MODEL)?,
+            software: self.get_ascii(tags::SOFTWARE)?,
+            host_computer: self.get_ascii(tags::HOST_COMPUTER)?,
+            artist: self.get_ascii(tags::ARTIST)?,
+            copyright: self.get_ascii(tags::COPYRIGHT)?,
+            date_time: self.get_ascii(tags::DATE_TIME)?,
+            target_printer: self.get_ascii(tags::TARGET_PRINTER)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Provenance;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn load_provenance_fields() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Big)
+            .entry(271, FieldValue::Ascii("Scanner Co".to_string()))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .entry(306, FieldValue::Ascii("2020:02:29 12:00:00".to_string()))
+            .entry(316, FieldValue::Ascii("build-01".to_string()))
+            .entry(337, FieldValue::Ascii("Printer".to_string()))
+            .entry(33432, FieldValue::Ascii("Photographer\0Editor".to_string()))
+            .ifd()
+            .entry(315, FieldValue::Short(vec![1]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles[0].provenance().unwrap(), Provenance {
+            make: Some("Scanner Co".to_string()),
+            software: Some("lazytiff".to_string()),
+            host_computer: Some("build-01".to_string()),
+            copyright: Some("Photographer".to_string()),
+            date_time: Some("2020:02:29 12:00:00".to_string()),
+            target_printer: Some("Printer".to_string()),
+            ..Provenance::default()
+        });
+        assert!(tiff_reader.subfiles[1].provenance().is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{Read, Seek};
+
+use crate::error::TiffReadError;
+use crate::TiffReader;
+use crate::reader::Sharing;
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Returns the index and area (width times height) of every subfile
+    /// in `subfiles`, largest first. Subfiles of equal area are ordered
+    /// by index.
+    ///
+    /// Only the main IFD chain is considered: pyramid levels stored as
+    /// SubIFDs are not, since they are not part of `subfiles`. Use
+    /// `read_ifd_tree` to find those.
+    pub fn subfiles_by_area(&mut self) -> Result<Vec<(usize, u64)>, TiffReadError> {
+        let mut areas = Vec::with_capacity(self.subfiles.len());
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            let area = u64::from(subfile.image_width()?)*u64::from(subfile.image_length()?);
+            areas.push((index, area));
+        }
+        
+        areas.sort_by(|(index_a, area_a), (index_b, area_b)| area_b.cmp(area_a).then(index_a.cmp(index_b)));
+        Ok(areas)
+    }
+    
+    /// Returns the index of the narrowest subfile whose width is at least
+    /// `target_width`, or `None` if every subfile is narrower. Subfiles
+    /// of equal width are ordered by index.
+    ///
+    /// As with `subfiles_by_area`, only the main IFD chain is considered,
+    /// not SubIFDs.
+    pub fn best_subfile_for_width(&mut self, target_width: u32) -> Result<Option<usize>, TiffReadError> {
+        let mut best: Option<(u32, usize)> = None;
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            let width = subfile.image_width()?;
+            if width >= target_width && best.is_none_or(|(best_width, _)| width < best_width) {
+                best = Some((width, index));
+            }
+        }
+        
+        Ok(best.map(|(_, index)| index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn select_pyramid_levels() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Long(vec![1000]))
+            .entry(257, FieldValue::Long(vec![800]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![250]))
+            .entry(257, FieldValue::Short(vec![200]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![500]))
+            .entry(257, FieldValue::Short(vec![400]))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![500]))
+            .entry(257, FieldValue::Short(vec![400]))
+            .reader();
+        
+        assert_eq!(tiff_reader.subfiles_by_area().unwrap(), vec![(0, 800_000), (2, 200_000), (3, 200_000), (1, 50_000)]);
+        assert_eq!(tiff_reader.best_subfile_for_width(300).unwrap(), Some(2));
+        assert_eq!(tiff_reader.best_subfile_for_width(250).unwrap(), Some(1));
+        assert_eq!(tiff_reader.best_subfile_for_width(1001).unwrap(), None);
+    }
+}
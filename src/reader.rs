@@ -0,0 +1,258 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, BufReader};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::TiffReadError;
+use crate::{tags, OffsetWidth};
+
+/// How a `TiffReader` shares its reader with its subfiles and fields.
+/// `Threaded`, the default, uses an `Arc` and a `Mutex`, so that subfiles
+/// and fields can be sent to other threads. `Local`, as used by
+/// `LocalTiffReader`, uses an `Rc` and a `RefCell` instead, which saves
+/// the locking on every value read, but makes them neither `Send` nor
+/// `Sync`.
+///
+/// This trait is sealed: `Threaded` and `Local` are its only
+/// implementations.
+pub trait Sharing: private::Sealed + Sized {
+    #[doc(hidden)]
+    type Ref<T>: Clone + Deref<Target = T>;
+    #[doc(hidden)]
+    type Lock<T>;
+    #[doc(hidden)]
+    type Flag;
+    
+    #[doc(hidden)]
+    fn new_ref<T>(value: T) -> Self::Ref<T>;
+    #[doc(hidden)]
+    fn new_lock<T>(value: T) -> Self::Lock<T>;
+    #[doc(hidden)]
+    fn with_lock<T, U, F: FnOnce(&mut T) -> U>(lock: &Self::Lock<T>, f: F) -> U;
+    #[doc(hidden)]
+    fn new_flag(value: bool) -> Self::Flag;
+    #[doc(hidden)]
+    fn get_flag(flag: &Self::Flag) -> bool;
+    #[doc(hidden)]
+    fn set_flag(flag: &Self::Flag, value: bool);
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Shares the reader through an `Arc<Mutex<_>>`. See `Sharing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threaded {}
+
+impl private::Sealed for Threaded {}
+
+impl Sharing for Threaded {
+    type Ref<T> = Arc<T>;
+    type Lock<T> = Mutex<T>;
+    type Flag = AtomicBool;
+    
+    fn new_ref<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+    
+    fn new_lock<T>(value: T) -> Mutex<T> {
+        Mutex::new(value)
+    }
+    
+    fn with_lock<T, U, F: FnOnce(&mut T) -> U>(lock: &Mutex<T>, f: F) -> U {
+        f(&mut lock.lock().unwrap())
+    }
+    
+    fn new_flag(value: bool) -> AtomicBool {
+        AtomicBool::new(value)
+    }
+    
+    fn get_flag(flag: &AtomicBool) -> bool {
+        flag.load(Ordering::Relaxed)
+    }
+    
+    fn set_flag(flag: &AtomicBool, value: bool) {
+        flag.store(value, Ordering::Relaxed);
+    }
+}
+
+/// Shares the reader through an `Rc<RefCell<_>>`. See `Sharing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Local {}
+
+impl private::Sealed for Local {}
+
+impl Sharing for Local {
+    type Ref<T> = Rc<T>;
+    type Lock<T> = RefCell<T>;
+    type Flag = Cell<bool>;
+    
+    fn new_ref<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+    
+    fn new_lock<T>(value: T) -> RefCell<T> {
+        RefCell::new(value)
+    }
+    
+    fn with_lock<T, U, F: FnOnce(&mut T) -> U>(lock: &RefCell<T>, f: F) -> U {
+        f(&mut lock.borrow_mut())
+    }
+    
+    fn new_flag(value: bool) -> Cell<bool> {
+        Cell::new(value)
+    }
+    
+    fn get_flag(flag: &Cell<bool>) -> bool {
+        flag.get()
+    }
+    
+    fn set_flag(flag: &Cell<bool>, value: bool) {
+        flag.set(value);
+    }
+}
+
+/// The handle to the `SharedReader` held by a `TiffReader` and cloned
+/// into each of its subfiles and fields.
+pub(crate) struct ReaderRef<R, S: Sharing>(S::Ref<SharedReader<R, S>>);
+
+impl<R, S: Sharing> ReaderRef<R, S> {
+    pub fn new(shared_reader: SharedReader<R, S>) -> Self {
+        ReaderRef(S::new_ref(shared_reader))
+    }
+}
+
+impl<R, S: Sharing> Clone for ReaderRef<R, S> {
+    fn clone(&self) -> Self {
+        ReaderRef(self.0.clone())
+    }
+}
+
+impl<R, S: Sharing> Deref for ReaderRef<R, S> {
+    type Target = SharedReader<R, S>;
+    
+    fn deref(&self) -> &SharedReader<R, S> {
+        &self.0
+    }
+}
+
+impl<R, S: Sharing> fmt::Debug for ReaderRef<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The buffered reader shared between a `TiffReader` and all of its
+/// subfiles and fields, together with the stream length measured when
+/// the reader was set up.
+///
+/// All offsets are relative to `base_offset`, the position of the TIFF
+/// header in the underlying stream, and `stream_len` is measured from
+/// there as well.
+pub(crate) struct SharedReader<R, S: Sharing> {
+    buf_reader: S::Lock<BufReader<R>>,
+    base_offset: u64,
+    stream_len: u64,
+    ifd_pointer_tags: S::Lock<BTreeSet<u16>>,
+    strict: S::Flag,
+    offset_width: OffsetWidth,
+    segment_cache_capacity: usize,
+}
+
+impl<R: Read + Seek, S: Sharing> SharedReader<R, S> {
+    pub fn new(mut buf_reader: BufReader<R>, base_offset: u64) -> Result<Self, TiffReadError> {
+        let stream_len = buf_reader.seek(SeekFrom::End(0))?.saturating_sub(base_offset);
+        
+        let ifd_pointer_tags = [tags::EXIF_IFD, tags::GPS_IFD].iter().cloned().collect();
+        
+        Ok(SharedReader {
+            buf_reader: S::new_lock(buf_reader),
+            base_offset,
+            stream_len,
+            ifd_pointer_tags: S::new_lock(ifd_pointer_tags),
+            strict: S::new_flag(false),
+            offset_width: OffsetWidth::Four,
+            segment_cache_capacity: 0,
+        })
+    }
+    
+    pub fn stream_len(&self) -> u64 {
+        self.stream_len
+    }
+    
+    /// Reads exactly `len` bytes starting at `offset`. The range is
+    /// checked against the stream length before anything is allocated,
+    /// so a bogus offset or length can't trigger a huge allocation.
+    pub fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Vec<u8>, TiffReadError> {
+        let out_of_bounds = TiffReadError::OutOfBounds {offset, len, stream_len: self.stream_len};
+        let end = u64::try_from(len).ok().and_then(|len_u64| offset.checked_add(len_u64));
+        match end {
+            Some(end) if end <= self.stream_len => {},
+            _ => return Err(out_of_bounds),
+        }
+        
+        let mut buffer = vec![0u8; len];
+        
+        S::with_lock(&self.buf_reader, |buf_reader| -> Result<(), TiffReadError> {
+            buf_reader.seek(SeekFrom::Start(self.base_offset + offset))?;
+            buf_reader.read_exact(&mut buffer)?;
+            Ok(())
+        })?;
+        
+        Ok(buffer)
+    }
+    
+    pub fn register_ifd_pointer(&self, tag: u16) {
+        S::with_lock(&self.ifd_pointer_tags, |tags| tags.insert(tag));
+    }
+    
+    pub fn ifd_pointer_tags(&self) -> BTreeSet<u16> {
+        S::with_lock(&self.ifd_pointer_tags, |tags| tags.clone())
+    }
+    
+    pub fn set_strict(&self, strict: bool) {
+        S::set_flag(&self.strict, strict);
+    }
+    
+    pub fn is_strict(&self) -> bool {
+        S::get_flag(&self.strict)
+    }
+    
+    /// Set once the header has been parsed, before the reader is shared.
+    pub fn set_offset_width(&mut self, offset_width: OffsetWidth) {
+        self.offset_width = offset_width;
+    }
+    
+    pub fn offset_width(&self) -> OffsetWidth {
+        self.offset_width
+    }
+    
+    /// The initial segment cache capacity of new subfiles. Set before
+    /// the reader is shared.
+    pub fn set_segment_cache_capacity(&mut self, capacity: usize) {
+        self.segment_cache_capacity = capacity;
+    }
+    
+    pub fn segment_cache_capacity(&self) -> usize {
+        self.segment_cache_capacity
+    }
+}
+
+/* Only the plain fields are shown, so that the underlying reader
+ * doesn't need to implement `Debug`. */
+impl<R, S: Sharing> fmt::Debug for SharedReader<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedReader")
+            .field("base_offset", &self.base_offset)
+            .field("stream_len", &self.stream_len)
+            .field("offset_width", &self.offset_width)
+            .field("segment_cache_capacity", &self.segment_cache_capacity)
+            .finish_non_exhaustive()
+    }
+}
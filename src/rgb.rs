@@ -0,0 +1,230 @@
+use std::io::{Read, Seek};
+
+use crate::baseline::{ImageKind, SampleFormat};
+use crate::error::{ParseError, TiffReadError};
+use crate::image::unpack_subbyte_samples;
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::tags;
+use crate::types::Endianness;
+
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    /// Decodes the image and converts it to 8-bit RGB, returning
+    /// (width, height, pixels) with the pixels interleaved row by row.
+    ///
+    /// Grayscale, palette, RGB, CMYK and YCbCr images with unsigned
+    /// samples of 1, 2, 4, 8 or 16 bits are supported. Samples are scaled
+    /// to 8 bits, palette indices are looked up in ColorMap, YCbCr is
+    /// converted using YCbCrCoefficients and ReferenceBlackWhite, and
+    /// CMYK is converted naively, without a color profile. Alpha and any
+    /// other extra samples are dropped, and rows are in stored order,
+    /// whatever the Orientation.
+    ///
+    /// Other photometric interpretations, as well as subsampled YCbCr,
+    /// result in `TiffReadError::UnsupportedPhotometricInterpretation`.
+    pub fn read_rgb8(&mut self) -> Result<(u32, u32, Vec<u8>), TiffReadError> {
+        let width = self.image_width()?;
+        let height = self.image_length()?;
+        let bits = self.unpackable_bits_per_sample()?;
+        
+        let photometric_interpretation = self.get_u32(tags::PHOTOMETRIC_INTERPRETATION)?;
+        let unsupported = match photometric_interpretation {
+            Some(photometric_interpretation) => TiffReadError::UnsupportedPhotometricInterpretation {photometric_interpretation},
+            None => TiffReadError::MissingRequiredTag {tag: tags::PHOTOMETRIC_INTERPRETATION},
+        };
+        let samples = self.unpacked_samples(width, bits)?;
+        let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+        let pixels = samples.chunks_exact(samples_per_pixel);
+        
+        let max = (1u32 << bits) - 1;
+        let scale = |sample: u16| ((u32::from(sample)*255 + max/2)/max) as u8;
+        let rgb = match self.image_kind()? {
+            ImageKind::Bilevel | ImageKind::Grayscale {..} => {
+                let white_is_zero = photometric_interpretation == Some(0);
+                pixels.flat_map(|pixel| {
+                    let gray = if white_is_zero {255 - scale(pixel[0])} else {scale(pixel[0])};
+                    vec![gray; 3]
+                }).collect()
+            }
+            ImageKind::Rgb | ImageKind::Rgba {..} => pixels.flat_map(|pixel| pixel[..3].iter().map(|&sample| scale(sample)).collect::<Vec<u8>>()).collect(),
+            ImageKind::Palette {..} => {
+                let color_map = self.color_map()?.ok_or(TiffReadError::MissingRequiredTag {tag: tags::COLOR_MAP})?;
+                pixels.flat_map(|pixel| color_map[usize::from(pixel[0])].iter().map(|&value| (value >> 8) as u8).collect::<Vec<u8>>()).collect()
+            }
+            ImageKind::Cmyk => pixels.flat_map(|pixel| {
+                let black = 255 - u32::from(scale(pixel[3]));
+                pixel[..3].iter().map(|&ink| ((255 - u32::from(scale(ink)))*black/255) as u8).collect::<Vec<u8>>()
+            }).collect(),
+            ImageKind::YCbCr => {
+                if self.ycbcr_subsampling()? != (1, 1) {
+                    return Err(unsupported);
+                }
+                let [luma_red, luma_green, luma_blue] = self.ycbcr_coefficients()?;
+                let reference_black_white = self.reference_black_white()?;
+                if reference_black_white.iter().any(|&(black, white)| black == white) {
+                    return Err(ParseError::new("ReferenceBlackWhite has equal black and white points".to_string()).into());
+                }
+                /* Y is mapped to 0..255 and Cb and Cr to -127..127, as in
+                 * section 21 of the TIFF 6.0 spec. */
+                let component = |sample: u16, index: usize, coding_range: f64| {
+                    let (black, white) = reference_black_white[index];
+                    (f64::from(sample) - black)*coding_range/(white - black)
+                };
+                let to_u8 = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+                pixels.flat_map(|pixel| {
+                    let y = component(pixel[0], 0, 255.0);
+                    let cb = component(pixel[1], 1, 127.0);
+                    let cr = component(pixel[2], 2, 127.0);
+                    let red = cr*(2.0 - 2.0*luma_red) + y;
+                    let blue = cb*(2.0 - 2.0*luma_blue) + y;
+                    let green = (y - luma_blue*blue - luma_red*red)/luma_green;
+                    vec![to_u8(red), to_u8(green), to_u8(blue)]
+                }).collect()
+            }
+            ImageKind::Other => return Err(unsupported),
+        };
+        
+        Ok((width, height, rgb))
+    }
+    
+    /* Returns the bits per sample if they are the same for all samples,
+     * which must be unsigned integers of 1, 2, 4, 8 or 16 bits, as
+     * `unpacked_samples` requires. */
+    pub(crate) fn unpackable_bits_per_sample(&mut self) -> Result<u16, TiffReadError> {
+        let bits_per_sample = self.bits_per_sample()?;
+        let bits = match bits_per_sample.first() {
+            Some(&bits) => bits,
+            None => return Err(ParseError::new("BitsPerSample has no values".to_string()).into()),
+        };
+        if bits_per_sample.iter().any(|&other_bits| other_bits != bits) || ![1, 2, 4, 8, 16].contains(&bits) {
+            return Err(TiffReadError::UnsupportedBitsPerSample {bits_per_sample: bits});
+        }
+        if self.sample_format()?.iter().any(|&sample_format| sample_format != SampleFormat::Unsigned) {
+            return Err(ParseError::new("Only unsigned integer samples can be converted".to_string()).into());
+        }
+        Ok(bits)
+    }
+    
+    /* Decodes the image into one u16 per sample, in chunky order even for
+     * planar images. */
+    pub(crate) fn unpacked_samples(&mut self, width: u32, bits: u16) -> Result<Vec<u16>, TiffReadError> {
+        let endianness = self.endianness();
+        let is_planar = self.planar_configuration()? == 2;
+        let samples_per_pixel = usize::from(self.samples_per_pixel()?);
+        let samples_per_row = width as usize*if is_planar {1} else {samples_per_pixel};
+        
+        let mut planes: Vec<Vec<u16>> = vec![Vec::new(); if is_planar {samples_per_pixel} else {1}];
+        for row in self.strip_rows() {
+            let row = row?;
+            let plane = &mut planes[usize::from(row.plane)];
+            match bits {
+                1 | 2 | 4 => plane.extend(unpack_subbyte_samples(&row.data, samples_per_row, bits).into_iter().map(u16::from)),
+                8 => plane.extend(row.data[..samples_per_row].iter().map(|&sample| u16::from(sample))),
+                _ => plane.extend(row.data[..2*samples_per_row].chunks_exact(2).map(|bytes| match endianness {
+                    Endianness::Little => u16::from_le_bytes([bytes[0], bytes[1]]),
+                    Endianness::Big => u16::from_be_bytes([bytes[0], bytes[1]]),
+                })),
+            }
+        }
+        
+        if !is_planar {
+            return Ok(planes.remove(0));
+        }
+        let pixel_count = planes[0].len();
+        Ok((0..pixel_count).flat_map(|pixel| planes.iter().map(move |plane| plane[pixel])).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TiffReadError;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    fn image(width: u16, photometric_interpretation: u16, bits_per_sample: Vec<u16>) -> TiffBuilder {
+        let samples_per_pixel = bits_per_sample.len() as u16;
+        TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![width]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(258, FieldValue::Short(bits_per_sample))
+            .entry(262, FieldValue::Short(vec![photometric_interpretation]))
+            .entry(277, FieldValue::Short(vec![samples_per_pixel]))
+    }
+    
+    #[test]
+    fn convert_to_rgb8() {
+        let mut white_is_zero = image(2, 0, vec![8]).strips(vec![vec![0, 255]]).reader();
+        assert_eq!(white_is_zero.subfiles[0].read_rgb8().unwrap(), (2, 1, vec![255, 255, 255, 0, 0, 0]));
+        
+        let mut rgb16 = image(1, 2, vec![16, 16, 16]).strips(vec![vec![0xFF, 0xFF, 0x00, 0x80, 0x00, 0x00]]).reader();
+        assert_eq!(rgb16.subfiles[0].read_rgb8().unwrap().2, vec![255, 128, 0]);
+        
+        let mut planar_rgba = image(2, 2, vec![8, 8, 8, 8])
+            .entry(284, FieldValue::Short(vec![2]))
+            .entry(338, FieldValue::Short(vec![2]))
+            .strips(vec![vec![1, 2], vec![3, 4], vec![5, 6], vec![255, 255]])
+            .reader();
+        assert_eq!(planar_rgba.subfiles[0].read_rgb8().unwrap().2, vec![1, 3, 5, 2, 4, 6]);
+        
+        let mut palette = image(3, 3, vec![2])
+            .entry(320, FieldValue::Short(vec![0, 0xFFFF, 0, 0, 0, 0, 0xFFFF, 0, 0, 0, 0, 0x8000]))
+            .strips(vec![vec![0b0001_1000]])
+            .reader();
+        assert_eq!(palette.subfiles[0].read_rgb8().unwrap().2, vec![0, 0, 0, 255, 0, 0, 0, 255, 0]);
+        
+        let mut cmyk = image(1, 5, vec![8, 8, 8, 8]).strips(vec![vec![255, 0, 0, 51]]).reader();
+        assert_eq!(cmyk.subfiles[0].read_rgb8().unwrap().2, vec![0, 204, 204]);
+        
+        let mut ycbcr = image(2, 6, vec![8, 8, 8])
+            .entry(530, FieldValue::Short(vec![1, 1]))
+            .strips(vec![vec![255, 128, 128, 76, 85, 255]])
+            .reader();
+        assert_eq!(ycbcr.subfiles[0].read_rgb8().unwrap().2, vec![255, 255, 255, 254, 0, 0]);
+    }
+    
+    #[test]
+    fn reject_unsupported_images() {
+        let mut tiff_reader = image(1, 8, vec![8, 8, 8]).strips(vec![vec![0; 3]])
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .entry(262, FieldValue::Short(vec![6]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .strips(vec![vec![0; 3]])
+            .ifd()
+            .entry(256, FieldValue::Short(vec![1]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .entry(262, FieldValue::Long(vec![0x1_0002]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .strips(vec![vec![0; 3]])
+            .reader();
+        
+        for subfile in &mut tiff_reader.subfiles {
+            match subfile.read_rgb8() {
+                Err(TiffReadError::UnsupportedPhotometricInterpretation {..}) => {},
+                other => panic!("Expected UnsupportedPhotometricInterpretation, got {:?}", other),
+            }
+        }
+        match tiff_reader.subfiles[2].read_rgb8() {
+            Err(TiffReadError::UnsupportedPhotometricInterpretation {photometric_interpretation: 0x1_0002}) => {},
+            other => panic!("Expected UnsupportedPhotometricInterpretation 0x10002, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn reject_zero_samples_per_pixel() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![1]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(262, FieldValue::Short(vec![1]))
+            .entry(277, FieldValue::Short(vec![0]))
+            .strips(vec![vec![0]])
+            .reader();
+        match tiff_reader.subfiles[0].read_rgb8() {
+            Err(TiffReadError::Parse(_)) => {},
+            other => panic!("Expected ParseError, got {:?}", other),
+        }
+    }
+}
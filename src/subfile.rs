@@ -1,21 +1,42 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryInto;
-use std::io::{Read, Seek, BufReader};
-use std::sync::{Arc, Mutex};
+use std::ffi::CString;
+use std::io::{Read, Seek};
+use std::ops::Range;
 
 use crate::types::*;
-use crate::error::ParseError;
+use crate::error::{escaped_string_from_bytes, ParseError, TiffReadError};
+use crate::image::SegmentCache;
+use crate::reader::{ReaderRef, Sharing, Threaded};
+use crate::OffsetWidth;
 
 use FieldState::*;
 
+/* Field type code for IFD offsets, introduced by TIFF Technical Note 1
+ * as an alternative to LONG for sub-IFD pointers. */
+const IFD_FIELD_TYPE: u16 = 13;
+
 #[derive(Debug, Clone)]
-pub struct Field<R> {
-    buf_reader_ref: Arc<Mutex<BufReader<R>>>,
+pub struct Field<R, S: Sharing = Threaded> {
+    reader_ref: ReaderRef<R, S>,
     endianness: Endianness,
+    entry_offset: u64,
+    raw_entry: [u8; 12],
+    ifd_byte_range: Range<u64>,
     state: FieldState,
 }
 
-impl<R: Read + Seek> Field<R> {
+/// Where a field's value is stored in the file.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValueLocation {
+    /// The value fits in the value/offset bytes of the IFD entry and
+    /// occupies the given byte range of the file.
+    Inline {byte_range: Range<u64>},
+    /// The value is stored elsewhere, at the offset given in the entry.
+    OutOfLine {offset: u64, len: usize},
+}
+
+impl<R: Read + Seek, S: Sharing> Field<R, S> {
     pub fn field_type(&self) -> Option<FieldType> {
         match &self.state {
             FieldState::Local(value) => Some(value.field_type()),
@@ -27,6 +48,129 @@ impl<R: Read + Seek> Field<R> {
         }
     }
     
+    /// Returns the field type code as found in the IFD entry. Unlike
+    /// `field_type`, this also works for types not defined in TIFF 6.0,
+    /// and it is not affected by `reinterpret`.
+    pub fn field_type_raw(&self) -> u16 {
+        self.raw_entry_parts().0
+    }
+    
+    /// Returns `true` if the field type is not one defined in TIFF 6.0.
+    /// Such fields are kept, but their values cannot be decoded.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self.state, FieldState::Unknown {..})
+    }
+    
+    /// Returns the raw value/offset bytes of the IFD entry for a field
+    /// of unknown type, or `None` for fields of known types.
+    pub fn unknown_value_offset_bytes(&self) -> Option<[u8; 4]> {
+        match &self.state {
+            FieldState::Unknown {field_type_raw: _, count: _, value_offset_bytes} => Some(*value_offset_bytes),
+            _ => None,
+        }
+    }
+    
+    /// Offset of the 12-byte IFD entry describing this field.
+    pub fn entry_offset(&self) -> u64 {
+        self.entry_offset
+    }
+    
+    /// The 12-byte IFD entry describing this field, exactly as it was
+    /// read from the file.
+    pub fn raw_entry(&self) -> [u8; 12] {
+        self.raw_entry
+    }
+    
+    /// Returns where the field's value is stored, or `None` for fields
+    /// of unknown type, whose value size can't be determined. Like
+    /// `raw_bytes`, this goes by the raw IFD entry, so the result is the
+    /// same whether or not the value has been loaded.
+    pub fn value_location(&self) -> Option<ValueLocation> {
+        let (field_type_raw, count, value_offset_bytes) = self.raw_entry_parts();
+        let field_type = FieldType::from_u16(field_type_raw).or_else(|| self.field_type())?;
+        let len = compute_value_buffer_size(field_type, count)?;
+        /* The value/offset is the last part of the entry. */
+        let offset_width = self.reader_ref.offset_width();
+        if len as u64 <= offset_width.bytes() {
+            let value_start = self.entry_offset + offset_width.entry_len() - offset_width.bytes();
+            Some(ValueLocation::Inline {byte_range: value_start..value_start + len as u64})
+        } else {
+            Some(ValueLocation::OutOfLine {offset: u64::from(self.u32_from_bytes(value_offset_bytes)), len})
+        }
+    }
+    
+    /// Reads the value's bytes as stored in the file, without decoding
+    /// them: the relevant prefix of the IFD entry's value bytes for
+    /// inline values, and the bytes at the value offset otherwise. The
+    /// count is taken from the raw IFD entry, since a decoded ASCII value
+    /// doesn't keep it. Fails for fields of unknown type, whose value
+    /// size is unknown, unless they have been given a type with
+    /// `reinterpret`.
+    pub fn raw_bytes(&self) -> Result<Vec<u8>, TiffReadError> {
+        let (field_type_raw, count, value_offset_bytes) = self.raw_entry_parts();
+        let field_type = FieldType::from_u16(field_type_raw).or_else(|| self.field_type())
+            .ok_or_else(|| ParseError::new(format!("Value size of unknown field type {} is unknown", field_type_raw)))?;
+        
+        let len = compute_value_buffer_size(field_type, count).ok_or(TiffReadError::BufferSizeOverflow {field_type, count})?;
+        self.stored_bytes(len, value_offset_bytes)
+    }
+    
+    /// Decodes the stored value again as `as_type`, replacing the
+    /// current value. The bytes are the same as those read by
+    /// `raw_bytes`, and must be a whole number of `as_type` values. For
+    /// fields of unknown type, the count in the IFD entry is taken to be
+    /// a count of `as_type` values.
+    ///
+    /// This is meant for files that store a field with the wrong type,
+    /// e.g. offsets stored as BYTE rather than LONG values.
+    pub fn reinterpret(&mut self, as_type: FieldType) -> Result<(), TiffReadError> {
+        let (field_type_raw, count, value_offset_bytes) = self.raw_entry_parts();
+        let stored_type = FieldType::from_u16(field_type_raw).unwrap_or(as_type);
+        let len = compute_value_buffer_size(stored_type, count).ok_or(TiffReadError::BufferSizeOverflow {field_type: stored_type, count})?;
+        if len % as_type.size_of() != 0 {
+            return Err(ParseError::new(format!("Value of {} bytes can't be read as {} values", len, as_type)).into());
+        }
+        
+        let new_count = (len/as_type.size_of()) as u32;
+        let buffer = self.stored_bytes(len, value_offset_bytes)?;
+        let value = value_from_buffer(as_type, new_count, &buffer, self.endianness)?;
+        self.state = if len <= 4 {
+            FieldState::Local(value)
+        } else {
+            FieldState::Loaded {value, offset: self.u32_from_bytes(value_offset_bytes)}
+        };
+        
+        Ok(())
+    }
+    
+    /* The type, count and value/offset bytes of the raw IFD entry. */
+    pub(crate) fn raw_entry_parts(&self) -> (u16, u32, [u8; 4]) {
+        let field_type_bytes: [u8; 2] = self.raw_entry[2..4].try_into().unwrap();
+        let field_type_raw = match self.endianness {
+            Endianness::Little => u16::from_le_bytes(field_type_bytes),
+            Endianness::Big => u16::from_be_bytes(field_type_bytes),
+        };
+        let count = self.u32_from_bytes(self.raw_entry[4..8].try_into().unwrap());
+        (field_type_raw, count, self.raw_entry[8..12].try_into().unwrap())
+    }
+    
+    fn u32_from_bytes(&self, bytes: [u8; 4]) -> u32 {
+        match self.endianness {
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        }
+    }
+    
+    /* Reads `len` bytes of value stored inline or at the offset given
+     * by the value/offset bytes. */
+    fn stored_bytes(&self, len: usize, value_offset_bytes: [u8; 4]) -> Result<Vec<u8>, TiffReadError> {
+        if len <= 4 {
+            Ok(value_offset_bytes[..len].to_vec())
+        } else {
+            self.reader_ref.read_bytes_at(u64::from(self.u32_from_bytes(value_offset_bytes)), len)
+        }
+    }
+    
     pub fn count(&self) -> u32 {
         match &self.state {
             FieldState::Local(value) => {
@@ -46,65 +190,111 @@ impl<R: Read + Seek> Field<R> {
         }
     }
     
+    /// Whether the value is available without I/O, either because it is
+    /// stored in the IFD entry or because it has been loaded.
+    pub fn is_loaded(&self) -> bool {
+        matches!(self.state, FieldState::Local(_) | FieldState::Loaded {..})
+    }
+    
     /// Returns a `FieldValue` reference if the field value fit into
     /// the 4 bytes in the IFD. Will not trigger I/O operations.
     pub fn get_value_if_local(&self) -> Option<&FieldValue> {
         match &self.state {
-            FieldState::Local(value) => Some(&value),
+            FieldState::Local(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    /// Returns a borrowed view of the value if it is available without
+    /// I/O, see `is_loaded`.
+    pub fn value_ref(&self) -> Option<FieldValueRef<'_>> {
+        match &self.state {
+            FieldState::Local(value) => Some(value.as_value_ref()),
+            FieldState::Loaded {value, offset: _} => Some(value.as_value_ref()),
             _ => None,
         }
     }
     
-    pub fn get_value(&mut self) -> Result<Option<&FieldValue>, Box<dyn std::error::Error>> {
+    pub fn get_value(&mut self) -> Result<Option<&FieldValue>, TiffReadError> {
         self.load()?;
         
         match &self.state {
-            FieldState::Local(value) => Ok(Some(&value)),
-            FieldState::Loaded {value, offset: _} => Ok(Some(&value)),
+            FieldState::Local(value) => Ok(Some(value)),
+            FieldState::Loaded {value, offset: _} => Ok(Some(value)),
             _ => Ok(None),
         }
     }
     
-    pub fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        match self.state {
-            FieldState::NotLoaded {field_type, count, offset} => {
-                // TODO: overflow error type
-                let required_buffer_size = compute_value_buffer_size(field_type, count).ok_or(ParseError::new("Required buffer size too big".to_string()))?;
-                let mut value_buffer = vec![0u8; required_buffer_size];
-                
-                let mut buf_reader = self.buf_reader_ref.lock().unwrap();
-                buf_reader.seek(std::io::SeekFrom::Start(u64::from(offset)))?;
-                buf_reader.read_exact(&mut value_buffer)?;
-                
-                let value = value_from_buffer(field_type.clone(), count, &value_buffer, self.endianness)?;
-                
-                self.state = FieldState::Loaded {value, offset};
-                
-                Ok(())
+    pub fn load(&mut self) -> Result<(), TiffReadError> {
+        if let FieldState::NotLoaded {field_type, count, offset} = self.state {
+            let required_buffer_size = compute_value_buffer_size(field_type, count).ok_or(TiffReadError::BufferSizeOverflow {field_type, count})?;
+            
+            /* A value inside the header or the IFD itself can be read,
+             * but is almost certainly the result of a corrupt offset.
+             * Outside strict mode, `Subfile::check_value_region_integrity`
+             * reports it instead. */
+            if self.reader_ref.is_strict() {
+                let start = u64::from(offset);
+                let end = start + required_buffer_size as u64;
+                if start < 8 || (start < self.ifd_byte_range.end && self.ifd_byte_range.start < end) {
+                    return Err(TiffReadError::ValueOverlapsStructure {offset: start, len: required_buffer_size});
+                }
             }
-            _ => Ok(()),
+            
+            trace_event!(offset, field_type = %field_type, count, len = required_buffer_size, "loading field value");
+            let value_buffer = self.reader_ref.read_bytes_at(u64::from(offset), required_buffer_size)?;
+            
+            let value = value_from_buffer(field_type, count, &value_buffer, self.endianness)?;
+            
+            self.state = FieldState::Loaded {value, offset};
         }
+        
+        Ok(())
     }
     
     pub fn unload(&mut self) {
-        match &self.state {
-            FieldState::Loaded {value, offset} => {
-                let field_type = value.field_type();
-                let count_usize = value.count();
-                
-                /* The FieldValue will always be built from a
-                 * u32 `count`, so this will always succeed. */
-                let count: u32 = count_usize.try_into().unwrap();
-                
-                let offset: u32 = *offset;
-                
-                self.state = FieldState::NotLoaded {field_type: field_type, count: count, offset: offset};
-            }
-            _ => {},
+        if let FieldState::Loaded {value, offset} = &self.state {
+            let field_type = value.field_type();
+            let count_usize = value.count();
+            
+            /* The FieldValue will always be built from a
+             * u32 `count`, so this will always succeed. */
+            let count: u32 = count_usize.try_into().unwrap();
+            
+            let offset: u32 = *offset;
+            
+            self.state = FieldState::NotLoaded {field_type, count, offset};
         }
     }
 }
 
+/// Read-only view of a field that doesn't depend on the reader type, so
+/// fields from different readers can be handled as `&dyn FieldInfo`.
+pub trait FieldInfo {
+    fn field_type(&self) -> Option<FieldType>;
+    fn count(&self) -> u32;
+    fn is_loaded(&self) -> bool;
+    fn get_value_if_local(&self) -> Option<&FieldValue>;
+}
+
+impl<R: Read + Seek, S: Sharing> FieldInfo for Field<R, S> {
+    fn field_type(&self) -> Option<FieldType> {
+        Field::field_type(self)
+    }
+    
+    fn count(&self) -> u32 {
+        Field::count(self)
+    }
+    
+    fn is_loaded(&self) -> bool {
+        Field::is_loaded(self)
+    }
+    
+    fn get_value_if_local(&self) -> Option<&FieldValue> {
+        Field::get_value_if_local(self)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum FieldState {
     Local(FieldValue),
@@ -114,12 +304,11 @@ enum FieldState {
 }
 
 impl FieldState {
-    fn from_ifd_entry_data(field_type_raw: u16, count: u32, value_offset_bytes: [u8; 4], endianness: Endianness) -> Result<FieldState, Box<dyn std::error::Error>> {
+    fn from_ifd_entry_data(field_type_raw: u16, count: u32, value_offset_bytes: [u8; 4], endianness: Endianness) -> Result<FieldState, TiffReadError> {
         match FieldType::from_u16(field_type_raw) {
-            None => Ok(Unknown {field_type_raw: field_type_raw, count: count, value_offset_bytes: value_offset_bytes}),
+            None => Ok(Unknown {field_type_raw, count, value_offset_bytes}),
             Some(field_type) => {
-                // TODO: new overflow error type?
-                let required_buffer_size = compute_value_buffer_size(field_type, count).ok_or(ParseError::new("Required buffer size too big".to_string()))?;
+                let required_buffer_size = compute_value_buffer_size(field_type, count).ok_or(TiffReadError::BufferSizeOverflow {field_type, count})?;
                 
                 if required_buffer_size <= 4 {
                     /* The value(s) fit in the IFD entry, load them
@@ -137,7 +326,7 @@ impl FieldState {
                         Endianness::Big => u32::from_be_bytes(value_offset_bytes),
                     };
                     
-                    Ok(NotLoaded {field_type: field_type, count: count, offset: offset})
+                    Ok(NotLoaded {field_type, count, offset})
                 }
             },
         }
@@ -145,47 +334,56 @@ impl FieldState {
 }
 
 #[derive(Debug)]
-pub struct Subfile<R> {
-    buf_reader_ref: Arc<Mutex<BufReader<R>>>,
+pub struct Subfile<R, S: Sharing = Threaded> {
+    reader_ref: ReaderRef<R, S>,
     endianness: Endianness,
-    fields: BTreeMap<u16, Field<R>>,
+    offset: u32,
+    entry_count: u16,
+    fields: BTreeMap<u16, Field<R, S>>,
     offset_to_next_ifd: Option<u32>,
+    segment_cache: SegmentCache,
 }
 
-impl<R: Read + Seek> Subfile<R> {
-    pub fn new(buf_reader_ref: Arc<Mutex<BufReader<R>>>, offset: u32, endianness: Endianness) -> Result<Self, Box<dyn std::error::Error>> {
-        let ifd_entry_count: u16;
-        let ifd_remaining_buffer_size: usize;
-        let mut ifd_remaining_buffer: Vec<u8>;
-        
-        /* Restrict the borrow of buf_reader_ref to this scope so that
-         * we can save it as a field in the output struct. */
-        {
-            let mut buf_reader = buf_reader_ref.lock().unwrap();
-            
-            buf_reader.seek(std::io::SeekFrom::Start(u64::from(offset)))?;
-            
-            let mut ifd_entry_count_bytes = [0u8; 2];
-            buf_reader.read_exact(&mut ifd_entry_count_bytes)?;
-            
-            ifd_entry_count = match endianness {
-                Endianness::Little => u16::from_le_bytes(ifd_entry_count_bytes),
-                Endianness::Big => u16::from_be_bytes(ifd_entry_count_bytes),
-            };
-            
-            // TODO: handle overflow
-            ifd_remaining_buffer_size = 12*usize::from(ifd_entry_count) + 4;
-            
-            ifd_remaining_buffer = vec![0u8; ifd_remaining_buffer_size];
-            
-            /* Read remainder of the IFD now that we know how many bytes
-             * to read. */
-            buf_reader.read_exact(&mut ifd_remaining_buffer)?;
+impl<R: Read + Seek, S: Sharing> Subfile<R, S> {
+    pub(crate) fn new(reader_ref: ReaderRef<R, S>, offset: u32, endianness: Endianness) -> Result<Self, TiffReadError> {
+        /* The IFD's layout comes from the offset width, but entries can
+         * only be decoded in the classic layout so far. */
+        let offset_width = reader_ref.offset_width();
+        if offset_width != OffsetWidth::Four {
+            return Err(ParseError::new(format!("Can't read IFD at offset {} with {}-byte offsets", offset, offset_width.bytes())).into());
+        }
+        
+        let ifd_entry_count_bytes: [u8; 2] = reader_ref.read_bytes_at(u64::from(offset), 2)?[..].try_into().unwrap();
+        
+        let ifd_entry_count = match endianness {
+            Endianness::Little => u16::from_le_bytes(ifd_entry_count_bytes),
+            Endianness::Big => u16::from_be_bytes(ifd_entry_count_bytes),
+        };
+        
+        /* The spec requires at least one entry, so an empty IFD more
+         * likely means the offset doesn't point at an IFD at all. */
+        if ifd_entry_count == 0 && reader_ref.is_strict() {
+            return Err(TiffReadError::EmptyIfd {offset});
+        }
+        
+        /* Cannot overflow, since the entry count is at most 65535. */
+        let ifd_byte_range = u64::from(offset)..u64::from(offset) + offset_width.ifd_len(u64::from(ifd_entry_count));
+        let entries_start = ifd_byte_range.start + offset_width.entry_count_len();
+        let entry_len = offset_width.entry_len() as usize;
+        
+        /* A corrupt entry count would otherwise show up as a generic
+         * out-of-bounds read. */
+        if ifd_byte_range.end > reader_ref.stream_len() {
+            return Err(TiffReadError::IfdExtendsPastEof {offset, declared_entries: ifd_entry_count});
         }
         
+        /* Read remainder of the IFD now that we know how many bytes
+         * to read. */
+        let ifd_remaining_buffer = reader_ref.read_bytes_at(entries_start, (ifd_byte_range.end - entries_start) as usize)?;
+        
         let mut fields_map = BTreeMap::new();
         for i in 0..usize::from(ifd_entry_count) {
-            let ifd_entry_bytes: [u8; 12] = ifd_remaining_buffer[12*i..12*(i+1)].try_into().unwrap();
+            let ifd_entry_bytes: [u8; 12] = ifd_remaining_buffer[entry_len*i..entry_len*(i+1)].try_into().unwrap();
             
             let tag_bytes: [u8; 2] = ifd_entry_bytes[0..2].try_into().unwrap();
             let field_type_bytes: [u8; 2] = ifd_entry_bytes[2..4].try_into().unwrap();
@@ -209,16 +407,26 @@ impl<R: Read + Seek> Subfile<R> {
                 }
             }
             
+            /* Type 0 is never valid and more likely means the entry
+             * isn't where it should be than that it is an extension. */
+            if field_type_raw == 0 && reader_ref.is_strict() {
+                return Err(TiffReadError::InvalidFieldType {tag, raw: field_type_raw});
+            }
+            
             let field_state = FieldState::from_ifd_entry_data(field_type_raw, count, value_offset_bytes, endianness)?;
             let field = Field {
-                buf_reader_ref: buf_reader_ref.clone(),
-                endianness: endianness,
+                reader_ref: reader_ref.clone(),
+                endianness,
+                entry_offset: entries_start + (entry_len*i) as u64,
+                raw_entry: ifd_entry_bytes,
+                ifd_byte_range: ifd_byte_range.clone(),
                 state: field_state,
             };
             fields_map.insert(tag, field);
         }
         
-        let ifd_offset_bytes: [u8; 4] = ifd_remaining_buffer[ifd_remaining_buffer_size-4..].try_into().unwrap();
+        let next_offset_start = entry_len*usize::from(ifd_entry_count);
+        let ifd_offset_bytes: [u8; 4] = ifd_remaining_buffer[next_offset_start..].try_into().unwrap();
         let next_ifd_offset_raw = match endianness {
             Endianness::Little => u32::from_le_bytes(ifd_offset_bytes),
             Endianness::Big => u32::from_be_bytes(ifd_offset_bytes),
@@ -230,11 +438,17 @@ impl<R: Read + Seek> Subfile<R> {
             None
         };
         
+        trace_event!(offset, entry_count = ifd_entry_count, next_ifd_offset = next_ifd_offset_raw, "parsed IFD");
+        
+        let segment_cache = SegmentCache::with_capacity(reader_ref.segment_cache_capacity());
         Ok(Subfile {
-            buf_reader_ref: buf_reader_ref,
-            endianness: endianness,
+            reader_ref,
+            endianness,
+            offset,
+            entry_count: ifd_entry_count,
             fields: fields_map,
             offset_to_next_ifd: next_ifd_offset_opt,
+            segment_cache,
         })
     }
     
@@ -242,26 +456,768 @@ impl<R: Read + Seek> Subfile<R> {
         self.offset_to_next_ifd
     }
     
-    pub fn get_field(&self, tag: u16) -> Option<&Field<R>> {
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+    
+    /// Reads `len` raw bytes starting at `offset` in the stream this
+    /// subfile was read from.
+    pub fn read_bytes_at(&self, offset: u64, len: usize) -> Result<Vec<u8>, TiffReadError> {
+        self.reader_ref.read_bytes_at(offset, len)
+    }
+    
+    /// Returns the tags of all fields in this subfile.
+    pub fn tag_set(&self) -> BTreeSet<u16> {
+        self.fields.keys().cloned().collect()
+    }
+    
+    /// Iterates over all fields in tag order, including fields of
+    /// unknown type.
+    pub fn fields(&self) -> impl Iterator<Item = (u16, &Field<R, S>)> {
+        self.fields.iter().map(|(&tag, field)| (tag, field))
+    }
+    
+    pub fn contains(&self, tag: u16) -> bool {
+        self.fields.contains_key(&tag)
+    }
+    
+    /// Number of fields, including ones of unknown type.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+    
+    pub fn get_field(&self, tag: u16) -> Option<&Field<R, S>> {
         self.fields.get(&tag)
     }
     
-    pub fn get_field_mut(&mut self, tag: u16) -> Option<&mut Field<R>> {
+    /// Decodes the value of `tag` again as `as_type`, see
+    /// `Field::reinterpret`.
+    pub fn reinterpret_field(&mut self, tag: u16, as_type: FieldType) -> Result<(), TiffReadError> {
+        self.evict_segments();
+        match self.fields.get_mut(&tag) {
+            Some(field) => field.reinterpret(as_type).map_err(|err| TiffReadError::InField {tag, source: Box::new(err)}),
+            None => Err(TiffReadError::MissingRequiredTag {tag}),
+        }
+    }
+    
+    /// Returns the original 12-byte IFD entry for `tag`, see
+    /// `Field::raw_entry`.
+    pub fn raw_entry(&self, tag: u16) -> Option<[u8; 12]> {
+        self.fields.get(&tag).map(|field| field.raw_entry())
+    }
+    
+    /// Returns the field for `tag` for modification. Strips cached by
+    /// `read_strip` are kept, so call `evict_segments` after changing a
+    /// field they depend on.
+    pub fn get_field_mut(&mut self, tag: u16) -> Option<&mut Field<R, S>> {
         self.fields.get_mut(&tag)
     }
     
-    pub fn load_all_field_values(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let tags: Vec<_> = self.fields.keys().cloned().collect();
+    /// Loads the field with the given tag (if present) and returns its
+    /// value.
+    pub fn get_value(&mut self, tag: u16) -> Result<Option<&FieldValue>, TiffReadError> {
+        match self.fields.get_mut(&tag) {
+            Some(field) => field.get_value(),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads an ASCII field as a string, up to the first NUL byte. Other
+    /// field types result in `TiffReadError::TypeMismatch`.
+    pub fn get_ascii(&mut self, tag: u16) -> Result<Option<String>, TiffReadError> {
+        match self.get_value(tag)? {
+            Some(FieldValue::Ascii(text)) => Ok(Some(text.split('\0').next().unwrap_or_default().to_string())),
+            Some(value) => Err(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads an ASCII field as a C string, checking with
+    /// `FieldValue::as_cstr` that it is stored as the spec requires:
+    /// ending in a NUL byte, with no other NUL bytes before it. Fields
+    /// that break this result in a parse error, other field types in
+    /// `TiffReadError::TypeMismatch` and unknown ones in
+    /// `TiffReadError::UnknownFieldType`.
+    pub fn get_cstring(&mut self, tag: u16) -> Result<Option<CString>, TiffReadError> {
+        match self.fields.get(&tag) {
+            None => return Ok(None),
+            Some(field) => match field.field_type() {
+                Some(FieldType::Ascii) => {},
+                Some(found) => return Err(TiffReadError::TypeMismatch {tag, found}),
+                None => return Err(TiffReadError::UnknownFieldType {tag, raw: field.field_type_raw()}),
+            },
+        }
+        let endianness = self.endianness;
+        let value = self.get_value(tag)?.unwrap();
+        
+        match value.as_cstr() {
+            Some(text) => Ok(Some(text.to_owned())),
+            None => Err(ParseError::new(format!("ASCII value of tag {} is not a single NUL-terminated string: \"{}\"", tag, escaped_string_from_bytes(&bytes_from_value(value, endianness)))).into()),
+        }
+    }
+    
+    /// Loads every ASCII field, as with `get_ascii`, and returns them by
+    /// tag.
+    pub fn ascii_fields(&mut self) -> Result<BTreeMap<u16, String>, TiffReadError> {
+        let tags: Vec<u16> = self.fields()
+            .filter(|(_, field)| field.field_type() == Some(FieldType::Ascii))
+            .map(|(tag, _)| tag)
+            .collect();
+        
+        let mut texts = BTreeMap::new();
         for tag in tags {
-            self.get_field_mut(tag).unwrap().load()?;
+            if let Some(text) = self.get_ascii(tag)? {
+                texts.insert(tag, text);
+            }
+        }
+        Ok(texts)
+    }
+    
+    /// Loads a RATIONAL field as (numerator, denominator) pairs, exactly
+    /// as stored. Other field types result in
+    /// `TiffReadError::TypeMismatch`.
+    pub fn get_rationals(&mut self, tag: u16) -> Result<Option<Vec<(u32, u32)>>, TiffReadError> {
+        match self.get_value(tag)? {
+            Some(FieldValue::Rational(values)) => Ok(Some(values.iter().map(|value| (*value.numer(), *value.denom())).collect())),
+            Some(value) => Err(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads an SRATIONAL field as (numerator, denominator) pairs,
+    /// exactly as stored. Other field types result in
+    /// `TiffReadError::TypeMismatch`.
+    pub fn get_srationals(&mut self, tag: u16) -> Result<Option<Vec<(i32, i32)>>, TiffReadError> {
+        match self.get_value(tag)? {
+            Some(FieldValue::SRational(values)) => Ok(Some(values.iter().map(|value| (*value.numer(), *value.denom())).collect())),
+            Some(value) => Err(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads an unsigned integer field (BYTE, SHORT or LONG) as `u32`s.
+    pub(crate) fn get_u32_values(&mut self, tag: u16) -> Result<Option<Vec<u32>>, TiffReadError> {
+        match self.get_value(tag)? {
+            Some(value) => match value.to_u32_vec() {
+                Some(values) => Ok(Some(values)),
+                None => Err(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Loads an unsigned integer field and returns its first value.
+    pub(crate) fn get_u32(&mut self, tag: u16) -> Result<Option<u32>, TiffReadError> {
+        Ok(self.get_u32_values(tag)?.and_then(|values| values.first().cloned()))
+    }
+    
+    /// Reads the bytes of a field's value as stored in the file,
+    /// without decoding them. Returns `None` for fields of unknown type.
+    pub(crate) fn raw_value_bytes(&self, tag: u16) -> Result<Option<Vec<u8>>, TiffReadError> {
+        match self.fields.get(&tag) {
+            Some(field) if !field.is_unknown() => Ok(Some(field.raw_bytes()?)),
+            _ => Ok(None),
+        }
+    }
+    
+    /// Loads an ASCII field as a list of NUL-separated strings, ignoring
+    /// trailing NULs. Other field types result in
+    /// `TiffReadError::TypeMismatch`, and unknown ones in
+    /// `TiffReadError::UnknownFieldType`.
+    pub(crate) fn get_ascii_strings(&mut self, tag: u16) -> Result<Option<Vec<String>>, TiffReadError> {
+        match self.get_value(tag)? {
+            Some(FieldValue::Ascii(text)) => match text.trim_end_matches('\0') {
+                "" => Ok(Some(Vec::new())),
+                text => Ok(Some(text.split('\0').map(str::to_string).collect())),
+            },
+            Some(value) => Err(TiffReadError::TypeMismatch {tag, found: value.field_type()}),
+            None => match self.fields.get(&tag) {
+                Some(field) => Err(TiffReadError::UnknownFieldType {tag, raw: field.field_type_raw()}),
+                None => Ok(None),
+            },
+        }
+    }
+    
+    /// Loads a field holding IFD offsets, either as LONG (or SHORT) or
+    /// as the IFD type from TIFF Technical Note 1.
+    pub(crate) fn get_ifd_offsets(&mut self, tag: u16) -> Result<Option<Vec<u32>>, TiffReadError> {
+        let endianness = self.endianness;
+        let (count, value_offset_bytes) = match self.fields.get(&tag) {
+            Some(Field {state: Unknown {field_type_raw: IFD_FIELD_TYPE, count, value_offset_bytes}, ..}) => (*count, *value_offset_bytes),
+            _ => return self.get_u32_values(tag),
+        };
+        
+        let u32_from_bytes = |bytes: [u8; 4]| match endianness {
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        };
+        
+        if count == 0 {
+            Ok(Some(Vec::new()))
+        } else if count == 1 {
+            Ok(Some(vec![u32_from_bytes(value_offset_bytes)]))
+        } else {
+            let buffer_size = compute_value_buffer_size(FieldType::Long, count).ok_or(TiffReadError::BufferSizeOverflow {field_type: FieldType::Long, count})?;
+            let buffer = self.read_bytes_at(u64::from(u32_from_bytes(value_offset_bytes)), buffer_size)?;
+            Ok(Some(buffer.chunks_exact(4).map(|chunk| u32_from_bytes(chunk.try_into().unwrap())).collect()))
+        }
+    }
+    
+    /// Byte range occupied by the IFD itself: entry count, entries and
+    /// next IFD offset.
+    pub(crate) fn ifd_byte_range(&self) -> Range<u64> {
+        let start = u64::from(self.offset);
+        start..start + self.reader_ref.offset_width().ifd_len(u64::from(self.entry_count))
+    }
+    
+    /// Returns the IFD's start offset and the number of bytes it
+    /// occupies, as given by `OffsetWidth::ifd_len`.
+    pub fn ifd_byte_span(&self) -> (u64, usize) {
+        let range = self.ifd_byte_range();
+        (range.start, (range.end - range.start) as usize)
+    }
+    
+    pub(crate) fn reader_ref(&self) -> &ReaderRef<R, S> {
+        &self.reader_ref
+    }
+    
+    pub(crate) fn segment_cache_mut(&mut self) -> &mut SegmentCache {
+        &mut self.segment_cache
+    }
+    
+    /// Parses the IFD at `offset` using the same reader and byte order
+    /// as this subfile.
+    pub(crate) fn child_subfile(&self, offset: u32) -> Result<Subfile<R, S>, TiffReadError> {
+        Subfile::new(self.reader_ref.clone(), offset, self.endianness)
+    }
+    
+    /// Parses the IFDs pointed to by every registered pointer tag (see
+    /// `TiffReader::register_ifd_pointer`) present in this subfile.
+    /// Only the first offset of each pointer field is followed.
+    pub fn follow_registered_pointers(&mut self) -> Result<BTreeMap<u16, Subfile<R, S>>, TiffReadError> {
+        let mut children = BTreeMap::new();
+        for tag in self.reader_ref.ifd_pointer_tags() {
+            let offsets = match self.get_ifd_offsets(tag)? {
+                Some(offsets) => offsets,
+                None => continue,
+            };
+            if let Some(&offset) = offsets.first() {
+                children.insert(tag, self.child_subfile(offset)?);
+            }
+        }
+        
+        Ok(children)
+    }
+    
+    /// Parses the IFD pointed to by the field with the given tag, which
+    /// must hold a single offset of type LONG or of the IFD type from TIFF
+    /// Technical Note 1. Use this for pointer tags the crate doesn't know
+    /// about; unlike `follow_registered_pointers`, the tag doesn't need
+    /// to be registered.
+    pub fn follow_as_ifd(&mut self, tag: u16) -> Result<Subfile<R, S>, TiffReadError> {
+        match self.fields.get(&tag) {
+            None => return Err(TiffReadError::MissingRequiredTag {tag}),
+            Some(Field {state: Unknown {field_type_raw: IFD_FIELD_TYPE, ..}, ..}) => {},
+            Some(field) => match field.field_type() {
+                Some(FieldType::Long) => {},
+                Some(found) => return Err(TiffReadError::TypeMismatch {tag, found}),
+                None => return Err(TiffReadError::UnknownFieldType {tag, raw: field.field_type_raw()}),
+            },
+        }
+        
+        let offsets = self.get_ifd_offsets(tag)?.unwrap_or_default();
+        match offsets[..] {
+            [offset] => self.child_subfile(offset).map_err(|err| TiffReadError::InField {tag, source: Box::new(err)}),
+            _ => Err(ParseError::new(format!("Expected a single IFD offset in tag {}, found {}", tag, offsets.len())).into()),
+        }
+    }
+    
+    /// Captures the current state of every field, including any loaded
+    /// values, in a structure independent of the reader.
+    pub fn snapshot(&self) -> SubfileSnapshot {
+        SubfileSnapshot {
+            endianness: self.endianness,
+            field_states: self.fields.iter().map(|(&tag, field)| (tag, field.state.clone())).collect(),
+            offset_to_next_ifd: self.offset_to_next_ifd,
+        }
+    }
+    
+    /// Loads every field of known type and returns the values in a
+    /// structure independent of the reader, so the file can be closed
+    /// while the metadata is kept. This reads all out-of-line values,
+    /// however large. Fields of unknown type are left out.
+    pub fn into_owned(mut self) -> Result<OwnedSubfile, TiffReadError> {
+        Ok(OwnedSubfile {
+            endianness: self.endianness,
+            offset_to_next_ifd: self.offset_to_next_ifd,
+            values: self.to_value_map(true)?,
+        })
+    }
+    
+    /// Returns the (offset, length) byte range of every field value that
+    /// hasn't been loaded yet, in tag order. Reading these ranges is all
+    /// the I/O `load_all_field_values` would do.
+    pub fn pending_read_ranges(&self) -> Vec<(u64, usize)> {
+        self.fields.values()
+            .filter(|field| matches!(field.state, FieldState::NotLoaded {..}))
+            .filter_map(|field| match field.value_location()? {
+                ValueLocation::OutOfLine {offset, len} => Some((offset, len)),
+                ValueLocation::Inline {..} => None,
+            })
+            .collect()
+    }
+    
+    /// Collects the values of all fields of known type, loading them as
+    /// needed. With `include_out_of_line` false, only values stored in
+    /// the IFD itself are included, so no I/O is performed.
+    #[allow(clippy::wrong_self_convention)] // needs `&mut self` to load values
+    pub fn to_value_map(&mut self, include_out_of_line: bool) -> Result<BTreeMap<u16, FieldValue>, TiffReadError> {
+        let mut values = BTreeMap::new();
+        for (&tag, field) in self.fields.iter_mut() {
+            let value = if include_out_of_line {
+                field.get_value().map_err(|err| TiffReadError::InField {tag, source: Box::new(err)})?
+            } else {
+                field.get_value_if_local()
+            };
+            if let Some(value) = value {
+                values.insert(tag, value.clone());
+            }
+        }
+        Ok(values)
+    }
+    
+    pub fn load_all_field_values(&mut self) -> Result<(), TiffReadError> {
+        for field in self.fields.values_mut() {
+            field.load()?;
         }
         Ok(())
     }
     
+    /// Unloads all field values, see `Field::unload`. Strips cached by
+    /// `read_strip` are evicted as well.
     pub fn unload_all_field_values(&mut self) {
-        let tags: Vec<_> = self.fields.keys().cloned().collect();
-        for tag in tags {
-            self.get_field_mut(tag).unwrap().unload();
+        self.evict_segments();
+        for field in self.fields.values_mut() {
+            field.unload();
         }
     }
 }
+
+/// Read-only copy of a subfile's field states, created by
+/// `Subfile::snapshot`. Values that were not loaded at the time of the
+/// snapshot are not available from it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SubfileSnapshot {
+    endianness: Endianness,
+    field_states: BTreeMap<u16, FieldState>,
+    offset_to_next_ifd: Option<u32>,
+}
+
+impl SubfileSnapshot {
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+    
+    pub fn offset_to_next_ifd(&self) -> Option<u32> {
+        self.offset_to_next_ifd
+    }
+    
+    pub fn tag_set(&self) -> BTreeSet<u16> {
+        self.field_states.keys().cloned().collect()
+    }
+    
+    /// Returns the value of the field with the given tag if it was
+    /// inline or loaded when the snapshot was taken.
+    pub fn get_field_value(&self, tag: u16) -> Option<&FieldValue> {
+        match self.field_states.get(&tag) {
+            Some(FieldState::Local(value)) => Some(value),
+            Some(FieldState::Loaded {value, offset: _}) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// The fully loaded field values of a subfile, as returned by
+/// `Subfile::into_owned`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OwnedSubfile {
+    endianness: Endianness,
+    offset_to_next_ifd: Option<u32>,
+    values: BTreeMap<u16, FieldValue>,
+}
+
+impl OwnedSubfile {
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+    
+    pub fn offset_to_next_ifd(&self) -> Option<u32> {
+        self.offset_to_next_ifd
+    }
+    
+    pub fn get_value(&self, tag: u16) -> Option<&FieldValue> {
+        self.values.get(&tag)
+    }
+    
+    pub fn values(&self) -> &BTreeMap<u16, FieldValue> {
+        &self.values
+    }
+    
+    pub fn into_values(self) -> BTreeMap<u16, FieldValue> {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn inline_value(field_type_raw: u16, count: u32, value_offset_bytes: &[u8; 4], endianness: Endianness) -> FieldValue {
+        match FieldState::from_ifd_entry_data(field_type_raw, count, *value_offset_bytes, endianness).unwrap() {
+            Local(value) => value,
+            other => panic!("Expected inline value, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn inline_short_count_1_ignores_trailing_bytes() {
+        assert_eq!(inline_value(3, 1, b"\x34\x12\xFF\xFF", Endianness::Little), FieldValue::Short(vec![0x1234]));
+        assert_eq!(inline_value(3, 1, b"\x12\x34\xFF\xFF", Endianness::Big), FieldValue::Short(vec![0x1234]));
+    }
+    
+    #[test]
+    fn inline_sshort_count_1_ignores_trailing_bytes() {
+        assert_eq!(inline_value(8, 1, b"\xFE\xFF\x7F\x7F", Endianness::Little), FieldValue::SShort(vec![-2]));
+        assert_eq!(inline_value(8, 1, b"\xFF\xFE\x7F\x7F", Endianness::Big), FieldValue::SShort(vec![-2]));
+    }
+    
+    #[test]
+    fn inline_short_count_2() {
+        assert_eq!(inline_value(3, 2, b"\x34\x12\x78\x56", Endianness::Little), FieldValue::Short(vec![0x1234, 0x5678]));
+        assert_eq!(inline_value(3, 2, b"\x12\x34\x56\x78", Endianness::Big), FieldValue::Short(vec![0x1234, 0x5678]));
+    }
+    
+    #[test]
+    fn inline_byte_count_3_and_4() {
+        for &endianness in &[Endianness::Little, Endianness::Big] {
+            assert_eq!(inline_value(1, 3, b"\x01\x02\x03\xFF", endianness), FieldValue::Byte(vec![1, 2, 3]));
+            assert_eq!(inline_value(1, 4, b"\x01\x02\x03\x04", endianness), FieldValue::Byte(vec![1, 2, 3, 4]));
+        }
+    }
+    
+    #[test]
+    fn unknown_field_type_is_kept() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD: number of entries (2)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageWidth, SHORT, 16
+            b"\x39\x05\x63\x00\x02\x00\x00\x00\xDE\xAD\xBE\xEF".as_ref(), // tag 1337, type 99, count 2
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        assert_eq!(subfile.fields().map(|(tag, _)| tag).collect::<Vec<_>>(), vec![256, 1337]);
+        assert_eq!(subfile.len(), 2);
+        assert!(subfile.contains(1337));
+        assert!(!subfile.contains(257));
+        
+        let field = subfile.get_field_mut(1337).unwrap();
+        assert!(field.is_unknown());
+        assert_eq!(field.field_type(), None);
+        assert_eq!(field.field_type_raw(), 99);
+        assert_eq!(field.count(), 2);
+        assert_eq!(field.unknown_value_offset_bytes(), Some(*b"\xDE\xAD\xBE\xEF"));
+        assert_eq!(field.get_value().unwrap(), None);
+        
+        let field = subfile.get_field(256).unwrap();
+        assert!(!field.is_unknown());
+        assert_eq!(field.field_type_raw(), 3);
+        assert_eq!(field.unknown_value_offset_bytes(), None);
+    }
+    
+    #[test]
+    fn value_locations() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD: number of entries (2)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageWidth, SHORT, 16
+            b"\x1A\x01\x05\x00\x01\x00\x00\x00\x26\x00\x00\x00".as_ref(), // XResolution, RATIONAL, offset 38
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+            b"\x2C\x01\x00\x00\x01\x00\x00\x00".as_ref(), // 300/1
+        ].concat();
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        let width_field = subfile.get_field(256).unwrap();
+        assert_eq!(width_field.entry_offset(), 10);
+        assert_eq!(width_field.value_location(), Some(ValueLocation::Inline {byte_range: 18..20}));
+        
+        let resolution_field = subfile.get_field_mut(282).unwrap();
+        assert_eq!(resolution_field.entry_offset(), 22);
+        assert_eq!(resolution_field.value_location(), Some(ValueLocation::OutOfLine {offset: 38, len: 8}));
+        resolution_field.load().unwrap();
+        assert_eq!(resolution_field.value_location(), Some(ValueLocation::OutOfLine {offset: 38, len: 8}));
+        assert_eq!(resolution_field.raw_bytes().unwrap(), b"\x2C\x01\x00\x00\x01\x00\x00\x00".to_vec());
+        assert_eq!(subfile.get_field(256).unwrap().raw_bytes().unwrap(), b"\x10\x00".to_vec());
+        assert_eq!(subfile.raw_entry(282), Some(*b"\x1A\x01\x05\x00\x01\x00\x00\x00\x26\x00\x00\x00"));
+        assert_eq!(subfile.raw_entry(283), None);
+    }
+    
+    #[test]
+    fn value_location_of_ascii_is_unaffected_by_loading() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .raw_entry(305, 2, 8, b"Writer\0\0".to_vec())
+            .raw_entry(315, 2, 4, b"ab\0\0".to_vec())
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        for &tag in &[305, 315] {
+            let before = subfile.get_field(tag).unwrap().value_location();
+            subfile.get_value(tag).unwrap();
+            assert_eq!(subfile.get_field(tag).unwrap().value_location(), before);
+        }
+        match subfile.get_field(305).unwrap().value_location() {
+            Some(ValueLocation::OutOfLine {len: 8, ..}) => {},
+            other => panic!("Expected 8 bytes out of line, got {:?}", other),
+        }
+        match subfile.get_field(315).unwrap().value_location() {
+            Some(ValueLocation::Inline {byte_range}) => assert_eq!(byte_range.end - byte_range.start, 4),
+            other => panic!("Expected 4 bytes inline, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn snapshot_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SubfileSnapshot>();
+    }
+    
+    #[test]
+    fn detach_owned_subfile() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Big)
+            .entry(256, FieldValue::Short(vec![16]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .raw_entry(65000, 99, 1, vec![0; 4])
+            .ifd()
+            .reader();
+        let owned = tiff_reader.subfiles.remove(0).into_owned().unwrap();
+        drop(tiff_reader);
+        
+        assert_eq!(owned.endianness(), Endianness::Big);
+        assert!(owned.offset_to_next_ifd().is_some());
+        assert_eq!(owned.get_value(305), Some(&FieldValue::Ascii("lazytiff\0".to_string())));
+        assert_eq!(owned.values().keys().cloned().collect::<Vec<_>>(), vec![256, 305]);
+    }
+    
+    #[test]
+    fn ascii_fields_as_c_strings() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![16]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .raw_entry(306, 2, 4, b"abcd".to_vec())
+            .entry(333, FieldValue::Ascii("Cyan\0Black".to_string()))
+            .raw_entry(337, 2, 4, b"ab\0\0".to_vec())
+            .raw_entry(338, 2, 3, b"ab\0\0".to_vec())
+            .raw_entry(339, 99, 3, b"ab\0\0".to_vec())
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        assert_eq!(subfile.get_cstring(305).unwrap(), Some(CString::new("lazytiff").unwrap()));
+        assert_eq!(subfile.get_cstring(315).unwrap(), None);
+        assert!(subfile.get_cstring(256).is_err());
+        /* Missing terminator, interior NUL and a second NUL within the
+         * count are all rejected. Padding past the count is not part of
+         * the value. */
+        assert!(subfile.get_cstring(306).is_err());
+        assert!(subfile.get_cstring(333).is_err());
+        assert!(subfile.get_cstring(337).is_err());
+        assert_eq!(subfile.get_cstring(338).unwrap(), Some(CString::new("ab").unwrap()));
+        match subfile.get_cstring(339) {
+            Err(TiffReadError::UnknownFieldType {tag: 339, raw: 99}) => {},
+            other => panic!("Expected UnknownFieldType, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn short_count_3_is_not_inline() {
+        let state = FieldState::from_ifd_entry_data(3, 3, *b"\x10\x00\x00\x00", Endianness::Little).unwrap();
+        assert_eq!(state, NotLoaded {field_type: FieldType::Short, count: 3, offset: 16});
+    }
+    
+    #[test]
+    fn list_pending_read_ranges() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Big)
+            .entry(256, FieldValue::Short(vec![16]))
+            .entry(282, FieldValue::Rational(vec![crate::Rational::new_raw(300, 1)]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        /* 3 entries put the value area at 8 + 2 + 36 + 4 = 50. */
+        assert_eq!(subfile.ifd_byte_span(), (8, 42));
+        assert_eq!(subfile.pending_read_ranges(), vec![(50, 8), (58, 9)]);
+        subfile.get_value(282).unwrap();
+        assert_eq!(subfile.pending_read_ranges(), vec![(58, 9)]);
+        
+        assert_eq!(subfile.to_value_map(false).unwrap().keys().cloned().collect::<Vec<_>>(), vec![256]);
+        assert_eq!(subfile.get_ascii(305).unwrap(), Some("lazytiff".to_string()));
+        assert_eq!(subfile.get_rationals(282).unwrap(), Some(vec![(300, 1)]));
+        assert_eq!(subfile.ascii_fields().unwrap().into_iter().collect::<Vec<_>>(), vec![(305, "lazytiff".to_string())]);
+        assert_eq!(subfile.get_rationals(283).unwrap(), None);
+        assert!(subfile.get_srationals(282).is_err());
+        assert_eq!(subfile.get_ascii(315).unwrap(), None);
+        assert!(subfile.get_ascii(256).is_err());
+        let values = subfile.to_value_map(true).unwrap();
+        assert_eq!(values.get(&305), Some(&FieldValue::Ascii("lazytiff\0".to_string())));
+        assert_eq!(values.len(), 3);
+    }
+    
+    #[test]
+    fn reinterpret_mistyped_fields() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(273, FieldValue::Byte(vec![0x10, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00]))
+            .raw_entry(279, 3, 2, b"\x05\x00\x06\x00".to_vec())
+            .raw_entry(65000, 99, 2, b"\x01\x02\x03\x04".to_vec())
+            .entry(305, FieldValue::Byte(vec![1, 2, 3]))
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        subfile.reinterpret_field(273, FieldType::Long).unwrap();
+        assert_eq!(subfile.get_value(273).unwrap(), Some(&FieldValue::Long(vec![0x10, 0x20])));
+        assert_eq!(subfile.get_field(273).unwrap().field_type_raw(), 1);
+        assert_eq!(subfile.get_field(273).unwrap().raw_bytes().unwrap().len(), 8);
+        
+        subfile.reinterpret_field(279, FieldType::Long).unwrap();
+        assert_eq!(subfile.get_value(279).unwrap(), Some(&FieldValue::Long(vec![0x0006_0005])));
+        
+        subfile.reinterpret_field(65000, FieldType::Short).unwrap();
+        assert_eq!(subfile.get_value(65000).unwrap(), Some(&FieldValue::Short(vec![0x0201, 0x0403])));
+        
+        assert!(subfile.reinterpret_field(305, FieldType::Short).is_err());
+        assert_eq!(subfile.get_value(305).unwrap(), Some(&FieldValue::Byte(vec![1, 2, 3])));
+        assert!(subfile.reinterpret_field(306, FieldType::Short).is_err());
+    }
+    
+    #[test]
+    fn borrowed_value_views() {
+        let mut tiff_reader = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![16]))
+            .entry(320, FieldValue::Short(vec![0, 1, 2, 3, 4, 5]))
+            .reader();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        assert_eq!(subfile.get_field(256).unwrap().value_ref(), Some(FieldValueRef::Short(&[16])));
+        assert_eq!(subfile.get_field(320).unwrap().value_ref(), None);
+        
+        subfile.get_value(320).unwrap();
+        let value_ref = subfile.get_field(320).unwrap().value_ref().unwrap();
+        assert_eq!(value_ref, FieldValueRef::Short(&[0, 1, 2, 3, 4, 5]));
+        assert_eq!((value_ref.field_type(), value_ref.count()), (FieldType::Short, 6));
+        assert_eq!(value_ref.to_owned_value(), FieldValue::Short(vec![0, 1, 2, 3, 4, 5]));
+    }
+    
+    #[test]
+    fn strict_mode_rejects_value_inside_ifd() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x01\x00".as_ref(), // IFD: number of entries (1)
+            b"\x31\x01\x02\x00\x08\x00\x00\x00\x0A\x00\x00\x00".as_ref(), // Software, ASCII, offset 10 (inside the IFD)
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        
+        tiff_reader.set_strict(true);
+        match tiff_reader.subfiles[0].get_value(305) {
+            Err(TiffReadError::ValueOverlapsStructure {offset: 10, len: 8}) => {},
+            other => panic!("Expected ValueOverlapsStructure, got {:?}", other),
+        }
+        
+        tiff_reader.set_strict(false);
+        assert!(tiff_reader.subfiles[0].get_value(305).unwrap().is_some());
+    }
+    
+    #[test]
+    fn strict_mode_rejects_empty_ifd() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x00\x00".as_ref(), // IFD: number of entries (0)
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes.clone())).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        assert!(tiff_reader.subfiles[0].is_empty());
+        
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.set_strict(true);
+        match tiff_reader.read_all_ifds() {
+            Err(TiffReadError::EmptyIfd {offset: 8}) => {},
+            other => panic!("Expected EmptyIfd, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn entry_count_past_end_of_stream() {
+        let tiff_bytes = [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\xFF\xFF".as_ref(), // IFD: number of entries (65535, corrupt)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // IFD entry 0: ImageWidth (256), SHORT, count 1, value 16
+            b"\x00\x00\x00\x00".as_ref(), // IFD: offset to next IFD (0 = N/A)
+        ].concat();
+        
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        match tiff_reader.read_all_ifds() {
+            Err(TiffReadError::IfdExtendsPastEof {offset: 8, declared_entries: 65535}) => {},
+            other => panic!("Expected IfdExtendsPastEof, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn strict_mode_rejects_field_type_0() {
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![16]))
+            .raw_entry(257, 0, 1, vec![0x10, 0x00, 0x00, 0x00])
+            .build();
+        
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes.clone())).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        assert!(tiff_reader.subfiles[0].get_field(257).unwrap().is_unknown());
+        
+        let mut tiff_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes)).unwrap();
+        tiff_reader.set_strict(true);
+        match tiff_reader.read_all_ifds() {
+            Err(TiffReadError::InvalidFieldType {tag: 257, raw: 0}) => {},
+            other => panic!("Expected InvalidFieldType, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn fields_as_trait_objects() {
+        let tiff_bytes = crate::test_util::TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![16]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .build();
+        let mut owned_reader = crate::TiffReader::new(std::io::Cursor::new(tiff_bytes.clone())).unwrap();
+        owned_reader.read_all_ifds().unwrap();
+        let mut borrowed_reader = crate::TiffReader::new(std::io::Cursor::new(&tiff_bytes[..])).unwrap();
+        borrowed_reader.read_all_ifds().unwrap();
+        
+        let fields: Vec<&dyn FieldInfo> = vec![
+            owned_reader.subfiles[0].get_field(256).unwrap(),
+            borrowed_reader.subfiles[0].get_field(305).unwrap(),
+        ];
+        assert_eq!(fields[0].get_value_if_local(), Some(&FieldValue::Short(vec![16])));
+        assert!(fields[0].is_loaded());
+        assert_eq!(fields[1].field_type(), Some(FieldType::Ascii));
+        assert_eq!(fields[1].count(), 9);
+        assert!(!fields[1].is_loaded());
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{Read, Seek};
+
+use crate::baseline::ImageKind;
+use crate::compression::Compression;
+use crate::error::TiffReadError;
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::{tags, TiffReader};
+
+/// The basic properties of a subfile, as returned by
+/// `TiffReader::summaries`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SubfileSummary {
+    pub width: u32,
+    pub height: u32,
+    pub compression: Compression,
+    /// PhotometricInterpretation, which has no default.
+    pub photometric_interpretation: Option<u32>,
+    pub samples_per_pixel: u16,
+    pub bits_per_sample: Vec<u16>,
+    pub image_kind: ImageKind,
+}
+
+impl SubfileSummary {
+    fn from_subfile<R: Read + Seek, S: Sharing>(subfile: &mut Subfile<R, S>) -> Result<Self, TiffReadError> {
+        Ok(SubfileSummary {
+            width: subfile.image_width()?,
+            height: subfile.image_length()?,
+            compression: subfile.compression()?,
+            photometric_interpretation: subfile.get_u32(tags::PHOTOMETRIC_INTERPRETATION)?,
+            samples_per_pixel: subfile.samples_per_pixel()?,
+            bits_per_sample: subfile.bits_per_sample()?,
+            image_kind: subfile.image_kind()?,
+        })
+    }
+}
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Returns a summary of every subfile in `subfiles`, reading all IFDs
+    /// first if that hasn't been done yet. Only the fields needed for the
+    /// summaries are loaded. Errors are wrapped in
+    /// `TiffReadError::InSubfile` with the index of the subfile.
+    pub fn summaries(&mut self) -> Result<Vec<SubfileSummary>, TiffReadError> {
+        if self.subfiles.is_empty() {
+            self.read_all_ifds()?;
+        }
+        
+        self.subfiles.iter_mut().enumerate()
+            .map(|(index, subfile)| SubfileSummary::from_subfile(subfile).map_err(|err| TiffReadError::InSubfile {index, source: Box::new(err)}))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubfileSummary;
+    use crate::baseline::ImageKind;
+    use crate::compression::Compression;
+    use crate::error::TiffReadError;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn summarize_subfiles() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![640]))
+            .entry(257, FieldValue::Short(vec![480]))
+            .entry(258, FieldValue::Short(vec![8, 8, 8]))
+            .entry(259, FieldValue::Short(vec![32773]))
+            .entry(262, FieldValue::Short(vec![2]))
+            .entry(277, FieldValue::Short(vec![3]))
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .ifd()
+            .entry(256, FieldValue::Short(vec![64]))
+            .reader();
+        
+        match tiff_reader.summaries() {
+            Err(TiffReadError::InSubfile {index: 1, source}) => match *source {
+                TiffReadError::MissingRequiredTag {tag: 257} => {},
+                other => panic!("Expected MissingRequiredTag, got {:?}", other),
+            },
+            other => panic!("Expected InSubfile, got {:?}", other),
+        }
+        
+        tiff_reader.subfiles.truncate(1);
+        assert_eq!(tiff_reader.summaries().unwrap(), vec![SubfileSummary {
+            width: 640,
+            height: 480,
+            compression: Compression::PackBits,
+            photometric_interpretation: Some(2),
+            samples_per_pixel: 3,
+            bits_per_sample: vec![8, 8, 8],
+            image_kind: ImageKind::Rgb,
+        }]);
+        assert!(!tiff_reader.subfiles[0].get_field(305).unwrap().is_loaded());
+    }
+}
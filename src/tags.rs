@@ -0,0 +1,125 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Seek};
+
+use crate::subfile::Subfile;
+
+// Tag numbers for all fields listed in the TIFF 6.0 spec
+
+pub const NEW_SUBFILE_TYPE: u16 = 254;
+pub const SUBFILE_TYPE: u16 = 255;
+pub const IMAGE_WIDTH: u16 = 256;
+pub const IMAGE_LENGTH: u16 = 257;
+pub const BITS_PER_SAMPLE: u16 = 258;
+pub const COMPRESSION: u16 = 259;
+pub const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+pub const THRESHHOLDING: u16 = 263;
+pub const CELL_WIDTH: u16 = 264;
+pub const CELL_LENGTH: u16 = 265;
+pub const FILL_ORDER: u16 = 266;
+pub const DOCUMENT_NAME: u16 = 269;
+pub const IMAGE_DESCRIPTION: u16 = 270;
+pub const MAKE: u16 = 271;
+pub const MODEL: u16 = 272;
+pub const STRIP_OFFSETS: u16 = 273;
+pub const ORIENTATION: u16 = 274;
+pub const SAMPLES_PER_PIXEL: u16 = 277;
+pub const ROWS_PER_STRIP: u16 = 278;
+pub const STRIP_BYTE_COUNTS: u16 = 279;
+pub const MIN_SAMPLE_VALUE: u16 = 280;
+pub const MAX_SAMPLE_VALUE: u16 = 281;
+pub const X_RESOLUTION: u16 = 282;
+pub const Y_RESOLUTION: u16 = 283;
+pub const PLANAR_CONFIGURATION: u16 = 284;
+pub const PAGE_NAME: u16 = 285;
+pub const X_POSITION: u16 = 286;
+pub const Y_POSITION: u16 = 287;
+pub const FREE_OFFSETS: u16 = 288;
+pub const FREE_BYTE_COUNTS: u16 = 289;
+pub const GRAY_RESPONSE_UNIT: u16 = 290;
+pub const GRAY_RESPONSE_CURVE: u16 = 291;
+pub const T4_OPTIONS: u16 = 292;
+pub const T6_OPTIONS: u16 = 293;
+pub const RESOLUTION_UNIT: u16 = 296;
+pub const PAGE_NUMBER: u16 = 297;
+pub const TRANSFER_FUNCTION: u16 = 301;
+pub const SOFTWARE: u16 = 305;
+pub const DATE_TIME: u16 = 306;
+pub const ARTIST: u16 = 315;
+pub const HOST_COMPUTER: u16 = 316;
+pub const PREDICTOR: u16 = 317;
+pub const WHITE_POINT: u16 = 318;
+pub const PRIMARY_CHROMATICITIES: u16 = 319;
+pub const COLOR_MAP: u16 = 320;
+pub const HALFTONE_HINTS: u16 = 321;
+pub const TILE_WIDTH: u16 = 322;
+pub const TILE_LENGTH: u16 = 323;
+pub const TILE_OFFSETS: u16 = 324;
+pub const TILE_BYTE_COUNTS: u16 = 325;
+pub const INK_SET: u16 = 332;
+pub const INK_NAMES: u16 = 333;
+pub const NUMBER_OF_INKS: u16 = 334;
+pub const DOT_RANGE: u16 = 336;
+pub const TARGET_PRINTER: u16 = 337;
+pub const EXTRA_SAMPLES: u16 = 338;
+pub const SAMPLE_FORMAT: u16 = 339;
+pub const S_MIN_SAMPLE_VALUE: u16 = 340;
+pub const S_MAX_SAMPLE_VALUE: u16 = 341;
+pub const TRANSFER_RANGE: u16 = 342;
+pub const JPEG_PROC: u16 = 512;
+pub const JPEG_INTERCHANGE_FORMAT: u16 = 513;
+pub const JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 514;
+pub const JPEG_RESTART_INTERVAL: u16 = 515;
+pub const JPEG_LOSSLESS_PREDICTORS: u16 = 517;
+pub const JPEG_POINT_TRANSFORMS: u16 = 518;
+pub const JPEG_Q_TABLES: u16 = 519;
+pub const JPEG_DC_TABLES: u16 = 520;
+pub const JPEG_AC_TABLES: u16 = 521;
+pub const YCBCR_COEFFICIENTS: u16 = 529;
+pub const YCBCR_SUBSAMPLING: u16 = 530;
+pub const YCBCR_POSITIONING: u16 = 531;
+pub const REFERENCE_BLACK_WHITE: u16 = 532;
+pub const COPYRIGHT: u16 = 33432;
+
+// Child IFDs, defined in TIFF Technical Note 1
+pub const SUB_IFDS: u16 = 330;
+
+// Private tags registered by Adobe
+pub const PHOTOSHOP: u16 = 34377;
+pub const IMAGE_SOURCE_DATA: u16 = 37724;
+
+// Pointers to private IFDs defined by the Exif spec
+pub const EXIF_IFD: u16 = 34665;
+pub const GPS_IFD: u16 = 34853;
+
+/// Differences between the sets of fields in two subfiles.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TagDiff {
+    pub only_in_a: BTreeSet<u16>,
+    pub only_in_b: BTreeSet<u16>,
+    /// Tags present in both subfiles but with a different field type
+    /// or value count.
+    pub differing: BTreeSet<u16>,
+}
+
+/// Compares the fields of two subfiles by tag, field type and count, as
+/// stored in their IFD entries. Field values are not compared, so no I/O
+/// is performed, and loading values doesn't affect the result.
+pub fn diff<A: Read + Seek, B: Read + Seek>(a: &Subfile<A>, b: &Subfile<B>) -> TagDiff {
+    let tags_a = a.tag_set();
+    let tags_b = b.tag_set();
+    
+    let differing = tags_a.intersection(&tags_b)
+        .filter(|&&tag| {
+            let (field_type_a, count_a, _) = a.get_field(tag).unwrap().raw_entry_parts();
+            let (field_type_b, count_b, _) = b.get_field(tag).unwrap().raw_entry_parts();
+            field_type_a != field_type_b || count_a != count_b
+        })
+        .cloned()
+        .collect();
+    
+    TagDiff {
+        only_in_a: tags_a.difference(&tags_b).cloned().collect(),
+        only_in_b: tags_b.difference(&tags_a).cloned().collect(),
+        differing,
+    }
+}
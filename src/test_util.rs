@@ -0,0 +1,117 @@
+use std::io::Cursor;
+
+use crate::types::{bytes_from_value, Endianness, FieldValue};
+use crate::writer::{write_header, write_ifd, EntryData, IfdEntry, IfdLayout};
+use crate::TiffReader;
+
+/// Builds TIFF files in memory for tests. Each IFD is written right
+/// after the previous one's data, followed by its out-of-line values and
+/// then its strips or tiles. Values that fit in 4 bytes are stored in
+/// the IFD entry.
+pub struct TiffBuilder {
+    endianness: Endianness,
+    ifds: Vec<IfdLayout>,
+}
+
+impl TiffBuilder {
+    /// Creates a builder with a single, empty IFD.
+    pub fn new(endianness: Endianness) -> Self {
+        TiffBuilder {
+            endianness,
+            ifds: vec![IfdLayout::default()],
+        }
+    }
+    
+    /// Starts a new IFD, chained after the previous one.
+    pub fn ifd(mut self) -> Self {
+        self.ifds.push(IfdLayout::default());
+        self
+    }
+    
+    /// Adds an entry to the current IFD.
+    pub fn entry(self, tag: u16, value: FieldValue) -> Self {
+        let field_type_raw = value.field_type().to_u16();
+        let data = bytes_from_value(&value, self.endianness);
+        let count = match &value {
+            FieldValue::Ascii(_) => data.len() as u32,
+            _ => value.count() as u32,
+        };
+        self.raw_entry(tag, field_type_raw, count, data)
+    }
+    
+    /// Adds an entry with arbitrary type code and count, whose value is
+    /// given as raw bytes.
+    pub fn raw_entry(mut self, tag: u16, field_type_raw: u16, count: u32, data: Vec<u8>) -> Self {
+        self.ifds.last_mut().unwrap().entries.push(IfdEntry {tag, field_type_raw, count, data: EntryData::Bytes(data)});
+        self
+    }
+    
+    /// Adds the given image data segments to the current IFD, along with
+    /// LONG entries for their offsets and byte counts under the given
+    /// tags (StripOffsets/StripByteCounts or TileOffsets/TileByteCounts).
+    pub fn segments(mut self, offsets_tag: u16, byte_counts_tag: u16, segments: Vec<Vec<u8>>) -> Self {
+        let count = segments.len() as u32;
+        let byte_counts: Vec<u32> = segments.iter().map(|segment| segment.len() as u32).collect();
+        self = self.entry(byte_counts_tag, FieldValue::Long(byte_counts));
+        
+        let ifd = self.ifds.last_mut().unwrap();
+        ifd.entries.push(IfdEntry {tag: offsets_tag, field_type_raw: 4, count, data: EntryData::SegmentOffsets});
+        ifd.segments = segments;
+        self
+    }
+    
+    pub fn strips(self, strips: Vec<Vec<u8>>) -> Self {
+        self.segments(crate::tags::STRIP_OFFSETS, crate::tags::STRIP_BYTE_COUNTS, strips)
+    }
+    
+    pub fn build(self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_header(&mut out, self.endianness).unwrap();
+        
+        let mut position = 8;
+        let ifd_count = self.ifds.len();
+        for (index, ifd) in self.ifds.into_iter().enumerate() {
+            position = write_ifd(&mut out, self.endianness, ifd, position, index + 1 == ifd_count).unwrap();
+        }
+        
+        out
+    }
+    
+    /// Builds the file and opens it with a `TiffReader`, with all IFDs
+    /// read.
+    pub fn reader(self) -> TiffReader<Cursor<Vec<u8>>> {
+        let mut tiff_reader = TiffReader::new(Cursor::new(self.build())).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        tiff_reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TiffBuilder;
+    use crate::types::{Endianness, FieldValue, Rational};
+    
+    #[test]
+    fn builder_places_values_inline_and_out_of_line() {
+        for &endianness in &[Endianness::Little, Endianness::Big] {
+            let mut tiff_reader = TiffBuilder::new(endianness)
+                .entry(256, FieldValue::Short(vec![640]))
+                .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+                .strips(vec![vec![1, 2, 3], vec![4, 5, 6]])
+                .ifd()
+                .entry(282, FieldValue::Rational(vec![Rational::new_raw(300, 1)]))
+                .reader();
+            
+            assert_eq!(tiff_reader.subfiles.len(), 2);
+            let subfile = &mut tiff_reader.subfiles[0];
+            assert_eq!(subfile.get_field(256).unwrap().get_value_if_local(), Some(&FieldValue::Short(vec![640])));
+            assert_eq!(subfile.get_value(305).unwrap(), Some(&FieldValue::Ascii("lazytiff\0".to_string())));
+            let strip_offsets = subfile.get_u32_values(273).unwrap().unwrap();
+            assert_eq!(subfile.read_bytes_at(u64::from(strip_offsets[1]), 3).unwrap(), vec![4, 5, 6]);
+            assert_eq!(
+                tiff_reader.subfiles[1].get_value(282).unwrap(),
+                Some(&FieldValue::Rational(vec![Rational::new_raw(300, 1)]))
+            );
+        }
+    }
+}
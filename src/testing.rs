@@ -0,0 +1,127 @@
+//! Instrumentation for checking how much I/O reading a file performs.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default)]
+struct Counts {
+    reads: AtomicU64,
+    seeks: AtomicU64,
+    bytes_read: AtomicU64,
+}
+
+/// A handle to the counts of a `CountingReader`, which stays usable after
+/// the reader itself has been moved into a `TiffReader`.
+///
+/// Note that `TiffReader` buffers its input, so the counts reflect reads
+/// that reach the underlying stream rather than individual field loads.
+#[derive(Debug, Clone)]
+pub struct IoCounter {
+    counts: Arc<Counts>,
+}
+
+impl IoCounter {
+    /// Number of `read` calls made so far.
+    pub fn reads(&self) -> u64 {
+        self.counts.reads.load(Ordering::Relaxed)
+    }
+    
+    /// Number of `seek` calls made so far.
+    pub fn seeks(&self) -> u64 {
+        self.counts.seeks.load(Ordering::Relaxed)
+    }
+    
+    /// Total number of bytes returned by `read` calls so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.counts.bytes_read.load(Ordering::Relaxed)
+    }
+    
+    /// Sets all counts back to zero.
+    pub fn reset(&self) {
+        self.counts.reads.store(0, Ordering::Relaxed);
+        self.counts.seeks.store(0, Ordering::Relaxed);
+        self.counts.bytes_read.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A `Read + Seek` wrapper that counts the calls made to it and the
+/// number of bytes read.
+#[derive(Debug)]
+pub struct CountingReader<R> {
+    inner: R,
+    counts: Arc<Counts>,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        CountingReader {
+            inner,
+            counts: Arc::new(Counts::default()),
+        }
+    }
+    
+    /// Returns a handle to this reader's counts.
+    pub fn counter(&self) -> IoCounter {
+        IoCounter {
+            counts: Arc::clone(&self.counts),
+        }
+    }
+    
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.counts.reads.fetch_add(1, Ordering::Relaxed);
+        self.counts.bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.counts.seeks.fetch_add(1, Ordering::Relaxed);
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use super::CountingReader;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    use crate::TiffReader;
+    
+    #[test]
+    fn loaded_values_are_not_read_again() {
+        let bytes = TiffBuilder::new(Endianness::Little)
+            .entry(305, FieldValue::Ascii("lazytiff".to_string()))
+            .build();
+        let reader = CountingReader::new(Cursor::new(bytes));
+        let counter = reader.counter();
+        /* A tiny buffer makes every uncached access reach the stream. */
+        let options = crate::TiffReaderOptions::default().buffer_capacity(1);
+        let mut tiff_reader = TiffReader::with_options(reader, options).unwrap();
+        tiff_reader.read_all_ifds().unwrap();
+        let subfile = &mut tiff_reader.subfiles[0];
+        
+        counter.reset();
+        assert_eq!(subfile.get_value(305).unwrap(), Some(&FieldValue::Ascii("lazytiff\0".to_string())));
+        assert!(counter.reads() > 0);
+        
+        counter.reset();
+        subfile.get_value(305).unwrap();
+        assert_eq!(counter.reads(), 0);
+        assert_eq!(counter.seeks(), 0);
+        
+        subfile.get_field_mut(305).unwrap().unload();
+        subfile.get_value(305).unwrap();
+        assert!(counter.bytes_read() > 0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Seek};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::{ReaderRef, Sharing, Threaded};
+use crate::subfile::Subfile;
+use crate::types::Endianness;
+use crate::{tags, TiffReader};
+
+/* Limit on how deeply IFD pointers may nest, so that a crafted file
+ * can't exhaust the stack while the tree is read or dropped. Real files
+ * rarely nest more than two or three levels. */
+const MAX_TREE_DEPTH: usize = 64;
+
+/// How an IFD chain in an `IfdNode` tree was reached from its parent.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PointerKind {
+    /// One of the parent's SubIFDs (330).
+    SubIfd,
+    /// The parent's ExifIFD (34665).
+    Exif,
+    /// The parent's GPSIFD (34853).
+    Gps,
+    /// Another tag registered with `TiffReader::register_ifd_pointer`.
+    Other(u16),
+}
+
+impl PointerKind {
+    fn from_tag(tag: u16) -> Self {
+        match tag {
+            tags::SUB_IFDS => PointerKind::SubIfd,
+            tags::EXIF_IFD => PointerKind::Exif,
+            tags::GPS_IFD => PointerKind::Gps,
+            other => PointerKind::Other(other),
+        }
+    }
+}
+
+/// An IFD together with every IFD reachable from it through IFD
+/// pointers, created by `TiffReader::read_ifd_tree`. Each pointer leads
+/// to an IFD chain, whose members are stored side by side in chain
+/// order.
+#[derive(Debug)]
+pub struct IfdNode<R, S: Sharing = Threaded> {
+    pub subfile: Subfile<R, S>,
+    pub children: Vec<(PointerKind, Vec<IfdNode<R, S>>)>,
+}
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Parses every IFD in the file into a tree, returning the main IFD
+    /// chain starting at the first IFD. SubIFDs and all registered IFD
+    /// pointer tags (ExifIFD and GPSIFD by default) are followed, along
+    /// with the chains they lead to. Each IFD may only be reached once in
+    /// the whole tree; otherwise this fails with
+    /// `TiffReadError::IfdCycle`. Pointers nested more than 64 levels
+    /// deep result in a `ParseError`.
+    ///
+    /// The subfiles in the tree are independent of `subfiles`.
+    pub fn read_ifd_tree(&self) -> Result<Vec<IfdNode<R, S>>, TiffReadError> {
+        let mut pointer_tags = self.reader_ref.ifd_pointer_tags();
+        pointer_tags.insert(tags::SUB_IFDS);
+        
+        let mut tree_reader = TreeReader {
+            reader_ref: self.reader_ref.clone(),
+            endianness: self.endianness,
+            pointer_tags,
+            visited: BTreeSet::new(),
+        };
+        tree_reader.read_chain(self.offset_to_first_ifd, 0)
+    }
+}
+
+struct TreeReader<R, S: Sharing> {
+    reader_ref: ReaderRef<R, S>,
+    endianness: Endianness,
+    pointer_tags: BTreeSet<u16>,
+    visited: BTreeSet<u32>,
+}
+
+impl<R: Read + Seek, S: Sharing> TreeReader<R, S> {
+    /* Chains are read in a loop, so only pointers to other chains add to
+     * the recursion depth. */
+    fn read_chain(&mut self, first_offset: u32, depth: usize) -> Result<Vec<IfdNode<R, S>>, TiffReadError> {
+        if depth > MAX_TREE_DEPTH {
+            return Err(ParseError::new(format!("IFD pointers nested more than {} levels deep", MAX_TREE_DEPTH)).into());
+        }
+        
+        let mut nodes = Vec::new();
+        let mut next_offset = Some(first_offset);
+        while let Some(offset) = next_offset {
+            if !self.visited.insert(offset) {
+                return Err(TiffReadError::IfdCycle {offset});
+            }
+            
+            let mut subfile = Subfile::new(self.reader_ref.clone(), offset, self.endianness)?;
+            next_offset = subfile.offset_to_next_ifd();
+            
+            let mut children = Vec::new();
+            for tag in self.pointer_tags.clone() {
+                for child_offset in subfile.get_ifd_offsets(tag)?.unwrap_or_default() {
+                    children.push((PointerKind::from_tag(tag), self.read_chain(child_offset, depth + 1)?));
+                }
+            }
+            nodes.push(IfdNode {subfile, children});
+        }
+        Ok(nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use crate::error::TiffReadError;
+    use super::{PointerKind, MAX_TREE_DEPTH};
+    
+    fn tiff_with_sub_ifd_pointing_to(sub_ifd_offset: u8) -> Vec<u8> {
+        [
+            b"II\x2A\x00\x08\x00\x00\x00".as_ref(), // image file header, offset 8 to first IFD
+            b"\x02\x00".as_ref(), // IFD 0: number of entries (2)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x10\x00\x00\x00".as_ref(), // ImageWidth, SHORT, 16
+            b"\x69\x87\x04\x00\x01\x00\x00\x00\x26\x00\x00\x00".as_ref(), // ExifIFD, LONG, offset 38
+            b"\x38\x00\x00\x00".as_ref(), // IFD 0: offset to next IFD (56)
+            b"\x01\x00".as_ref(), // Exif IFD: number of entries (1)
+            b"\x00\x90\x07\x00\x04\x00\x00\x00\x30\x32\x33\x30".as_ref(), // ExifVersion, UNDEFINED, "0230"
+            b"\x00\x00\x00\x00".as_ref(), // Exif IFD: offset to next IFD (0 = N/A)
+            b"\x01\x00".as_ref(), // IFD 1: number of entries (1)
+            b"\x4A\x01\x04\x00\x01\x00\x00\x00".as_ref(), [sub_ifd_offset, 0, 0, 0].as_ref(), // SubIFDs, LONG, 1 offset
+            b"\x00\x00\x00\x00".as_ref(), // IFD 1: offset to next IFD (0 = N/A)
+            b"\x01\x00".as_ref(), // SubIFD: number of entries (1)
+            b"\x00\x01\x03\x00\x01\x00\x00\x00\x08\x00\x00\x00".as_ref(), // ImageWidth, SHORT, 8
+            b"\x00\x00\x00\x00".as_ref(), // SubIFD: offset to next IFD (0 = N/A)
+        ].concat()
+    }
+    
+    #[test]
+    fn read_ifd_tree() {
+        let tiff_reader = crate::TiffReader::new(Cursor::new(tiff_with_sub_ifd_pointing_to(74))).unwrap();
+        let chain = tiff_reader.read_ifd_tree().unwrap();
+        assert_eq!(chain.len(), 2);
+        
+        let kinds: Vec<PointerKind> = chain[0].children.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, vec![PointerKind::Exif]);
+        assert!(chain[0].children[0].1[0].subfile.contains(0x9000));
+        
+        assert_eq!(chain[1].children.len(), 1);
+        assert_eq!(chain[1].children[0].0, PointerKind::SubIfd);
+        assert_eq!(chain[1].children[0].1[0].subfile.ifd_byte_span(), (74, 18));
+    }
+    
+    #[test]
+    fn read_long_chain_without_deep_recursion() {
+        const IFD_COUNT: u32 = 100_000;
+        let mut tiff_bytes = b"II\x2A\x00\x08\x00\x00\x00".to_vec(); // image file header, offset 8 to first IFD
+        for i in 0..IFD_COUNT {
+            let next_offset = if i + 1 < IFD_COUNT {8 + 6*(i + 1)} else {0};
+            tiff_bytes.extend_from_slice(b"\x00\x00"); // number of entries (0)
+            tiff_bytes.extend_from_slice(&next_offset.to_le_bytes());
+        }
+        
+        let tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        let chain = tiff_reader.read_ifd_tree().unwrap();
+        assert_eq!(chain.len(), IFD_COUNT as usize);
+        assert!(chain.iter().all(|node| node.children.is_empty()));
+    }
+    
+    #[test]
+    fn reject_deeply_nested_sub_ifds() {
+        let ifd_count = MAX_TREE_DEPTH as u32 + 2;
+        let mut tiff_bytes = b"II\x2A\x00\x08\x00\x00\x00".to_vec(); // image file header, offset 8 to first IFD
+        for i in 0..ifd_count {
+            tiff_bytes.extend_from_slice(b"\x01\x00"); // number of entries (1)
+            tiff_bytes.extend_from_slice(b"\x4A\x01\x04\x00\x01\x00\x00\x00"); // SubIFDs, LONG, 1 offset
+            tiff_bytes.extend_from_slice(&(8 + 18*(i + 1)).to_le_bytes());
+            tiff_bytes.extend_from_slice(b"\x00\x00\x00\x00"); // offset to next IFD (0 = N/A)
+        }
+        tiff_bytes.extend_from_slice(b"\x00\x00\x00\x00\x00\x00"); // empty IFD at the bottom
+        
+        let tiff_reader = crate::TiffReader::new(Cursor::new(tiff_bytes)).unwrap();
+        match tiff_reader.read_ifd_tree() {
+            Err(TiffReadError::Parse(_)) => {},
+            other => panic!("Expected ParseError, got {:?}", other.map(|chain| chain.len())),
+        }
+    }
+    
+    #[test]
+    fn detect_cycle_across_tree() {
+        let tiff_reader = crate::TiffReader::new(Cursor::new(tiff_with_sub_ifd_pointing_to(8))).unwrap();
+        match tiff_reader.read_ifd_tree() {
+            Err(TiffReadError::IfdCycle {offset: 8}) => {},
+            other => panic!("Expected IfdCycle, got {:?}", other),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use num_rational::Ratio;
 use std::convert::{TryFrom, TryInto};
+use std::ffi::CStr;
 use std::fmt;
 use std::slice::ChunksExact;
 
-use crate::error::ParseError;
+use crate::error::{ParseError, TiffReadError};
 
 use FieldType::*;
 
@@ -13,12 +14,50 @@ pub enum Endianness {
     Big,
 }
 
-/* Note that this `Rational` type is not the same as the `Rational` type
- * exposed by num-rational. */
+/// An unsigned RATIONAL value, as a num-rational `Ratio<u32>`. This is
+/// not the same as num-rational's own `Rational` (which is
+/// `Ratio<isize>`).
+///
+/// Values read from a file are built with `Ratio::new_raw`, so they hold
+/// the numerator and denominator exactly as stored: they are not reduced,
+/// and the denominator may be zero. num-rational's arithmetic, comparison
+/// and `Ratio::new` assume a nonzero denominator and panic otherwise, so
+/// check `denom()` (or use `RationalExt::checked_to_f64`) before relying
+/// on them.
 pub type Rational = Ratio<u32>;
+/// A signed SRATIONAL value, as a num-rational `Ratio<i32>`. The same
+/// caveats as for `Rational` apply.
 pub type SRational = Ratio<i32>;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Conversions for `Rational` and `SRational` that are safe for the
+/// zero denominators found in real files.
+pub trait RationalExt {
+    /// Converts to `f64`, returning `None` for a zero denominator instead
+    /// of producing an infinity or NaN.
+    fn checked_to_f64(&self) -> Option<f64>;
+}
+
+impl RationalExt for Rational {
+    fn checked_to_f64(&self) -> Option<f64> {
+        if *self.denom() == 0 {
+            None
+        } else {
+            Some(f64::from(*self.numer()) / f64::from(*self.denom()))
+        }
+    }
+}
+
+impl RationalExt for SRational {
+    fn checked_to_f64(&self) -> Option<f64> {
+        if *self.denom() == 0 {
+            None
+        } else {
+            Some(f64::from(*self.numer()) / f64::from(*self.denom()))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum FieldType {
     Byte,      //  1
     Ascii,     //  2
@@ -53,6 +92,23 @@ impl FieldType {
         }
     }
     
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Byte => 1,
+            Ascii => 2,
+            Short => 3,
+            Long => 4,
+            Rational => 5,
+            SByte => 6,
+            Undefined => 7,
+            SShort => 8,
+            SLong => 9,
+            SRational => 10,
+            Float => 11,
+            Double => 12,
+        }
+    }
+    
     pub fn size_of(&self) -> usize {
         match self {
             Byte => 1,
@@ -141,6 +197,255 @@ impl FieldValue {
             FieldValue::Double(v) => v.len(),
         }
     }
+    
+    /// Returns the number of bytes the value takes up when stored in a
+    /// file. For ASCII values this includes the terminating NUL, as
+    /// written by `bytes_from_value`.
+    pub fn byte_len(&self) -> usize {
+        match self {
+            FieldValue::Ascii(s) => ascii_byte_len(s),
+            _ => self.field_type().size_of()*self.count(),
+        }
+    }
+    
+    /// Builds an UNDEFINED value, checking that the count fits in an
+    /// IFD entry.
+    pub fn undefined(bytes: Vec<u8>) -> Result<Self, TiffReadError> {
+        let value = FieldValue::Undefined(bytes);
+        value.validate()?;
+        Ok(value)
+    }
+    
+    /// Checks that the value can be stored in an IFD entry, whose count
+    /// is a u32. For ASCII values the count includes the terminating NUL.
+    pub fn validate(&self) -> Result<(), TiffReadError> {
+        let count = match self {
+            FieldValue::Ascii(s) => ascii_byte_len(s),
+            _ => self.count(),
+        };
+        
+        match u32::try_from(count) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TiffReadError::TooManyValues {count}),
+        }
+    }
+    
+    /// Returns the values widened to `u32` if this is an unsigned
+    /// integer value (BYTE, SHORT or LONG), otherwise `None`.
+    pub fn to_u32_vec(&self) -> Option<Vec<u32>> {
+        match self {
+            FieldValue::Byte(v) => Some(v.iter().map(|&x| u32::from(x)).collect()),
+            FieldValue::Short(v) => Some(v.iter().map(|&x| u32::from(x)).collect()),
+            FieldValue::Long(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+    
+    /// Returns the values converted to `f64` if this is a numeric value
+    /// (anything but ASCII and UNDEFINED), otherwise `None`. Rationals
+    /// with a zero denominator become NaN.
+    pub fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        match self {
+            FieldValue::Byte(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::Short(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::Long(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::Rational(v) => Some(v.iter().map(|x| x.checked_to_f64().unwrap_or(f64::NAN)).collect()),
+            FieldValue::SByte(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::SShort(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::SLong(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::SRational(v) => Some(v.iter().map(|x| x.checked_to_f64().unwrap_or(f64::NAN)).collect()),
+            FieldValue::Float(v) => Some(v.iter().map(|&x| f64::from(x)).collect()),
+            FieldValue::Double(v) => Some(v.clone()),
+            FieldValue::Ascii(_) | FieldValue::Undefined(_) => None,
+        }
+    }
+    
+    /// Compares two values, allowing FLOAT, DOUBLE, RATIONAL and
+    /// SRATIONAL values to differ by up to `epsilon`. NaN is taken to
+    /// equal NaN, and rationals are compared by value, so 1/2 is close to
+    /// 2/4. Rationals with a zero denominator only equal the exact same
+    /// numerator and denominator. All other types are compared exactly,
+    /// and values of different types are never equal.
+    pub fn approx_eq(&self, other: &FieldValue, epsilon: f64) -> bool {
+        fn close(a: f64, b: f64, epsilon: f64) -> bool {
+            (a.is_nan() && b.is_nan()) || (a - b).abs() <= epsilon
+        }
+        fn all_close<T>(a: &[T], b: &[T], is_close: impl Fn(&T, &T) -> bool) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_close(a, b))
+        }
+        
+        match (self, other) {
+            (FieldValue::Float(a), FieldValue::Float(b)) => all_close(a, b, |&a, &b| close(f64::from(a), f64::from(b), epsilon)),
+            (FieldValue::Double(a), FieldValue::Double(b)) => all_close(a, b, |&a, &b| close(a, b, epsilon)),
+            (FieldValue::Rational(a), FieldValue::Rational(b)) => all_close(a, b, |a, b| match (a.checked_to_f64(), b.checked_to_f64()) {
+                (Some(a), Some(b)) => close(a, b, epsilon),
+                _ => a.numer() == b.numer() && a.denom() == b.denom(),
+            }),
+            (FieldValue::SRational(a), FieldValue::SRational(b)) => all_close(a, b, |a, b| match (a.checked_to_f64(), b.checked_to_f64()) {
+                (Some(a), Some(b)) => close(a, b, epsilon),
+                _ => a.numer() == b.numer() && a.denom() == b.denom(),
+            }),
+            _ => self == other,
+        }
+    }
+    
+    /// Borrows an ASCII value as a C string. Returns `None` for other
+    /// types, and unless the value ends in a NUL byte with no other NUL
+    /// before it, as the spec requires of ASCII values. Values read from
+    /// a file keep their terminator, so this checks them as stored.
+    pub fn as_cstr(&self) -> Option<&CStr> {
+        match self {
+            FieldValue::Ascii(text) => CStr::from_bytes_with_nul(text.as_bytes()).ok(),
+            _ => None,
+        }
+    }
+    
+    /// Returns a borrowed view of the value.
+    pub fn as_value_ref(&self) -> FieldValueRef<'_> {
+        match self {
+            FieldValue::Byte(v) => FieldValueRef::Byte(v),
+            FieldValue::Ascii(s) => FieldValueRef::Ascii(s),
+            FieldValue::Short(v) => FieldValueRef::Short(v),
+            FieldValue::Long(v) => FieldValueRef::Long(v),
+            FieldValue::Rational(v) => FieldValueRef::Rational(v),
+            FieldValue::SByte(v) => FieldValueRef::SByte(v),
+            FieldValue::Undefined(v) => FieldValueRef::Undefined(v),
+            FieldValue::SShort(v) => FieldValueRef::SShort(v),
+            FieldValue::SLong(v) => FieldValueRef::SLong(v),
+            FieldValue::SRational(v) => FieldValueRef::SRational(v),
+            FieldValue::Float(v) => FieldValueRef::Float(v),
+            FieldValue::Double(v) => FieldValueRef::Double(v),
+        }
+    }
+}
+
+/// A borrowed view of a `FieldValue`, for passing values around without
+/// cloning their contents.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldValueRef<'a> {
+    Byte(&'a [u8]),
+    Ascii(&'a str),
+    Short(&'a [u16]),
+    Long(&'a [u32]),
+    Rational(&'a [Rational]),
+    SByte(&'a [i8]),
+    Undefined(&'a [u8]),
+    SShort(&'a [i16]),
+    SLong(&'a [i32]),
+    SRational(&'a [SRational]),
+    Float(&'a [f32]),
+    Double(&'a [f64]),
+}
+
+impl FieldValueRef<'_> {
+    pub fn field_type(&self) -> FieldType {
+        match self {
+            FieldValueRef::Byte(_) => FieldType::Byte,
+            FieldValueRef::Ascii(_) => FieldType::Ascii,
+            FieldValueRef::Short(_) => FieldType::Short,
+            FieldValueRef::Long(_) => FieldType::Long,
+            FieldValueRef::Rational(_) => FieldType::Rational,
+            FieldValueRef::SByte(_) => FieldType::SByte,
+            FieldValueRef::Undefined(_) => FieldType::Undefined,
+            FieldValueRef::SShort(_) => FieldType::SShort,
+            FieldValueRef::SLong(_) => FieldType::SLong,
+            FieldValueRef::SRational(_) => FieldType::SRational,
+            FieldValueRef::Float(_) => FieldType::Float,
+            FieldValueRef::Double(_) => FieldType::Double,
+        }
+    }
+    
+    pub fn count(&self) -> usize {
+        match self {
+            FieldValueRef::Byte(v) => v.len(),
+            FieldValueRef::Ascii(s) => s.len(),
+            FieldValueRef::Short(v) => v.len(),
+            FieldValueRef::Long(v) => v.len(),
+            FieldValueRef::Rational(v) => v.len(),
+            FieldValueRef::SByte(v) => v.len(),
+            FieldValueRef::Undefined(v) => v.len(),
+            FieldValueRef::SShort(v) => v.len(),
+            FieldValueRef::SLong(v) => v.len(),
+            FieldValueRef::SRational(v) => v.len(),
+            FieldValueRef::Float(v) => v.len(),
+            FieldValueRef::Double(v) => v.len(),
+        }
+    }
+    
+    /// Copies the viewed values into an owned `FieldValue`.
+    pub fn to_owned_value(&self) -> FieldValue {
+        match *self {
+            FieldValueRef::Byte(v) => FieldValue::Byte(v.to_vec()),
+            FieldValueRef::Ascii(s) => FieldValue::Ascii(s.to_string()),
+            FieldValueRef::Short(v) => FieldValue::Short(v.to_vec()),
+            FieldValueRef::Long(v) => FieldValue::Long(v.to_vec()),
+            FieldValueRef::Rational(v) => FieldValue::Rational(v.to_vec()),
+            FieldValueRef::SByte(v) => FieldValue::SByte(v.to_vec()),
+            FieldValueRef::Undefined(v) => FieldValue::Undefined(v.to_vec()),
+            FieldValueRef::SShort(v) => FieldValue::SShort(v.to_vec()),
+            FieldValueRef::SLong(v) => FieldValue::SLong(v.to_vec()),
+            FieldValueRef::SRational(v) => FieldValue::SRational(v.to_vec()),
+            FieldValueRef::Float(v) => FieldValue::Float(v.to_vec()),
+            FieldValueRef::Double(v) => FieldValue::Double(v.to_vec()),
+        }
+    }
+}
+
+/* Builds a `FieldValue` from a vector or slice of the corresponding
+ * element type, checking that the count fits in an IFD entry. */
+macro_rules! impl_checked_from {
+    ($element_type:ty, $variant:ident) => {
+        impl TryFrom<Vec<$element_type>> for FieldValue {
+            type Error = TiffReadError;
+            
+            fn try_from(values: Vec<$element_type>) -> Result<Self, Self::Error> {
+                let value = FieldValue::$variant(values);
+                value.validate()?;
+                Ok(value)
+            }
+        }
+        
+        impl TryFrom<&[$element_type]> for FieldValue {
+            type Error = TiffReadError;
+            
+            fn try_from(values: &[$element_type]) -> Result<Self, Self::Error> {
+                FieldValue::try_from(values.to_vec())
+            }
+        }
+    }
+}
+
+/* BYTE is assumed for u8 values; use `FieldValue::undefined` for
+ * UNDEFINED. */
+impl_checked_from!(u8, Byte);
+impl_checked_from!(u16, Short);
+impl_checked_from!(u32, Long);
+impl_checked_from!(Rational, Rational);
+impl_checked_from!(i8, SByte);
+impl_checked_from!(i16, SShort);
+impl_checked_from!(i32, SLong);
+impl_checked_from!(SRational, SRational);
+impl_checked_from!(f32, Float);
+impl_checked_from!(f64, Double);
+
+impl TryFrom<&str> for FieldValue {
+    type Error = TiffReadError;
+    
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        let value = FieldValue::Ascii(text.to_string());
+        value.validate()?;
+        Ok(value)
+    }
+}
+
+/* ASCII values read from a file already end in their NUL, values built
+ * from a `&str` get one when written. */
+fn ascii_byte_len(text: &str) -> usize {
+    if text.ends_with('\0') {
+        text.len()
+    } else {
+        text.len().saturating_add(1)
+    }
 }
 
 fn rational_from_le_bytes(bytes: [u8; 8]) -> Rational {
@@ -191,6 +496,17 @@ pub fn value_from_buffer(field_type: FieldType, count: u32, buffer: &[u8], endia
 }
 
 fn value_from_chunks(field_type: FieldType, chunks: ChunksExact<u8>, endianness: Endianness) -> FieldValue {
+    /* The endianness is matched once, outside the loop, so each arm
+     * collects through a concrete iterator. */
+    macro_rules! decode {
+        ($from_le_bytes:expr, $from_be_bytes:expr) => {
+            match endianness {
+                Endianness::Little => chunks.map(|chunk_bytes| $from_le_bytes(chunk_bytes.try_into().unwrap())).collect(),
+                Endianness::Big => chunks.map(|chunk_bytes| $from_be_bytes(chunk_bytes.try_into().unwrap())).collect(),
+            }
+        }
+    }
+    
     /* The BYTE, ASCII, SBYTE and UNDEFINED data types are not endian-
      * sensitive. */
     match field_type {
@@ -198,77 +514,159 @@ fn value_from_chunks(field_type: FieldType, chunks: ChunksExact<u8>, endianness:
         Ascii => {
             let byte_vec: Vec<u8> = chunks.map(|chunk| chunk[0]).collect();
             
-            /* Handle field value as null-terminated string, even if the
-             * null byte is not the last one. */
-            let relevant_slice = &byte_vec[..byte_vec.iter().position(|&byte| byte == 0).unwrap_or(byte_vec.len())];
-            
-            FieldValue::Ascii(String::from_utf8_lossy(&relevant_slice).to_string())
-        }
-        Short => {
-            let values_iter: Box<dyn Iterator<Item = u16>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| u16::from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| u16::from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::Short(values_iter.collect())
-        }
-        Long => {
-            let values_iter: Box<dyn Iterator<Item = u32>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| u32::from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| u32::from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::Long(values_iter.collect())
-        }
-        Rational => {
-            let values_iter: Box<dyn Iterator<Item = Rational>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| rational_from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| rational_from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::Rational(values_iter.collect())
+            /* The value is kept as stored, NUL bytes included, so that
+             * `FieldValue::as_cstr` can check the terminator and a
+             * value with several strings is written back intact.
+             * `Subfile::get_ascii` cuts it at the first NUL. */
+            FieldValue::Ascii(String::from_utf8_lossy(&byte_vec).into_owned())
         }
+        Short => FieldValue::Short(decode!(u16::from_le_bytes, u16::from_be_bytes)),
+        Long => FieldValue::Long(decode!(u32::from_le_bytes, u32::from_be_bytes)),
+        Rational => FieldValue::Rational(decode!(rational_from_le_bytes, rational_from_be_bytes)),
         SByte => FieldValue::SByte(chunks.map(|chunk| chunk[0] as i8).collect()),
         Undefined => FieldValue::Undefined(chunks.map(|chunk| chunk[0]).collect()),
-        SShort => {
-            let values_iter: Box<dyn Iterator<Item = i16>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| i16::from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| i16::from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::SShort(values_iter.collect())
-        }
-        SLong => {
-            let values_iter: Box<dyn Iterator<Item = i32>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| i32::from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| i32::from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::SLong(values_iter.collect())
-        }
-        SRational => {
-            let values_iter: Box<dyn Iterator<Item = SRational>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| srational_from_le_bytes(chunk_bytes.try_into().unwrap()))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| srational_from_be_bytes(chunk_bytes.try_into().unwrap()))),
-            };
-            
-            FieldValue::SRational(values_iter.collect())
+        SShort => FieldValue::SShort(decode!(i16::from_le_bytes, i16::from_be_bytes)),
+        SLong => FieldValue::SLong(decode!(i32::from_le_bytes, i32::from_be_bytes)),
+        SRational => FieldValue::SRational(decode!(srational_from_le_bytes, srational_from_be_bytes)),
+        /* Go through the bit patterns so NaN payloads survive. */
+        Float => FieldValue::Float(decode!(|bytes| f32::from_bits(u32::from_le_bytes(bytes)), |bytes| f32::from_bits(u32::from_be_bytes(bytes)))),
+        Double => FieldValue::Double(decode!(|bytes| f64::from_bits(u64::from_le_bytes(bytes)), |bytes| f64::from_bits(u64::from_be_bytes(bytes)))),
+    }
+}
+
+/// Encodes a value as it would be stored in a file with the given byte
+/// order. ASCII values get a terminating NUL byte unless they already
+/// end in one.
+pub fn bytes_from_value(value: &FieldValue, endianness: Endianness) -> Vec<u8> {
+    macro_rules! encode {
+        ($values:expr, $to_le_bytes:expr, $to_be_bytes:expr) => {
+            match endianness {
+                Endianness::Little => $values.iter().flat_map($to_le_bytes).collect(),
+                Endianness::Big => $values.iter().flat_map($to_be_bytes).collect(),
+            }
         }
-        Float => {
-            let values_iter: Box<dyn Iterator<Item = f32>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| f32::from_bits(u32::from_le_bytes(chunk_bytes.try_into().unwrap())))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| f32::from_bits(u32::from_be_bytes(chunk_bytes.try_into().unwrap())))),
-            };
-            
-            FieldValue::Float(values_iter.collect())
+    }
+    
+    match value {
+        FieldValue::Byte(values) => values.clone(),
+        FieldValue::Ascii(text) if text.ends_with('\0') => text.as_bytes().to_vec(),
+        FieldValue::Ascii(text) => [text.as_bytes(), b"\0"].concat(),
+        FieldValue::Short(values) => encode!(values, |value: &u16| value.to_le_bytes().to_vec(), |value: &u16| value.to_be_bytes().to_vec()),
+        FieldValue::Long(values) => encode!(values, |value: &u32| value.to_le_bytes().to_vec(), |value: &u32| value.to_be_bytes().to_vec()),
+        FieldValue::Rational(values) => encode!(
+            values,
+            |value: &Rational| [value.numer().to_le_bytes(), value.denom().to_le_bytes()].concat(),
+            |value: &Rational| [value.numer().to_be_bytes(), value.denom().to_be_bytes()].concat()
+        ),
+        FieldValue::SByte(values) => values.iter().map(|&value| value as u8).collect(),
+        FieldValue::Undefined(values) => values.clone(),
+        FieldValue::SShort(values) => encode!(values, |value: &i16| value.to_le_bytes().to_vec(), |value: &i16| value.to_be_bytes().to_vec()),
+        FieldValue::SLong(values) => encode!(values, |value: &i32| value.to_le_bytes().to_vec(), |value: &i32| value.to_be_bytes().to_vec()),
+        FieldValue::SRational(values) => encode!(
+            values,
+            |value: &SRational| [value.numer().to_le_bytes(), value.denom().to_le_bytes()].concat(),
+            |value: &SRational| [value.numer().to_be_bytes(), value.denom().to_be_bytes()].concat()
+        ),
+        /* Go through the bit patterns so NaN payloads survive. */
+        FieldValue::Float(values) => encode!(values, |value: &f32| value.to_bits().to_le_bytes().to_vec(), |value: &f32| value.to_bits().to_be_bytes().to_vec()),
+        FieldValue::Double(values) => encode!(values, |value: &f64| value.to_bits().to_le_bytes().to_vec(), |value: &f64| value.to_bits().to_be_bytes().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn checked_field_value_constructors() {
+        assert_eq!(FieldValue::try_from(vec![1u16, 2, 3]).unwrap(), FieldValue::Short(vec![1, 2, 3]));
+        assert_eq!(FieldValue::try_from(&[1u32, 2][..]).unwrap(), FieldValue::Long(vec![1, 2]));
+        assert_eq!(FieldValue::try_from(vec![-1i8]).unwrap(), FieldValue::SByte(vec![-1]));
+        assert_eq!(FieldValue::try_from("lazytiff").unwrap(), FieldValue::Ascii("lazytiff".to_string()));
+        assert_eq!(FieldValue::undefined(vec![0xFF]).unwrap(), FieldValue::Undefined(vec![0xFF]));
+        assert!(FieldValue::Double(vec![1.5]).validate().is_ok());
+    }
+    
+    #[test]
+    fn encode_and_decode_values() {
+        let values = [
+            FieldValue::Short(vec![1, 0x1234]),
+            FieldValue::Rational(vec![Ratio::new_raw(300, 1)]),
+            FieldValue::SLong(vec![-2]),
+            FieldValue::Double(vec![0.25]),
+            FieldValue::Ascii("a\0b\0".to_string()),
+        ];
+        for value in &values {
+            for &endianness in &[Endianness::Little, Endianness::Big] {
+                let bytes = bytes_from_value(value, endianness);
+                assert_eq!(&value_from_buffer(value.field_type(), value.count() as u32, &bytes, endianness).unwrap(), value);
+                assert_eq!(value.byte_len(), bytes.len());
+            }
         }
-        Double => {
-            let values_iter: Box<dyn Iterator<Item = f64>> = match endianness {
-                Endianness::Little => Box::new(chunks.map(|chunk_bytes| f64::from_bits(u64::from_le_bytes(chunk_bytes.try_into().unwrap())))),
-                Endianness::Big => Box::new(chunks.map(|chunk_bytes| f64::from_bits(u64::from_be_bytes(chunk_bytes.try_into().unwrap())))),
-            };
+        assert_eq!(bytes_from_value(&FieldValue::Short(vec![0x1234]), Endianness::Big), vec![0x12, 0x34]);
+        assert_eq!(bytes_from_value(&FieldValue::Ascii("ab".to_string()), Endianness::Little), b"ab\0".to_vec());
+        assert_eq!(FieldValue::Ascii("ab".to_string()).byte_len(), 3);
+    }
+    
+    #[test]
+    fn borrow_ascii_values_as_c_strings() {
+        assert_eq!(FieldValue::Ascii("ab\0".to_string()).as_cstr(), CStr::from_bytes_with_nul(b"ab\0").ok());
+        assert_eq!(FieldValue::Ascii("ab".to_string()).as_cstr(), None);
+        assert_eq!(FieldValue::Ascii("a\0b\0".to_string()).as_cstr(), None);
+        assert_eq!(FieldValue::Undefined(b"ab\0".to_vec()).as_cstr(), None);
+    }
+    
+    #[test]
+    fn float_bit_patterns_survive_round_trip() {
+        /* A signaling NaN, a quiet NaN with a payload and the smallest
+         * subnormal. NaN never compares equal, so the bits are compared. */
+        let float_bits = [0x7F80_0001u32, 0x7FC0_1234, 0x0000_0001];
+        let double_bits = [0x7FF0_0000_0000_0001u64, 0x7FF8_0000_0000_1234, 0x0000_0000_0000_0001];
+        for &endianness in &[Endianness::Little, Endianness::Big] {
+            let float_bytes: Vec<u8> = float_bits.iter().flat_map(|bits| match endianness {
+                Endianness::Little => bits.to_le_bytes(),
+                Endianness::Big => bits.to_be_bytes(),
+            }).collect();
+            let value = value_from_buffer(Float, 3, &float_bytes, endianness).unwrap();
+            assert_eq!(bytes_from_value(&value, endianness), float_bytes);
+            match value_from_buffer(Float, 3, &bytes_from_value(&value, endianness), endianness).unwrap() {
+                FieldValue::Float(values) => assert_eq!(values.iter().map(|value| value.to_bits()).collect::<Vec<_>>(), float_bits),
+                other => panic!("Expected FLOAT values, got {:?}", other),
+            }
             
-            FieldValue::Double(values_iter.collect())
+            let double_bytes: Vec<u8> = double_bits.iter().flat_map(|bits| match endianness {
+                Endianness::Little => bits.to_le_bytes(),
+                Endianness::Big => bits.to_be_bytes(),
+            }).collect();
+            let value = value_from_buffer(Double, 3, &double_bytes, endianness).unwrap();
+            assert_eq!(bytes_from_value(&value, endianness), double_bytes);
+            match value_from_buffer(Double, 3, &bytes_from_value(&value, endianness), endianness).unwrap() {
+                FieldValue::Double(values) => assert_eq!(values.iter().map(|value| value.to_bits()).collect::<Vec<_>>(), double_bits),
+                other => panic!("Expected DOUBLE values, got {:?}", other),
+            }
         }
     }
+    
+    #[test]
+    fn compare_values_approximately() {
+        assert!(FieldValue::Float(vec![0.1, f32::NAN]).approx_eq(&FieldValue::Float(vec![0.100_001, f32::NAN]), 1e-5));
+        assert!(!FieldValue::Double(vec![0.1]).approx_eq(&FieldValue::Double(vec![0.2]), 1e-5));
+        assert!(!FieldValue::Double(vec![0.1]).approx_eq(&FieldValue::Double(vec![0.1, 0.1]), 1e-5));
+        assert!(FieldValue::Rational(vec![Ratio::new_raw(1, 2)]).approx_eq(&FieldValue::Rational(vec![Ratio::new_raw(2, 4)]), 0.0));
+        assert!(!FieldValue::Rational(vec![Ratio::new_raw(1, 0)]).approx_eq(&FieldValue::Rational(vec![Ratio::new_raw(2, 0)]), 1.0));
+        assert!(FieldValue::SRational(vec![Ratio::new_raw(-1, 3)]).approx_eq(&FieldValue::SRational(vec![Ratio::new_raw(-333, 1000)]), 1e-3));
+        assert!(!FieldValue::Short(vec![1]).approx_eq(&FieldValue::Short(vec![2]), 10.0));
+        assert!(!FieldValue::Short(vec![1]).approx_eq(&FieldValue::Long(vec![1]), 10.0));
+    }
+    
+    #[test]
+    fn checked_rational_conversion() {
+        assert_eq!(Rational::new_raw(3, 4).checked_to_f64(), Some(0.75));
+        assert_eq!(Rational::new_raw(3, 0).checked_to_f64(), None);
+        assert_eq!(SRational::new_raw(-1, 2).checked_to_f64(), Some(-0.5));
+        assert_eq!(SRational::new_raw(0, 0).checked_to_f64(), None);
+        
+        /* Values are kept as stored, not reduced. */
+        assert_eq!(*Rational::new_raw(300, 100).numer(), 300);
+    }
 }
@@ -0,0 +1,300 @@
+use std::convert::TryFrom;
+use std::io::{Read, Seek, Write};
+
+use crate::error::{ParseError, TiffReadError};
+use crate::reader::Sharing;
+use crate::subfile::Subfile;
+use crate::types::{bytes_from_value, Endianness, FieldType};
+use crate::{tags, TiffReader};
+
+pub(crate) enum EntryData {
+    Bytes(Vec<u8>),
+    /* Offsets of the IFD's image data segments, filled in once the
+     * layout is known. */
+    SegmentOffsets,
+}
+
+pub(crate) struct IfdEntry {
+    pub tag: u16,
+    pub field_type_raw: u16,
+    pub count: u32,
+    pub data: EntryData,
+}
+
+/// One IFD to be written, with its entries and the strips or tiles its
+/// `EntryData::SegmentOffsets` entry points to.
+#[derive(Default)]
+pub(crate) struct IfdLayout {
+    pub entries: Vec<IfdEntry>,
+    pub segments: Vec<Vec<u8>>,
+}
+
+/// Writes the header of a classic TIFF file, with the first IFD right
+/// after it at offset 8.
+pub(crate) fn write_header<W: Write>(out: &mut W, endianness: Endianness) -> Result<(), TiffReadError> {
+    match endianness {
+        Endianness::Little => out.write_all(b"II\x2A\x00\x08\x00\x00\x00")?,
+        Endianness::Big => out.write_all(b"MM\x00\x2A\x00\x00\x00\x08")?,
+    }
+    Ok(())
+}
+
+/// Writes an IFD at `position`, followed by its out-of-line values and
+/// then its segments, all starting on word boundaries. Returns the
+/// position right after the IFD's data, where the next IFD would go.
+///
+/// `position` must be even and equal to the number of bytes written
+/// since the header.
+pub(crate) fn write_ifd<W: Write>(out: &mut W, endianness: Endianness, mut ifd: IfdLayout, position: u64, is_last: bool) -> Result<u64, TiffReadError> {
+    let u16_bytes = |value: u16| match endianness {
+        Endianness::Little => value.to_le_bytes(),
+        Endianness::Big => value.to_be_bytes(),
+    };
+    let u32_bytes = |value: u32| match endianness {
+        Endianness::Little => value.to_le_bytes(),
+        Endianness::Big => value.to_be_bytes(),
+    };
+    let offset_u32 = |offset: u64| if offset <= u64::from(u32::MAX) {
+        Ok(offset as u32)
+    } else {
+        Err(crate::error::ParseError::new(format!("Offset {} doesn't fit in a classic TIFF", offset)))
+    };
+    
+    ifd.entries.sort_by_key(|entry| entry.tag);
+    
+    /* Work out where everything goes before writing, since the entries
+     * need the offsets of the data that follows them. */
+    let mut end = position + 2 + 12*ifd.entries.len() as u64 + 4;
+    let mut value_positions = Vec::new();
+    for entry in &ifd.entries {
+        let len = match &entry.data {
+            EntryData::Bytes(bytes) => bytes.len(),
+            EntryData::SegmentOffsets => 4*ifd.segments.len(),
+        } as u64;
+        if len > 4 {
+            value_positions.push(Some(end));
+            end += len + len % 2;
+        } else {
+            value_positions.push(None);
+        }
+    }
+    let mut segment_offsets = Vec::new();
+    for segment in &ifd.segments {
+        segment_offsets.push(offset_u32(end)?);
+        end += segment.len() as u64 + segment.len() as u64 % 2;
+    }
+    let next_ifd_offset = if is_last {0} else {offset_u32(end)?};
+    
+    let mut ifd_bytes = u16_bytes(ifd.entries.len() as u16).to_vec();
+    let mut value_area = Vec::new();
+    for (entry, value_position) in ifd.entries.iter().zip(value_positions) {
+        let data = match &entry.data {
+            EntryData::Bytes(bytes) => bytes.clone(),
+            EntryData::SegmentOffsets => segment_offsets.iter().flat_map(|&offset| u32_bytes(offset).to_vec()).collect(),
+        };
+        ifd_bytes.extend(&u16_bytes(entry.tag));
+        ifd_bytes.extend(&u16_bytes(entry.field_type_raw));
+        ifd_bytes.extend(&u32_bytes(entry.count));
+        match value_position {
+            Some(value_position) => {
+                ifd_bytes.extend(&u32_bytes(offset_u32(value_position)?));
+                value_area.extend(&data);
+                if data.len() % 2 == 1 {
+                    value_area.push(0);
+                }
+            }
+            None => {
+                let mut inline = data;
+                inline.resize(4, 0);
+                ifd_bytes.extend(&inline);
+            }
+        }
+    }
+    ifd_bytes.extend(&u32_bytes(next_ifd_offset));
+    
+    out.write_all(&ifd_bytes)?;
+    out.write_all(&value_area)?;
+    for segment in &ifd.segments {
+        out.write_all(segment)?;
+        if segment.len() % 2 == 1 {
+            out.write_all(&[0])?;
+        }
+    }
+    
+    Ok(end)
+}
+
+/* Tags whose values are offsets to data that isn't copied over by
+ * `rewrite_as`, so they would point at garbage in the new file. */
+const UNRELOCATED_OFFSET_TAGS: [u16; 8] = [
+    tags::FREE_OFFSETS,
+    tags::FREE_BYTE_COUNTS,
+    tags::SUB_IFDS,
+    tags::JPEG_INTERCHANGE_FORMAT,
+    tags::JPEG_INTERCHANGE_FORMAT_LENGTH,
+    tags::JPEG_Q_TABLES,
+    tags::JPEG_DC_TABLES,
+    tags::JPEG_AC_TABLES,
+];
+
+impl<R: Read + Seek, S: Sharing> TiffReader<R, S> {
+    /// Writes an equivalent classic TIFF file in the given byte order.
+    /// Field values are re-encoded and strip or tile data is copied, with
+    /// the bytes of 16, 32 and 64-bit samples swapped if the byte order
+    /// changes. Reads all IFDs first if that hasn't been done yet.
+    ///
+    /// Fields of unknown type are dropped, as are fields pointing to data
+    /// that isn't copied: registered IFD pointers, SubIFDs, FreeOffsets
+    /// and the old-style JPEG offsets. Image data is held in memory one
+    /// IFD at a time.
+    pub fn rewrite_as(&mut self, endianness: Endianness, mut out: impl Write + Seek) -> Result<(), TiffReadError> {
+        if self.subfiles.is_empty() {
+            self.read_all_ifds()?;
+        }
+        
+        write_header(&mut out, endianness)?;
+        let mut position = 8;
+        let subfile_count = self.subfiles.len();
+        let ifd_pointer_tags = self.reader_ref.ifd_pointer_tags();
+        for (index, subfile) in self.subfiles.iter_mut().enumerate() {
+            let ifd = rewritten_ifd(subfile, endianness, |tag| ifd_pointer_tags.contains(&tag) || UNRELOCATED_OFFSET_TAGS.contains(&tag))?;
+            position = write_ifd(&mut out, endianness, ifd, position, index + 1 == subfile_count)?;
+        }
+        
+        Ok(())
+    }
+}
+
+fn rewritten_ifd<R: Read + Seek, S: Sharing>(subfile: &mut Subfile<R, S>, endianness: Endianness, is_dropped: impl Fn(u16) -> bool) -> Result<IfdLayout, TiffReadError> {
+    let (offsets_tag, byte_counts_tag) = if subfile.get_field(tags::TILE_OFFSETS).is_some() {
+        (tags::TILE_OFFSETS, tags::TILE_BYTE_COUNTS)
+    } else {
+        (tags::STRIP_OFFSETS, tags::STRIP_BYTE_COUNTS)
+    };
+    
+    let mut ifd = IfdLayout::default();
+    let tags: Vec<u16> = subfile.fields().map(|(tag, _)| tag).collect();
+    for tag in tags {
+        let field_type = match subfile.get_field(tag).unwrap().field_type() {
+            Some(field_type) => field_type,
+            None => continue,
+        };
+        if is_dropped(tag) {
+            continue;
+        }
+        
+        if tag == offsets_tag {
+            /* The count is filled in once the segments have been read. */
+            ifd.entries.push(IfdEntry {tag, field_type_raw: FieldType::Long.to_u16(), count: 0, data: EntryData::SegmentOffsets});
+            continue;
+        }
+        
+        /* Single-byte types don't depend on byte order, so their raw bytes
+         * are copied as is. This also keeps everything after the first
+         * NUL in ASCII fields. */
+        let data = if field_type.size_of() == 1 {
+            subfile.raw_value_bytes(tag)?.unwrap()
+        } else {
+            bytes_from_value(subfile.get_value(tag)?.unwrap(), endianness)
+        };
+        let count = (data.len()/field_type.size_of()) as u32;
+        ifd.entries.push(IfdEntry {tag, field_type_raw: field_type.to_u16(), count, data: EntryData::Bytes(data)});
+    }
+    
+    let offsets = subfile.get_u32_values(offsets_tag)?.unwrap_or_default();
+    let byte_counts = subfile.get_u32_values(byte_counts_tag)?.unwrap_or_default();
+    let sample_size = if offsets.is_empty() || endianness == subfile.endianness() {1} else {swapped_sample_size(subfile)?};
+    for (&offset, &byte_count) in offsets.iter().zip(&byte_counts) {
+        let mut segment = subfile.read_bytes_at(u64::from(offset), byte_count as usize)?;
+        if sample_size > 1 {
+            for sample in segment.chunks_exact_mut(sample_size) {
+                sample.reverse();
+            }
+        }
+        ifd.segments.push(segment);
+    }
+    for entry in &mut ifd.entries {
+        if let EntryData::SegmentOffsets = entry.data {
+            entry.count = ifd.segments.len() as u32;
+        }
+    }
+    
+    Ok(ifd)
+}
+
+/// Returns the size in bytes of the samples whose byte order has to be
+/// swapped, or 1 if the image data doesn't depend on byte order.
+fn swapped_sample_size<R: Read + Seek, S: Sharing>(subfile: &mut Subfile<R, S>) -> Result<usize, TiffReadError> {
+    let bits_per_sample = subfile.bits_per_sample()?;
+    let bits = match bits_per_sample.first() {
+        Some(&bits) => bits,
+        None => return Err(ParseError::new("BitsPerSample has no values".to_string()).into()),
+    };
+    if bits_per_sample.iter().all(|&other_bits| other_bits <= 8) {
+        return Ok(1);
+    }
+    if bits_per_sample.iter().any(|&other_bits| other_bits != bits) || ![16, 32, 64].contains(&bits) {
+        return Err(TiffReadError::UnsupportedBitsPerSample {bits_per_sample: bits});
+    }
+    
+    /* Swapping is only possible on uncompressed samples, and the
+     * floating point predictor stores samples as separate byte planes. */
+    let compression = subfile.get_u32(tags::COMPRESSION)?.unwrap_or(1);
+    if compression != 1 {
+        return Err(TiffReadError::UnsupportedCompression {compression: u16::try_from(compression).unwrap_or(u16::MAX)});
+    }
+    let predictor = subfile.predictor()?;
+    if predictor == 3 {
+        return Err(TiffReadError::UnsupportedPredictor {predictor, bits_per_sample: bits});
+    }
+    
+    Ok(usize::from(bits/8))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    
+    use crate::error::TiffReadError;
+    use crate::test_util::TiffBuilder;
+    use crate::types::{Endianness, FieldValue};
+    
+    #[test]
+    fn rewrite_in_other_byte_order() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![2]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(258, FieldValue::Short(vec![16]))
+            .entry(282, FieldValue::Rational(vec![crate::Rational::new_raw(300, 1)]))
+            .entry(333, FieldValue::Ascii("Cyan\0Black".to_string()))
+            .strips(vec![vec![0x01, 0x02, 0x03, 0x04]])
+            .reader();
+        
+        let mut out = Cursor::new(Vec::new());
+        tiff_reader.rewrite_as(Endianness::Big, &mut out).unwrap();
+        let mut rewritten = crate::TiffReader::new(Cursor::new(out.into_inner())).unwrap();
+        rewritten.read_all_ifds().unwrap();
+        
+        let subfile = &mut rewritten.subfiles[0];
+        assert_eq!(subfile.endianness(), Endianness::Big);
+        assert_eq!(subfile.image_width().unwrap(), 2);
+        assert_eq!(subfile.get_value(282).unwrap(), Some(&FieldValue::Rational(vec![crate::Rational::new_raw(300, 1)])));
+        assert_eq!(subfile.ink_names().unwrap(), Some(vec!["Cyan".to_string(), "Black".to_string()]));
+        assert_eq!(subfile.read_strip(0).unwrap(), vec![0x02, 0x01, 0x04, 0x03]);
+    }
+    
+    #[test]
+    fn reject_zero_samples_per_pixel() {
+        let mut tiff_reader = TiffBuilder::new(Endianness::Little)
+            .entry(256, FieldValue::Short(vec![1]))
+            .entry(257, FieldValue::Short(vec![1]))
+            .entry(277, FieldValue::Short(vec![0]))
+            .strips(vec![vec![0x01, 0x02]])
+            .reader();
+        
+        match tiff_reader.rewrite_as(Endianness::Big, Cursor::new(Vec::new())) {
+            Err(TiffReadError::Parse(_)) => {},
+            other => panic!("Expected ParseError, got {:?}", other),
+        }
+    }
+}